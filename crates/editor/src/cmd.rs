@@ -1,12 +1,19 @@
 use std::collections::HashMap;
 
-use crate::states::{cursor::EditorCursor, mode::EditorMode, EditorState};
+use crate::states::{
+    cursor::EditorCursor, mode::EditorMode, operator::TextRange, options::EditorOptions,
+    EditorState,
+};
 
 pub type Command = Box<dyn Fn(&mut EditorState)>;
-type CommandMap = HashMap<String, Command>;
+pub type ArgCommand = Box<dyn Fn(&mut EditorState, &str)>;
+pub type OperatorCommand = Box<dyn Fn(&mut EditorState, TextRange)>;
+type CommandMap = HashMap<String, ArgCommand>;
+type OperatorMap = HashMap<String, OperatorCommand>;
 
 pub struct EditorCommand {
     cmds: CommandMap,
+    ops: OperatorMap,
 }
 
 impl EditorCommand {
@@ -15,40 +22,139 @@ impl EditorCommand {
     }
 
     pub fn register(&mut self, cmd: &str, f: Command) {
-        self.cmds.insert(cmd.to_string(), f);
+        self.register_with_args(cmd, Box::new(move |editor, _| f(editor)));
+    }
+
+    pub fn register_with_args(&mut self, cmd: &str, f: ArgCommand) {
+        if self.cmds.insert(cmd.to_string(), f).is_some() {
+            panic!("Command already registered: {}", cmd);
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.cmds.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn register_operator(&mut self, name: &str, f: OperatorCommand) {
+        let op_name = name.to_string();
+        self.register(
+            name,
+            Box::new(move |editor| editor.start_operator(&op_name)),
+        );
+        self.ops.insert(name.to_string(), f);
     }
 
     pub fn register_default_commands() -> Self {
         let mut cmds = Self {
             cmds: HashMap::new(),
+            ops: HashMap::new(),
         };
 
-        cmds.register("q", Box::new(|editor| editor.quit()));
-        cmds.register("w", Box::new(|editor| editor.write()));
-        cmds.register(
-            "x",
-            Box::new(|editor| {
-                editor.write();
-                editor.quit();
-            }),
-        );
-        cmds.register(
-            "wq",
-            Box::new(|editor| {
-                editor.write();
-                editor.quit();
-            }),
-        );
-
         EditorCursor::register_cmds(&mut cmds);
         EditorMode::register_cmds(&mut cmds);
+        EditorOptions::register_cmds(&mut cmds);
+        EditorState::register_scroll_cmds(&mut cmds);
+        EditorState::register_wrap_cmds(&mut cmds);
+        EditorState::register_indent_cmds(&mut cmds);
+        EditorState::register_history_cmds(&mut cmds);
+        EditorState::register_comment_cmds(&mut cmds);
+        EditorState::register_multicursor_cmds(&mut cmds);
+        EditorState::register_edit_cmds(&mut cmds);
+        EditorState::register_case_cmds(&mut cmds);
+        EditorState::register_number_cmds(&mut cmds);
+        EditorState::register_ex_cmds(&mut cmds);
+        EditorState::register_filter_cmds(&mut cmds);
+        EditorState::register_file_cmds(&mut cmds);
+        EditorState::register_buffer_cmds(&mut cmds);
+        EditorState::register_window_cmds(&mut cmds);
+        EditorState::register_tab_cmds(&mut cmds);
+        EditorState::register_explorer_cmds(&mut cmds);
+        EditorState::register_picker_cmds(&mut cmds);
+        EditorState::register_grep_cmds(&mut cmds);
+        EditorState::register_quickfix_cmds(&mut cmds);
+        EditorState::register_syntax_tree_cmds(&mut cmds);
+        EditorState::register_theme_cmds(&mut cmds);
+        EditorState::register_session_cmds(&mut cmds);
 
         cmds
     }
 
+    // `t.` and `sort!` have no space after the name
+    fn split_command<'a>(&self, cmd: &'a str) -> (&'a str, &'a str) {
+        let cmd = cmd.trim();
+        match cmd.split_once(' ') {
+            Some((name, args)) if self.cmds.contains_key(name) => return (name, args.trim()),
+            None if self.cmds.contains_key(cmd) => return (cmd, ""),
+            _ => {}
+        }
+
+        let len = match cmd.chars().take_while(|c| c.is_ascii_alphabetic()).count() {
+            0 => cmd.chars().next().map_or(0, |c| c.len_utf8()),
+            len => len,
+        };
+        (&cmd[..len], cmd[len..].trim())
+    }
+
     pub fn run(&mut self, cmd: &str, editor: &mut EditorState) {
-        if let Some(cmd) = self.cmds.get(cmd) {
-            cmd(editor);
+        let (range, cmd) = match editor.parse_range(cmd) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                editor.set_message(err.to_string());
+                return;
+            }
+        };
+        let (name, args) = self.split_command(cmd);
+
+        if let Some(cmd) = self.cmds.get(name) {
+            editor.cmd_range = range;
+            cmd(editor, args);
+            editor.cmd_range = None;
+        } else if !name.is_empty() {
+            editor.set_message(format!("Not an editor command: {}", name));
+        } else if let Some(range) = range {
+            // A range alone moves to its last line
+            editor.cursor.move_y_to(&editor.buf, range.end.y);
+        }
+    }
+
+    pub fn run_operator(&mut self, name: &str, editor: &mut EditorState, range: TextRange) {
+        if let Some(op) = self.ops.get(name) {
+            op(editor, range);
+        }
+        editor.finish_operator(range);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_commands() {
+        let cmds = EditorCommand::new();
+        assert!(cmds.names().contains(&"up".to_string()));
+        assert!(cmds.names().contains(&"update".to_string()));
+        for motion in crate::states::operator::MOTIONS {
+            assert!(cmds.names().contains(&motion.to_string()), "{}", motion);
         }
+        for motion in crate::states::operator::LINEWISE_MOTIONS
+            .iter()
+            .chain(crate::states::operator::INCLUSIVE_MOTIONS)
+        {
+            assert!(
+                crate::states::operator::MOTIONS.contains(motion),
+                "{}",
+                motion
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Command already registered: up")]
+    fn test_duplicate_command() {
+        let mut cmds = EditorCommand::new();
+        cmds.register("up", Box::new(|_| {}));
     }
 }
@@ -2,18 +2,24 @@ mod cmd;
 mod key;
 mod states;
 
-use std::{fs::read_to_string, io::Write, path::PathBuf};
+use std::{fmt::Display, io::Write, path::PathBuf};
 
 use anyhow::Result;
 use cmd::EditorCommand;
-use crossterm::event::{Event, KeyCode, KeyModifiers};
-use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute, SetStyle};
 use crossterm::terminal::{Clear, ClearType};
 use crossterm::{cursor, execute};
 use key::EditorKeys;
+use states::buffers::read_file;
 use states::mode::EditorMode;
-use states::EditorState;
-use utils::cli::terminal_size;
+use states::multicursor::PRIMARY_ONLY_COMMANDS;
+use states::operator::{TextRange, MOTIONS};
+use states::session::list_sessions;
+use states::start::{load_recent_files, save_recent_files};
+use states::theme::Style;
+use states::{ex::NormalKeys, EditorState};
+use utils::{cli::terminal_size, types::Vec2};
 
 pub struct Editor {
     cmds: EditorCommand,
@@ -23,86 +29,160 @@ pub struct Editor {
 
 impl Editor {
     pub fn new(buf: String, path: Option<PathBuf>) -> Self {
+        let cmds = EditorCommand::new();
+        let mut state = EditorState::new(buf, path);
+        state.set_command_names(cmds.names());
         Self {
-            cmds: EditorCommand::new(),
+            cmds,
             keys: EditorKeys::new(),
-            state: EditorState::new(buf, path),
+            state,
         }
     }
 
-    pub fn open(path: PathBuf) -> Self {
-        let mut buf = read_to_string(path.clone()).unwrap_or_default();
-
-        if buf.is_empty() {
-            buf = String::from("\n");
+    pub fn open(paths: Vec<PathBuf>, tabs: bool) -> Self {
+        let path = paths[0].clone();
+        // Unreadable files get an unnamed buffer so they are never overwritten
+        let mut editor = match read_file(&path) {
+            Ok(buf) => Self::new(buf, Some(path)),
+            Err(err) => {
+                let mut editor = Self::new(String::from("\n"), None);
+                editor.state.set_message(err.to_string());
+                editor
+            }
+        };
+        editor.state.set_args(paths.clone());
+        if tabs {
+            editor.state.open_tabs(&paths[1..]);
         }
+        editor
+    }
 
-        Self::new(buf, Some(path))
+    pub fn start() -> Self {
+        let mut editor = Self::default();
+        editor
+            .state
+            .show_start_screen(load_recent_files(), list_sessions());
+        editor
     }
 
-    pub fn draw<T: Write>(&self, stdout: &mut T) -> Result<()> {
-        let mode = self.state.get_mode();
+    pub fn save_recent_files(&self) -> Result<()> {
+        save_recent_files(self.state.buffer_paths())
+    }
 
-        let (term_w, term_h) = terminal_size()?;
-        let (cursor_x, cursor_y) = self.state.cursor.get_display(&self.state.buf);
+    pub fn resize(&mut self, term_w: usize, term_h: usize) {
+        self.state.resize(term_w, term_h);
+        self.state.scroll_to_cursor();
+    }
 
-        let str_buf = self.state.buf.to_string();
-        let lines = str_buf.lines();
+    pub fn draw<T: Write>(&mut self, stdout: &mut T) -> Result<()> {
+        let mode = self.state.get_mode();
+        let (term_w, term_h) = terminal_size()?;
 
-        execute!(stdout, Clear(ClearType::All))?;
+        execute!(
+            stdout,
+            SetAttribute(Attribute::Reset),
+            SetStyle(self.state.theme().content_style(Style::default())),
+            Clear(ClearType::All)
+        )?;
 
-        // Draw line numbers
-        let len_count = self.state.buf.line_count();
-        let line_num_w = len_count.to_string().len();
-        let line_numbers: Vec<String> = (1..=len_count)
-            .skip(self.state.offset.y)
-            .take(term_h - 1)
-            .map(|x| x.to_string())
-            .collect();
-        execute!(stdout, cursor::MoveTo(0, 1))?;
-        execute!(stdout, Print(line_numbers.join("\r\n")))?;
-
-        // Draw code
-        lines
-            .skip(self.state.offset.y)
-            .take(term_h - 1)
-            .enumerate()
-            .for_each(|(index, line)| {
+        // Draw start screen
+        if self.state.is_start_screen() {
+            let lines = self.state.start_screen_lines();
+            let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+            let left = term_w.saturating_sub(width) / 2;
+            let top = term_h.saturating_sub(lines.len()) / 2;
+            for (i, line) in lines.iter().enumerate() {
                 execute!(
                     stdout,
-                    cursor::MoveTo(1 + line_num_w as u16, 1 + index as u16)
-                )
-                .unwrap();
-                execute!(stdout, Print(line)).unwrap();
-            });
-
-        // Draw info bar
-        execute!(stdout, cursor::MoveTo(0, 0))?;
-        execute!(stdout, SetBackgroundColor(Color::White))?;
-        execute!(stdout, SetForegroundColor(Color::Black))?;
-        execute!(stdout, Print(" ".repeat(term_w)))?;
-        execute!(stdout, cursor::MoveTo(0, 0))?;
-        write!(
-            stdout,
-            " {} {},{} {},{}",
-            mode, cursor_x, cursor_y, self.state.offset.x, self.state.offset.y
-        )
-        .unwrap();
-        execute!(stdout, ResetColor)?;
+                    cursor::MoveTo(left as u16, (top + i) as u16),
+                    Print(line)
+                )?;
+            }
+            stdout.flush()?;
+            return Ok(());
+        }
+
+        // Draw tab line
+        if self.state.has_tab_line() {
+            execute!(stdout, cursor::MoveTo(0, 0))?;
+            let theme = self.state.theme();
+            let mut width = 0;
+            for (label, current) in self.state.tab_labels() {
+                let style = if current {
+                    theme.tab_line_selected
+                } else {
+                    theme.tab_line
+                };
+                let label: String = label.chars().take(term_w.saturating_sub(width)).collect();
+                width += label.chars().count();
+                self.print_styled(stdout, style, label)?;
+            }
+            let rest = " ".repeat(term_w.saturating_sub(width));
+            self.print_styled(stdout, theme.tab_line, rest)?;
+        }
+
+        // Draw explorer
+        if self.state.is_explorer_visible() {
+            self.draw_explorer(stdout)?;
+        }
+
+        // Draw windows
+        let current = self.state.current_window();
+        self.state.update_highlight();
+        for id in self.state.window_ids() {
+            let diagnostics = self.state.diagnostic_lines(id);
+            if id == current {
+                self.draw_window(stdout, &self.state, &diagnostics, true)?;
+            } else {
+                let view = self.state.window_view(id);
+                self.draw_window(stdout, &view, &diagnostics, false)?;
+            }
+        }
+
+        // Draw message
+        if let Some(message) = self.state.get_message() {
+            let lines: Vec<&str> = message.lines().collect();
+            let top = term_h.saturating_sub(lines.len());
+            for (i, line) in lines.iter().enumerate() {
+                execute!(stdout, cursor::MoveTo(0, (top + i) as u16))?;
+                execute!(stdout, Clear(ClearType::CurrentLine), Print(line))?;
+            }
+        }
 
+        let rect = self.state.window_rect(current);
+        let gutter_w = self.state.gutter_width();
+        let (screen_x, screen_y) = self.state.cursor_screen_pos();
         execute!(
             stdout,
             cursor::MoveTo(
-                (cursor_x + 1 - self.state.offset.x + line_num_w) as u16,
-                (cursor_y + 1 - self.state.offset.y) as u16
+                (rect.x + screen_x + gutter_w) as u16,
+                (rect.y + screen_y) as u16
             )
         )?;
 
-        if let EditorMode::Command = mode {
-            execute!(stdout, cursor::MoveTo(1, term_h as u16 - 1))?;
+        if self.state.is_explorer_focused() {
+            let top = if self.state.has_tab_line() { 1 } else { 0 };
+            let row = top + self.state.explorer_cursor_row();
+            execute!(stdout, cursor::MoveTo(0, row as u16))?;
+        }
+
+        if let Some(prompt) = self.state.explorer_prompt() {
+            execute!(
+                stdout,
+                cursor::MoveTo(0, term_h as u16 - 1),
+                Clear(ClearType::CurrentLine),
+                Print(prompt)
+            )?;
+        } else if let EditorMode::Command = mode {
+            execute!(stdout, cursor::MoveTo(0, term_h as u16 - 1))?;
             write!(stdout, ":{}", self.state.cmd_buf).unwrap();
         }
 
+        // Draw picker
+        if self.state.is_picker_open() {
+            self.draw_picker(stdout)?;
+        }
+
         match mode {
             EditorMode::Insert => write!(stdout, "{}", cursor::SetCursorStyle::SteadyBar).unwrap(),
             _ => write!(stdout, "{}", cursor::SetCursorStyle::SteadyBlock).unwrap(),
@@ -113,78 +193,333 @@ impl Editor {
         Ok(())
     }
 
-    fn on_insert_or_command_mode_event(&mut self, evt: Event) -> bool {
-        let (cursor_x, cursor_y) = self.state.cursor.get_display(&self.state.buf);
+    fn draw_explorer<T: Write>(&self, stdout: &mut T) -> Result<()> {
+        let width = self.state.explorer_width().saturating_sub(1);
+        let top = if self.state.has_tab_line() { 1 } else { 0 };
+        let (_, term_h) = terminal_size()?;
+        let focused = self.state.is_explorer_focused();
 
-        match evt {
-            Event::Key(key) => match self.state.get_mode() {
-                EditorMode::Insert => match (key.code, key.modifiers) {
-                    (KeyCode::Enter, KeyModifiers::NONE) => {
-                        if cursor_x < self.state.buf.line_length(cursor_y) {
-                            self.state.buf.split_line(cursor_x, cursor_y);
-                            self.state
-                                .cursor
-                                .move_by(&self.state.buf, &mut self.state.offset, 0, 1)
-                                .unwrap();
-                            self.state.cursor.move_x_to(&self.state.buf, 0)
-                        } else {
-                            self.state.buf.insert_line();
-                            self.state
-                                .cursor
-                                .move_by(&self.state.buf, &mut self.state.offset, 0, 1)
-                                .unwrap();
-                            self.state.cursor.move_x_to(&self.state.buf, 0);
-                        }
-                    }
-                    (KeyCode::Tab, KeyModifiers::NONE) => {
-                        self.state.buf.insert_str("  ", cursor_x, cursor_y);
-                        self.state
-                            .cursor
-                            .move_by(&self.state.buf, &mut self.state.offset, 2, 0)
-                            .unwrap();
+        for (index, (line, selected)) in self.state.explorer_lines().iter().enumerate() {
+            let line: String = format!("{:width$}", line, width = width)
+                .chars()
+                .take(width)
+                .collect();
+            execute!(stdout, cursor::MoveTo(0, (top + index) as u16))?;
+            let theme = self.state.theme();
+            let style = match (*selected, focused) {
+                (true, true) => theme.popup_selected,
+                (true, false) => theme.info_bar_inactive,
+                _ => Style::default(),
+            };
+            self.print_styled(stdout, style, line)?;
+        }
+
+        // Draw separator
+        for row in top..term_h.saturating_sub(1) {
+            execute!(stdout, cursor::MoveTo(width as u16, row as u16), Print('│'))?;
+        }
+        Ok(())
+    }
+
+    fn draw_picker<T: Write>(&mut self, stdout: &mut T) -> Result<()> {
+        let rect = self.state.picker_rect();
+        if rect.w == 0 || rect.h < 3 {
+            return Ok(());
+        }
+        let (x, y) = (rect.x as u16, rect.y as u16);
+        let list_w = if rect.w >= 60 { rect.w / 2 } else { rect.w };
+        let preview = if list_w < rect.w {
+            self.state.picker_preview()
+        } else {
+            Vec::new()
+        };
+        let fit = |text: &str, width: usize| -> String {
+            format!("{:width$}", text, width = width)
+                .chars()
+                .take(width)
+                .collect()
+        };
+
+        let theme = self.state.theme();
+        let prompt = self.state.picker_prompt();
+        execute!(stdout, cursor::MoveTo(x, y))?;
+        self.print_styled(stdout, theme.popup, fit(&prompt, rect.w))?;
+        execute!(stdout, cursor::MoveTo(x, y + 1))?;
+        self.print_styled(stdout, theme.popup, "─".repeat(rect.w))?;
+
+        let rows = self.state.picker_rows();
+        for row in 0..rect.h - 2 {
+            execute!(stdout, cursor::MoveTo(x, y + 2 + row as u16))?;
+            let Some((label, positions, selected)) = rows.get(row) else {
+                self.print_styled(stdout, theme.popup, fit("", list_w))?;
+                continue;
+            };
+            let style = if *selected {
+                theme.popup.patch(theme.popup_selected)
+            } else {
+                theme.popup
+            };
+            let label = fit(label, list_w);
+            let styles: Vec<Style> = (0..list_w)
+                .map(|i| {
+                    if positions.contains(&i) {
+                        style.patch(theme.search)
+                    } else {
+                        style
                     }
-                    (KeyCode::Backspace, KeyModifiers::NONE) => {
-                        if cursor_x > 0 {
-                            self.state.buf.delete(cursor_x - 1, cursor_y);
-                            self.state
-                                .cursor
-                                .move_by(&self.state.buf, &mut self.state.offset, -1, 0)
-                                .unwrap();
-                        } else if cursor_y > 0 {
-                            let line_len = self.state.buf.line_length(cursor_y - 1);
-
-                            self.state
-                                .cursor
-                                .move_by(&self.state.buf, &mut self.state.offset, 0, -1)
-                                .unwrap();
-                            self.state.cursor.move_x_to(&self.state.buf, line_len);
-                            self.state.buf.join_lines(cursor_y - 1);
-                        }
+                })
+                .collect();
+            self.draw_styled(stdout, &label, &styles)?;
+        }
+
+        // Draw preview
+        if list_w < rect.w {
+            let preview_w = rect.w - list_w - 1;
+            for row in 0..rect.h - 2 {
+                let line = preview.get(row).map(String::as_str).unwrap_or("");
+                execute!(
+                    stdout,
+                    cursor::MoveTo(x + list_w as u16, y + 2 + row as u16)
+                )?;
+                self.print_styled(stdout, theme.popup, format!("│{}", fit(line, preview_w)))?;
+            }
+        }
+
+        execute!(
+            stdout,
+            cursor::MoveTo(x + prompt.chars().count().min(rect.w) as u16, y)
+        )?;
+        Ok(())
+    }
+
+    fn print_styled<T: Write>(
+        &self,
+        stdout: &mut T,
+        style: Style,
+        text: impl Display,
+    ) -> Result<()> {
+        let theme = self.state.theme();
+        execute!(
+            stdout,
+            SetAttribute(Attribute::Reset),
+            SetStyle(theme.content_style(style)),
+            Print(text),
+            SetAttribute(Attribute::Reset),
+            SetStyle(theme.content_style(Style::default()))
+        )?;
+        Ok(())
+    }
+
+    fn draw_styled<T: Write>(&self, stdout: &mut T, text: &str, styles: &[Style]) -> Result<()> {
+        let chars: Vec<char> = text.chars().collect();
+        let style_at = |i: usize| styles.get(i).copied().unwrap_or_default();
+        let mut start = 0;
+        while start < chars.len() {
+            let style = style_at(start);
+            let end = (start..chars.len())
+                .find(|&i| style_at(i) != style)
+                .unwrap_or(chars.len());
+            let part: String = chars[start..end].iter().collect();
+            self.print_styled(stdout, style, part)?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    fn draw_window<T: Write>(
+        &self,
+        stdout: &mut T,
+        state: &EditorState,
+        diagnostics: &[usize],
+        active: bool,
+    ) -> Result<()> {
+        let rect = state.window_rect(state.current_window());
+        if rect.h == 0 || rect.w == 0 {
+            return Ok(());
+        }
+        let (cursor_x, cursor_y) = state.cursor.get_display(&state.buf);
+        let gutter_w = state.gutter_width();
+        let (x, y) = (rect.x as u16, rect.y as u16);
+
+        // Draw line numbers and code
+        let theme = state.theme();
+        let rows = state.visible_rows();
+        let positions = state.visible_positions();
+        let scopes = state.visible_scopes();
+        let selection = if active {
+            state.visual_selection()
+        } else {
+            None
+        };
+        let text_w = rect.w.saturating_sub(gutter_w);
+        for (index, (line_number, text)) in rows.iter().enumerate() {
+            let (line_y, cells) = &positions[index];
+            execute!(stdout, cursor::MoveTo(x, y + index as u16))?;
+            if let Some(line_number) = line_number {
+                let style = if diagnostics.contains(line_y) {
+                    theme.diagnostic
+                } else {
+                    theme.line_number
+                };
+                self.print_styled(stdout, style, line_number)?;
+            }
+            execute!(
+                stdout,
+                cursor::MoveTo(x + gutter_w as u16, y + index as u16)
+            )?;
+
+            let cursor_line = state.options.cursorline && *line_y == cursor_y;
+            let base = if cursor_line {
+                theme.cursor_line
+            } else {
+                Style::default()
+            };
+            let mut styles: Vec<Style> = cells
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| {
+                    let mut style = base;
+                    if let Some(scope) = scopes[index].get(i).copied().flatten() {
+                        style = style.patch(theme.scope_style(scope));
                     }
-                    (KeyCode::Delete, KeyModifiers::NONE) => {
-                        let line_len = self.state.buf.line_length(cursor_y);
-                        let len_count = self.state.buf.line_count();
-
-                        if cursor_x < line_len {
-                            self.state.buf.delete(cursor_x, cursor_y);
-                        } else if cursor_y < len_count {
-                            self.state.buf.join_lines(cursor_y);
-                        }
+                    let selected = cell.zip(selection.as_ref());
+                    if selected.is_some_and(|(cx, range)| range.contains(cx, *line_y)) {
+                        style = style.patch(theme.selection);
                     }
-                    (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
-                        self.state.buf.insert(c, cursor_x, cursor_y);
-                        self.state
-                            .cursor
-                            .move_by(&self.state.buf, &mut self.state.offset, 1, 0)
-                            .unwrap();
+                    style
+                })
+                .collect();
+            let mut text = text.clone();
+            // Fill the cursor line to the window width
+            if cursor_line {
+                let len = text.chars().count();
+                text.push_str(&" ".repeat(text_w.saturating_sub(len)));
+                styles.resize(text_w.max(len), base);
+            }
+            self.draw_styled(stdout, &text, &styles)?;
+        }
+
+        // Draw separator
+        let (term_w, _) = terminal_size()?;
+        if rect.x + rect.w < term_w {
+            for row in 0..rect.h {
+                execute!(
+                    stdout,
+                    cursor::MoveTo((rect.x + rect.w) as u16, y + row as u16),
+                    Print('│')
+                )?;
+            }
+        }
+
+        // Draw status line
+        let status_y = y + rect.h as u16 - 1;
+        let status = if active {
+            format!(
+                " {} {} {},{} {},{}",
+                state.get_mode(),
+                state.buffer_name(),
+                cursor_x,
+                cursor_y,
+                state.offset.x,
+                state.offset.y
+            )
+        } else {
+            format!(" {} {},{}", state.buffer_name(), cursor_x, cursor_y)
+        };
+        let status: String = format!("{:width$}", status, width = rect.w)
+            .chars()
+            .take(rect.w)
+            .collect();
+        let style = if active {
+            theme.info_bar
+        } else {
+            theme.info_bar_inactive
+        };
+        execute!(stdout, cursor::MoveTo(x, status_y))?;
+        self.print_styled(stdout, style, status)?;
+
+        // Draw extra cursors
+        if active {
+            for (screen_x, screen_y) in state.extra_cursor_screen_positions() {
+                let c = rows
+                    .get(screen_y)
+                    .and_then(|(_, text)| text.chars().nth(screen_x))
+                    .unwrap_or(' ');
+                execute!(
+                    stdout,
+                    cursor::MoveTo(x + (screen_x + gutter_w) as u16, y + screen_y as u16)
+                )?;
+                let style = Style {
+                    reverse: true,
+                    ..Style::default()
+                };
+                self.print_styled(stdout, style, c)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_insert_key(state: &mut EditorState, key: KeyEvent) -> bool {
+        let (cursor_x, cursor_y) = state.cursor.get_display(&state.buf);
+
+        match (key.code, key.modifiers) {
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                if !state.open_pair_block() {
+                    state.insert_newline();
+                }
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => state.insert_tab(),
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                if cursor_x > 0 {
+                    if !state.delete_pair() && !state.delete_soft_tab() {
+                        state.buf.delete(cursor_x - 1, cursor_y);
+                        state.cursor.move_by(&state.buf, -1, 0);
                     }
-                    _ => return false,
-                },
+                } else if cursor_y > 0 {
+                    let line_len = state.buf.line_length(cursor_y - 1);
+
+                    state.cursor.move_by(&state.buf, 0, -1);
+                    state.cursor.move_x_to(&state.buf, line_len);
+                    state.buf.join_lines(cursor_y - 1);
+                }
+            }
+            (KeyCode::Delete, KeyModifiers::NONE) => {
+                let line_len = state.buf.line_length(cursor_y);
+                let len_count = state.buf.line_count();
+
+                if cursor_x < line_len {
+                    state.buf.delete(cursor_x, cursor_y);
+                } else if cursor_y < len_count {
+                    state.buf.join_lines(cursor_y);
+                }
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                state.insert_char(c);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn on_insert_or_command_mode_event(&mut self, evt: Event) -> bool {
+        match evt {
+            Event::Key(key) => match self.state.get_mode() {
+                EditorMode::Insert => {
+                    let mut handled = false;
+                    self.state
+                        .for_each_cursor(|state| handled |= Self::on_insert_key(state, key));
+                    return handled;
+                }
                 EditorMode::Command => match (key.code, key.modifiers) {
                     (KeyCode::Enter, KeyModifiers::NONE) => {
                         self.cmds
                             .run(self.state.cmd_buf.clone().as_str(), &mut self.state);
                         self.cmds.run("normal", &mut self.state);
+                        if let Some(normal_keys) = self.state.take_normal_keys() {
+                            self.run_normal_keys(normal_keys);
+                        }
                     }
                     (KeyCode::Backspace, KeyModifiers::NONE) => {
                         if self.state.cmd_buf.is_empty() {
@@ -205,25 +540,203 @@ impl Editor {
         true
     }
 
+    fn on_operator_command(
+        cmds: &mut EditorCommand,
+        state: &mut EditorState,
+        cmd: &str,
+        pending: bool,
+        key: (KeyCode, KeyModifiers),
+    ) {
+        let Some(op) = state.pending_op.as_mut() else {
+            return;
+        };
+        let (cursor_x, cursor_y) = state.cursor.get_display(&state.buf);
+        let cursor = Vec2::new(cursor_x, cursor_y);
+
+        if !pending {
+            if op.visual {
+                let range = TextRange::from_visual(&state.buf, op.start, cursor);
+                let name = op.name.clone();
+                cmds.run_operator(&name, state, range);
+            } else {
+                op.key = Some(key);
+            }
+            return;
+        }
+
+        let range = TextRange::from_motion(&state.buf, op.start, cursor, cmd);
+        let name = op.name.clone();
+        cmds.run_operator(&name, state, range);
+    }
+
+    fn run_command(&mut self, cmd: &str, key: (KeyCode, KeyModifiers)) {
+        let pending = self.state.pending_op.is_some();
+
+        if pending && !MOTIONS.contains(&cmd) {
+            self.state.for_each_cursor(|state| state.pending_op = None);
+            return;
+        }
+
+        if PRIMARY_ONLY_COMMANDS.contains(&cmd) {
+            self.cmds.run(cmd, &mut self.state);
+            Self::on_operator_command(&mut self.cmds, &mut self.state, cmd, pending, key);
+            return;
+        }
+
+        let cmds = &mut self.cmds;
+        self.state.for_each_cursor(|state| cmds.run(cmd, state));
+        self.state.for_each_cursor(|state| {
+            Self::on_operator_command(cmds, state, cmd, pending, key);
+        });
+    }
+
+    fn run_normal_keys(&mut self, normal_keys: NormalKeys) {
+        let (start, end) = normal_keys.lines;
+        let mut y = start;
+
+        for _ in start..=end {
+            if y >= self.state.buf.line_count() {
+                break;
+            }
+            let line_count = self.state.buf.line_count();
+
+            self.state.set_mode(EditorMode::Normal);
+            self.state.cursor.move_y_to(&self.state.buf, y);
+            self.state.cursor.move_x_to(&self.state.buf, 0);
+            for c in normal_keys.keys.chars() {
+                let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+                self.dispatch_event(Event::Key(key));
+            }
+            self.state.pending_op = None;
+            self.state.clear_keys();
+            self.cmds.run("normal", &mut self.state);
+
+            y = (y + 1 + self.state.buf.line_count()).saturating_sub(line_count);
+        }
+    }
+
+    // One event is one undo step
     pub fn on_event(&mut self, evt: Event) -> bool {
+        self.state.begin_change();
+        let is_quit = self.dispatch_event(evt);
+        self.state.end_change();
+        self.state.update_syntax_tree();
+
+        is_quit
+    }
+
+    fn dispatch_event(&mut self, evt: Event) -> bool {
+        match evt {
+            Event::Key(_) => self.state.clear_message(),
+            Event::Resize(term_w, term_h) => self.resize(term_w as usize, term_h as usize),
+            _ => {}
+        }
+
+        if let Event::Key(key) = evt {
+            if self.state.is_start_screen() {
+                match (key.code, key.modifiers) {
+                    (KeyCode::Char(c), KeyModifiers::NONE) if self.state.on_start_screen_key(c) => {
+                        return self.state.is_quit;
+                    }
+                    _ => self.state.close_start_screen(),
+                }
+            }
+        }
+
+        if let Event::Key(key) = evt {
+            if self.state.is_picker_open() {
+                if let Some(cmd) = self.state.on_picker_key(key.code, key.modifiers) {
+                    self.cmds.run(&cmd, &mut self.state);
+                }
+                self.state.scroll_to_cursor();
+                return self.state.is_quit;
+            }
+        }
+
+        if let Event::Key(key) = evt {
+            if self.state.is_explorer_focused()
+                && self.state.get_mode() == EditorMode::Normal
+                && self.state.on_explorer_key(key.code, key.modifiers)
+            {
+                return self.state.is_quit;
+            }
+        }
+
         if self.on_insert_or_command_mode_event(evt.clone()) {
+            self.state.scroll_to_cursor();
             return self.state.is_quit;
         };
-        
+
         if let Event::Key(key) = evt {
             let mut keys = self.state.get_keys();
+
+            // `"` selects the register for the next command
+            if self.state.selecting_register {
+                self.state.selecting_register = false;
+                if let KeyCode::Char(c) = key.code {
+                    self.state.select_register(c);
+                }
+                return self.state.is_quit;
+            }
+            if keys.is_empty() && key.code == KeyCode::Char('"') {
+                self.state.selecting_register = true;
+                return self.state.is_quit;
+            }
+
+            // Count (a lone 0 is line_start)
+            if let (KeyCode::Char(c @ '0'..='9'), KeyModifiers::NONE) = (key.code, key.modifiers) {
+                if keys.is_empty() && (c != '0' || self.state.has_count()) {
+                    self.state.push_count(c as usize - '0' as usize);
+                    return self.state.is_quit;
+                }
+            }
+
+            if let Some(op) = self.state.pending_op.clone() {
+                if keys.is_empty() && op.key == Some((key.code, key.modifiers)) {
+                    // Repeating the operator key (`==`, `gcc`) applies it to count lines
+                    let cmds = &mut self.cmds;
+                    self.state.for_each_cursor(|state| {
+                        let (_, cursor_y) = state.cursor.get_display(&state.buf);
+                        let range = TextRange::lines(cursor_y, cursor_y + state.get_count() - 1);
+                        cmds.run_operator(&op.name, state, range);
+                    });
+                    self.state.clear_count();
+                    self.state.clear_register();
+                    self.state.scroll_to_cursor();
+                    return self.state.is_quit;
+                }
+                if key.code == KeyCode::Esc {
+                    self.state.for_each_cursor(|state| state.pending_op = None);
+                    self.state.clear_keys();
+                    self.state.clear_count();
+                    self.state.clear_register();
+                    return self.state.is_quit;
+                }
+            }
+
             keys.push((key.code, key.modifiers));
 
-            let cmd = self.keys.get(self.state.get_mode(), keys);
+            // Text objects take precedence while an operator is pending
+            let mode = self.state.get_mode();
+            let pending = match self.state.pending_op {
+                Some(_) if mode == EditorMode::Normal => self.keys.get_pending(&keys),
+                _ => None,
+            };
+            let cmd = match pending {
+                Some(cmd) => cmd.cloned(),
+                None => self.keys.get(mode, keys).cloned(),
+            };
             if let Some(cmd) = cmd {
                 self.state.clear_keys();
-                self.cmds.run(cmd, &mut self.state);
+                self.run_command(&cmd, (key.code, key.modifiers));
+                self.state.clear_count();
+                self.state.clear_register();
             } else {
                 self.state.push_key((key.code, key.modifiers));
             }
-         
         }
 
+        self.state.scroll_to_cursor();
         self.state.is_quit
     }
 }
@@ -239,3 +752,67 @@ impl From<String> for Editor {
         Self::new(buf, None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(editor: &mut Editor, keys: &str) {
+        for c in keys.chars() {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            editor.on_event(Event::Key(key));
+        }
+    }
+
+    #[test]
+    fn test_operator_without_motion() {
+        let mut editor = Editor::from("a\nb\nc".to_string());
+        type_keys(&mut editor, "gcp");
+        assert!(editor.state.pending_op.is_none());
+        assert_eq!(editor.state.buf.to_string(), "a\nb\nc");
+
+        type_keys(&mut editor, "gcj");
+        assert_eq!(editor.state.buf.to_string(), "# a\n# b\nc");
+    }
+
+    #[test]
+    fn test_visual_yank_and_delete() {
+        let mut editor = Editor::from("abc\ndef\nghi".to_string());
+        type_keys(&mut editor, "vly");
+        assert_eq!(editor.state.registers.get('0').unwrap().text, "ab");
+        assert!(editor.state.get_mode() == EditorMode::Normal);
+
+        type_keys(&mut editor, "vld");
+        assert_eq!(editor.state.buf.to_string(), "c\ndef\nghi");
+        assert_eq!(editor.state.registers.get('-').unwrap().text, "ab");
+    }
+
+    #[test]
+    fn test_visual_yank_and_delete_multibyte() {
+        let mut editor = Editor::from(
+            "aé
+éb"
+            .to_string(),
+        );
+        type_keys(&mut editor, "lvy");
+        assert_eq!(editor.state.registers.get('0').unwrap().text, "é");
+
+        type_keys(&mut editor, "j0vd");
+        assert_eq!(
+            editor.state.buf.to_string(),
+            "aé
+b"
+        );
+        assert_eq!(editor.state.registers.get('-').unwrap().text, "é");
+    }
+
+    #[test]
+    fn test_visual_case_multibyte() {
+        let mut editor = Editor::from("aéb".to_string());
+        type_keys(&mut editor, "lvU");
+        assert_eq!(editor.state.buf.to_string(), "aÉb");
+
+        type_keys(&mut editor, "v~");
+        assert_eq!(editor.state.buf.to_string(), "aéb");
+    }
+}
@@ -9,6 +9,7 @@ type KeyMap = HashMap<(EditorMode, Vec<Key>), String>;
 
 pub struct EditorKeys {
     pub keys: KeyMap,
+    pub pending_keys: HashMap<Vec<Key>, String>,
 }
 
 impl EditorKeys {
@@ -33,13 +34,19 @@ impl EditorKeys {
         self.v_register(key, cmd);
     }
 
+    pub fn ov_register(&mut self, key: Vec<Key>, cmd: &str) {
+        self.pending_keys.insert(key.clone(), cmd.to_string());
+        self.v_register(key, cmd);
+    }
+
     pub fn register_default_keys() -> Self {
         let mut keys = Self {
             keys: HashMap::new(),
+            pending_keys: HashMap::new(),
         };
 
         // Normal mode -> Command mode
-        keys.n_register(vec![Key::Char(':')], "command");
+        keys.nv_register(vec![Key::Char(':')], "command");
 
         // Insert mode -> Normal mode
         keys.register(EditorMode::Insert, vec![Key::Ctrl('c')], "normal");
@@ -71,6 +78,25 @@ impl EditorKeys {
         keys.nv_register(vec![Key::Char('M')], "window_middle");
         keys.nv_register(vec![Key::Char('L')], "window_bottom");
         keys.nv_register(vec![Key::Char('%')], "match_paren");
+        keys.nv_register(vec![Key::Char('g'), Key::Char('j')], "screen_down");
+        keys.nv_register(vec![Key::Char('g'), Key::Char('k')], "screen_up");
+        keys.nv_register(vec![Key::Char('g'), Key::Char('0')], "screen_line_start");
+        keys.nv_register(vec![Key::Char('g'), Key::Char('$')], "screen_line_end");
+
+        // Scroll
+        keys.nv_register(vec![Key::Ctrl('d')], "scroll_half_down");
+        keys.nv_register(vec![Key::Ctrl('u')], "scroll_half_up");
+        keys.nv_register(vec![Key::Ctrl('f')], "scroll_page_down");
+        keys.nv_register(vec![Key::Ctrl('b')], "scroll_page_up");
+        keys.nv_register(vec![Key::Ctrl('e')], "scroll_line_down");
+        keys.nv_register(vec![Key::Ctrl('y')], "scroll_line_up");
+        keys.nv_register(vec![Key::Char('z'), Key::Char('z')], "scroll_cursor_center");
+        keys.nv_register(vec![Key::Char('z'), Key::Char('t')], "scroll_cursor_top");
+        keys.nv_register(vec![Key::Char('z'), Key::Char('b')], "scroll_cursor_bottom");
+        keys.nv_register(vec![Key::Char('z'), Key::Char('h')], "scroll_column_left");
+        keys.nv_register(vec![Key::Char('z'), Key::Char('l')], "scroll_column_right");
+        keys.nv_register(vec![Key::Char('z'), Key::Char('s')], "scroll_cursor_left");
+        keys.nv_register(vec![Key::Char('z'), Key::Char('e')], "scroll_cursor_right");
 
         // Insert
         keys.n_register(vec![Key::Char('i')], "insert_before");
@@ -85,13 +111,46 @@ impl EditorKeys {
         keys.n_register(vec![Key::Char('V')], "visual_line");
         keys.n_register(vec![Key::Ctrl('v')], "visual_block");
 
+        // Edit
+        keys.n_register(vec![Key::Char('x')], "delete_char");
+        keys.n_register(vec![Key::Char('X')], "delete_char_before");
+        keys.n_register(vec![Key::Char('D')], "delete_to_end");
+        keys.n_register(vec![Key::Char('C')], "change_to_end");
+        keys.n_register(vec![Key::Char('s')], "substitute_char");
+        keys.n_register(vec![Key::Char('S')], "change_line");
+        keys.n_register(vec![Key::Char('c'), Key::Char('c')], "change_line");
+        keys.n_register(vec![Key::Char('J')], "join_lines");
+        keys.n_register(vec![Key::Char('g'), Key::Char('J')], "join_lines_raw");
+        keys.n_register(vec![Key::Char('~')], "toggle_case");
+
+        // Case, Number
+        keys.nv_register(vec![Key::Char('g'), Key::Char('u')], "lowercase");
+        keys.nv_register(vec![Key::Char('g'), Key::Char('U')], "uppercase");
+        keys.nv_register(vec![Key::Char('g'), Key::Char('~')], "switch_case");
+        keys.v_register(vec![Key::Char('u')], "lowercase");
+        keys.v_register(vec![Key::Char('U')], "uppercase");
+        keys.v_register(vec![Key::Char('~')], "switch_case");
+        keys.n_register(vec![Key::Ctrl('a')], "increment");
+        keys.n_register(vec![Key::Ctrl('x')], "decrement");
+        keys.v_register(vec![Key::Ctrl('a')], "visual_increment");
+        keys.v_register(vec![Key::Ctrl('x')], "visual_decrement");
+        keys.v_register(
+            vec![Key::Char('g'), Key::Ctrl('a')],
+            "visual_increment_sequence",
+        );
+        keys.v_register(
+            vec![Key::Char('g'), Key::Ctrl('x')],
+            "visual_decrement_sequence",
+        );
+
         // Cut, Copy, Paste
         keys.n_register(vec![Key::Char('d'), Key::Char('d')], "delete_line");
         keys.n_register(vec![Key::Char('y'), Key::Char('y')], "copy_line");
+        keys.n_register(vec![Key::Char('Y')], "copy_line");
         keys.n_register(vec![Key::Char('p')], "paste_after");
         keys.n_register(vec![Key::Char('P')], "paste_before");
-        keys.v_register(vec![Key::Char('y')], "copy");
-        keys.v_register(vec![Key::Char('d')], "delete");
+        keys.v_register(vec![Key::Char('y')], "visual_yank");
+        keys.v_register(vec![Key::Char('d')], "visual_delete");
 
         // Undo, Redo
         keys.n_register(vec![Key::Char('u')], "undo");
@@ -102,8 +161,67 @@ impl EditorKeys {
         keys.n_register(vec![Key::Char('<'), Key::Char('<')], "de-indent");
         keys.n_register(vec![Key::Char('>'), Key::Char('%')], "block_indent");
         keys.n_register(vec![Key::Char('<'), Key::Char('%')], "block_de-indent");
-        keys.v_register(vec![Key::Char('>')], "indent");
-        keys.v_register(vec![Key::Char('<')], "indent");
+        keys.v_register(vec![Key::Char('>')], "visual_indent");
+        keys.v_register(vec![Key::Char('<')], "visual_de-indent");
+        keys.nv_register(vec![Key::Char('=')], "reindent");
+
+        // Multiple cursors
+        keys.nv_register(vec![Key::Ctrl('n')], "add_cursor_next_match");
+        keys.nv_register(vec![Key::Ctrl('j')], "add_cursor_below");
+        keys.nv_register(vec![Key::Ctrl('k')], "add_cursor_above");
+        keys.n_register(vec![Key::Esc], "single_cursor");
+
+        // Comment
+        keys.nv_register(vec![Key::Char('g'), Key::Char('c')], "comment");
+
+        // Filter
+        keys.nv_register(vec![Key::Char('!')], "filter");
+
+        // Buffer
+        // Terminals send Ctrl-^ as Ctrl-6
+        keys.n_register(vec![Key::Ctrl('^')], "alternate_buffer");
+        keys.n_register(vec![Key::Ctrl('6')], "alternate_buffer");
+
+        // Window
+        let window_keys = [
+            ('s', "window_split"),
+            ('v', "window_vsplit"),
+            ('h', "window_left"),
+            ('j', "window_down"),
+            ('k', "window_up"),
+            ('l', "window_right"),
+            ('w', "window_next"),
+            ('c', "window_close"),
+            ('o', "window_only"),
+            ('=', "window_equalize"),
+            ('+', "window_increase_height"),
+            ('-', "window_decrease_height"),
+            ('>', "window_increase_width"),
+            ('<', "window_decrease_width"),
+        ];
+        for (c, cmd) in window_keys {
+            keys.n_register(vec![Key::Ctrl('w'), Key::Char(c)], cmd);
+        }
+
+        // Quickfix
+        keys.n_register(vec![Key::Enter], "open_list_item");
+
+        // Tab
+        keys.n_register(vec![Key::Char('g'), Key::Char('t')], "next_tab");
+        keys.n_register(vec![Key::Char('g'), Key::Char('T')], "prev_tab");
+
+        // Syntax tree
+        let text_objects = [('f', "function"), ('c', "class"), ('a', "argument")];
+        for (c, object) in text_objects {
+            let outer = format!("select_{}_outer", object);
+            let inner = format!("select_{}_inner", object);
+            keys.ov_register(vec![Key::Char('a'), Key::Char(c)], &outer);
+            keys.ov_register(vec![Key::Char('i'), Key::Char(c)], &inner);
+        }
+        keys.ov_register(vec![Key::Char('a'), Key::Char('n')], "expand_selection");
+        keys.ov_register(vec![Key::Char('i'), Key::Char('n')], "shrink_selection");
+        keys.nv_register(vec![Key::Char(']'), Key::Char('m')], "next_function");
+        keys.nv_register(vec![Key::Char('['), Key::Char('m')], "prev_function");
 
         keys
     }
@@ -112,4 +230,15 @@ impl EditorKeys {
         self.keys
             .get(&(mode, keys.iter().map(|key| Key::from(*key)).collect()))
     }
+
+    pub fn get_pending(&self, keys: &[(KeyCode, KeyModifiers)]) -> Option<Option<&String>> {
+        let keys: Vec<Key> = keys.iter().map(|key| Key::from(*key)).collect();
+        if let Some(cmd) = self.pending_keys.get(&keys) {
+            return Some(Some(cmd));
+        }
+        self.pending_keys
+            .keys()
+            .any(|pending| pending.starts_with(&keys))
+            .then_some(None)
+    }
 }
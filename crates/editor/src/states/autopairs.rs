@@ -0,0 +1,143 @@
+use crate::states::EditorState;
+
+impl EditorState {
+    fn pairs(&self) -> Vec<(char, char)> {
+        let chars: Vec<char> = self.options.pairs.chars().collect();
+        chars.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+
+    fn chars_around_cursor(&self) -> (Option<char>, Option<char>) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let line = self.buf.get_line(cursor_y);
+
+        (
+            line[..cursor_x].chars().last(),
+            line[cursor_x..].chars().next(),
+        )
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let (prev, next) = self.chars_around_cursor();
+        let pairs = if self.options.autopairs {
+            self.pairs()
+        } else {
+            Vec::new()
+        };
+
+        if next == Some(c) && pairs.iter().any(|(_, close)| *close == c) {
+            self.cursor.move_by(&self.buf, 1, 0);
+            return;
+        }
+
+        let close = pairs
+            .iter()
+            .find(|(open, _)| *open == c)
+            .map(|(_, close)| *close);
+        let before_space = match next {
+            None => true,
+            Some(next) => next.is_whitespace() || pairs.iter().any(|(_, close)| *close == next),
+        };
+        // Don't pair a quote right after a word (don't)
+        let after_word = prev.is_some_and(|prev| prev.is_alphanumeric() || prev == c);
+
+        self.buf.insert(c, cursor_x, cursor_y);
+        self.cursor.move_by(&self.buf, 1, 0);
+
+        match close {
+            Some(close) if before_space && !(close == c && after_word) => {
+                self.buf.insert(close, cursor_x + c.len_utf8(), cursor_y);
+            }
+            _ => self.dedent_closer(c),
+        }
+    }
+
+    pub fn delete_pair(&mut self) -> bool {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let (Some(prev), Some(next)) = self.chars_around_cursor() else {
+            return false;
+        };
+
+        if !self.options.autopairs || !self.pairs().contains(&(prev, next)) {
+            return false;
+        }
+
+        self.buf.delete(cursor_x, cursor_y);
+        self.buf.delete(cursor_x - prev.len_utf8(), cursor_y);
+        self.cursor.move_by(&self.buf, -1, 0);
+
+        true
+    }
+
+    pub fn open_pair_block(&mut self) -> bool {
+        let (Some(prev), Some(next)) = self.chars_around_cursor() else {
+            return false;
+        };
+
+        if !self.options.autopairs || prev == next || !self.pairs().contains(&(prev, next)) {
+            return false;
+        }
+
+        self.insert_newline();
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        self.buf.insert_line(cursor_y, String::new());
+        let width = self.compute_indent(cursor_y);
+        self.set_indent(cursor_y, width);
+
+        self.cursor.move_y_to(&self.buf, cursor_y);
+        let x = self.buf.line_length(cursor_y);
+        self.cursor.move_x_to(&self.buf, x);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::filetype::FileType;
+
+    fn type_str(state: &mut EditorState, s: &str) {
+        for c in s.chars() {
+            state.insert_char(c);
+        }
+    }
+
+    #[test]
+    fn test_insert_pairs() {
+        let mut state = EditorState::new(String::new(), None);
+        state.options.set_filetype(FileType::Python);
+
+        type_str(&mut state, "f(\"a");
+        assert_eq!(state.buf.to_string(), "f(\"a\")");
+        type_str(&mut state, "\")");
+        assert_eq!(state.buf.to_string(), "f(\"a\")");
+        assert_eq!(state.cursor.get_display(&state.buf), (6, 0));
+
+        type_str(&mut state, " don't");
+        assert_eq!(state.buf.to_string(), "f(\"a\") don't");
+    }
+
+    #[test]
+    fn test_rust_lifetime() {
+        let mut state = EditorState::new(String::new(), None);
+        state.options.set_filetype(FileType::Rust);
+
+        type_str(&mut state, "&'a [");
+        assert_eq!(state.buf.to_string(), "&'a []");
+        assert!(state.delete_pair());
+        assert_eq!(state.buf.to_string(), "&'a ");
+    }
+
+    #[test]
+    fn test_open_pair_block() {
+        let mut state = EditorState::new(String::new(), None);
+        state.options.set_filetype(FileType::Rust);
+        state.options.shiftwidth = 4;
+
+        type_str(&mut state, "fn f() {");
+        assert!(state.open_pair_block());
+        assert_eq!(state.buf.to_string(), "fn f() {\n    \n}");
+        assert_eq!(state.cursor.get_display(&state.buf), (4, 1));
+    }
+}
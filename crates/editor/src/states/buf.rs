@@ -1,30 +1,63 @@
-use std::fmt::{self, Display, Formatter};
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use utils::text::lines;
 
+static NEXT_VERSION: AtomicUsize = AtomicUsize::new(1);
+
+fn next_version() -> usize {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
 #[derive(Clone)]
 pub struct CodeBuffer {
     lines: Vec<String>,
+    version: usize,
 }
 
 impl CodeBuffer {
     pub fn new(buf: String) -> Self {
-        Self { lines: lines(buf) }
+        Self {
+            lines: lines(buf),
+            version: next_version(),
+        }
+    }
+
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    fn changed(&mut self) {
+        self.version = next_version();
     }
 
     pub fn insert(&mut self, c: char, x: usize, y: usize) {
+        self.changed();
         self.lines[y].insert(x, c);
     }
 
     pub fn insert_str(&mut self, s: &str, x: usize, y: usize) {
+        self.changed();
         self.lines[y].insert_str(x, s);
     }
 
-    pub fn insert_line(&mut self) {
-        self.lines.push(String::new());
+    pub fn insert_line(&mut self, y: usize, line: String) {
+        self.changed();
+        self.lines.insert(y, line);
+    }
+
+    pub fn remove_line(&mut self, y: usize) -> String {
+        self.changed();
+        if self.line_count() == 1 {
+            return std::mem::take(&mut self.lines[0]);
+        }
+        self.lines.remove(y)
     }
 
     pub fn delete(&mut self, x: usize, y: usize) {
+        self.changed();
         self.lines[y].remove(x);
     }
 
@@ -32,6 +65,7 @@ impl CodeBuffer {
     // yには上の行を指定する
     pub fn join_lines(&mut self, y: usize) {
         if y + 1 < self.line_count() {
+            self.changed();
             let combined = self.lines[y].clone() + &self.lines[y + 1];
             self.lines[y] = combined;
             self.lines.remove(y + 1);
@@ -39,12 +73,18 @@ impl CodeBuffer {
     }
 
     pub fn split_line(&mut self, x: usize, y: usize) {
+        self.changed();
         let original = self.lines[y].clone();
         let (p0, p1) = original.split_at(x);
         self.lines[y] = p0.to_string();
         self.lines.insert(y + 1, p1.to_string());
     }
 
+    pub fn set_line(&mut self, y: usize, line: String) {
+        self.changed();
+        self.lines[y] = line;
+    }
+
     pub fn line_count(&self) -> usize {
         self.lines.len()
     }
@@ -53,8 +93,15 @@ impl CodeBuffer {
         self.lines[line].len()
     }
 
-    pub fn get_lines(&self) -> Vec<String> {
-        self.lines.clone()
+    pub fn get_line(&self, line: usize) -> &str {
+        &self.lines[line]
+    }
+}
+
+// Same version means same contents
+impl PartialEq for CodeBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.version == other.version || self.lines == other.lines
     }
 }
 
@@ -0,0 +1,781 @@
+use std::{
+    env,
+    fs::{read_to_string, write},
+    io::ErrorKind,
+    mem::swap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use utils::types::Vec2;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{
+        buf::CodeBuffer, cursor::EditorCursor, file::absolute_path, filetype::FileType,
+        history::History, options::EditorOptions, syntax::Highlighter, syntax_tree::SyntaxTree,
+        EditorState,
+    },
+};
+
+#[derive(Clone)]
+pub struct Buffer {
+    pub id: usize,
+    buf: CodeBuffer,
+    saved_buf: CodeBuffer,
+    cursor: EditorCursor,
+    offset: Vec2<usize>,
+    history: History,
+    highlighter: Highlighter,
+    syntax_tree: SyntaxTree,
+    options: EditorOptions,
+    path: Option<PathBuf>,
+}
+
+impl Buffer {
+    fn new(id: usize, text: String, path: Option<PathBuf>, mut options: EditorOptions) -> Self {
+        let buf = CodeBuffer::new(text);
+        options.detect_indent(&buf);
+        if let Some(path) = &path {
+            options.set_filetype(FileType::detect(path));
+        }
+
+        Self {
+            id,
+            saved_buf: buf.clone(),
+            buf,
+            cursor: EditorCursor::default(),
+            offset: Vec2::default(),
+            history: History::default(),
+            highlighter: Highlighter::default(),
+            syntax_tree: SyntaxTree::default(),
+            options,
+            path,
+        }
+    }
+
+    fn open(id: usize, path: PathBuf, options: EditorOptions) -> Result<Self> {
+        let text = read_file(&path)?;
+        Ok(Self::new(id, text, Some(absolute_path(&path)), options))
+    }
+
+    fn is_modified(&self) -> bool {
+        self.buf != self.saved_buf
+    }
+}
+
+// An empty buffer saved over an unreadable file would wipe it
+pub fn read_file(path: &Path) -> Result<String> {
+    let text = match read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(anyhow!("Can't open file {}: {}", path.display(), err)),
+    };
+    if text.is_empty() {
+        return Ok(String::from("\n"));
+    }
+    Ok(text)
+}
+
+pub fn display_name(path: Option<&Path>) -> String {
+    let Some(path) = path else {
+        return "[No Name]".to_string();
+    };
+    let relative = env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf));
+    relative.as_deref().unwrap_or(path).display().to_string()
+}
+
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => {
+            let cwd = env::current_dir().unwrap_or_default();
+            cwd.join(a) == cwd.join(b)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BufferList {
+    buffers: Vec<Buffer>,
+    current: usize,
+    alternate: Option<usize>,
+    next_id: usize,
+    args: Vec<PathBuf>,
+    arg_index: usize,
+}
+
+impl BufferList {
+    pub fn new() -> Self {
+        Self {
+            buffers: vec![Buffer::new(
+                1,
+                String::new(),
+                None,
+                EditorOptions::default(),
+            )],
+            current: 1,
+            alternate: None,
+            next_id: 2,
+            args: Vec::new(),
+            arg_index: 0,
+        }
+    }
+
+    fn index_of(&self, id: usize) -> Option<usize> {
+        self.buffers.iter().position(|buffer| buffer.id == id)
+    }
+
+    fn get_mut(&mut self, id: usize) -> &mut Buffer {
+        let index = self.index_of(id).unwrap();
+        &mut self.buffers[index]
+    }
+
+    fn take_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+}
+
+impl EditorState {
+    pub fn buffer_name(&self) -> String {
+        let name = display_name(self.path.as_deref());
+        if self.is_modified() {
+            format!("{} [+]", name)
+        } else {
+            name
+        }
+    }
+
+    fn swap_buffer(&mut self, id: usize) {
+        let buffer = self.buffers.get_mut(id);
+        swap(&mut buffer.buf, &mut self.buf);
+        swap(&mut buffer.saved_buf, &mut self.saved_buf);
+        swap(&mut buffer.cursor, &mut self.cursor);
+        swap(&mut buffer.offset, &mut self.offset);
+        swap(&mut buffer.history, &mut self.history);
+        swap(&mut buffer.highlighter, &mut self.highlighter);
+        swap(&mut buffer.syntax_tree, &mut self.syntax_tree);
+        swap(&mut buffer.options, &mut self.options);
+        swap(&mut buffer.path, &mut self.path);
+    }
+
+    pub fn buffers_current(&self) -> usize {
+        self.buffers.current
+    }
+
+    pub fn activate_buffer(&mut self, id: usize) {
+        if id == self.buffers.current {
+            return;
+        }
+        self.swap_buffer(self.buffers.current);
+        self.swap_buffer(id);
+        self.buffers.current = id;
+    }
+
+    pub fn switch_buffer(&mut self, id: usize) {
+        if id == self.buffers.current {
+            return;
+        }
+
+        self.cancel_change();
+        self.buffers.alternate = Some(self.buffers.current);
+        self.activate_buffer(id);
+        self.cursors.clear();
+        self.visual_marks = None;
+        self.pending_op = None;
+        self.show_buffer_info();
+    }
+
+    fn show_buffer_info(&mut self) {
+        let name = display_name(self.path.as_deref());
+        let modified = if self.is_modified() { " [+]" } else { "" };
+        self.set_message(format!(
+            "\"{}\"{} {}L",
+            name,
+            modified,
+            self.buf.line_count()
+        ));
+    }
+
+    pub fn buffer_label(&self, id: usize) -> String {
+        if id == self.buffers.current {
+            return self.buffer_name();
+        }
+        let index = self.buffers.index_of(id).unwrap();
+        let buffer = &self.buffers.buffers[index];
+        let name = display_name(buffer.path.as_deref());
+        if buffer.is_modified() {
+            format!("{} [+]", name)
+        } else {
+            name
+        }
+    }
+
+    pub fn buffer_ids(&self) -> Vec<usize> {
+        self.buffers
+            .buffers
+            .iter()
+            .map(|buffer| buffer.id)
+            .collect()
+    }
+
+    pub fn buffer_text(&self, id: usize) -> &CodeBuffer {
+        if id == self.buffers.current {
+            return &self.buf;
+        }
+        let index = self.buffers.index_of(id).unwrap();
+        &self.buffers.buffers[index].buf
+    }
+
+    pub fn buffer_path(&self, id: usize) -> Option<&Path> {
+        if id == self.buffers.current {
+            return self.path.as_deref();
+        }
+        let index = self.buffers.index_of(id).unwrap();
+        self.buffers.buffers[index].path.as_deref()
+    }
+
+    pub fn copy_buffer_to(&mut self, id: usize, end: usize, view: &mut EditorState) {
+        let (buf, saved_buf, highlighter, options, path) = if id == self.buffers.current {
+            self.highlighter
+                .update(&self.buf, self.options.filetype, end);
+            (
+                &self.buf,
+                &self.saved_buf,
+                &self.highlighter,
+                &self.options,
+                &self.path,
+            )
+        } else {
+            let buffer = self.buffers.get_mut(id);
+            buffer
+                .highlighter
+                .update(&buffer.buf, buffer.options.filetype, end);
+            (
+                &buffer.buf,
+                &buffer.saved_buf,
+                &buffer.highlighter,
+                &buffer.options,
+                &buffer.path,
+            )
+        };
+        view.buf = buf.clone();
+        view.saved_buf = saved_buf.clone();
+        view.highlighter = highlighter.clone();
+        view.options = options.clone();
+        view.path = path.clone();
+    }
+
+    pub fn set_buffer_text(&mut self, id: usize, text: String) {
+        let buf = CodeBuffer::new(text);
+        if id == self.buffers.current {
+            self.buf = buf;
+            self.saved_buf = self.buf.clone();
+            self.cursor.clamp(&self.buf);
+        } else {
+            let buffer = self.buffers.get_mut(id);
+            buffer.buf = buf;
+            buffer.saved_buf = buffer.buf.clone();
+            buffer.cursor.clamp(&buffer.buf);
+        }
+    }
+
+    pub fn new_buffer(&mut self) -> usize {
+        let id = self.buffers.take_id();
+        let buffer = Buffer::new(id, "\n".to_string(), None, self.options.clone());
+        self.buffers.buffers.push(buffer);
+        id
+    }
+
+    fn add_buffer(&mut self, path: PathBuf) -> Result<usize> {
+        let buffer = Buffer::open(self.buffers.next_id, path, self.options.clone())?;
+        let id = self.buffers.take_id();
+        self.buffers.buffers.push(buffer);
+        Ok(id)
+    }
+
+    fn find_buffer(&self, path: &Path) -> Option<usize> {
+        if self.path.as_deref().is_some_and(|p| same_file(p, path)) {
+            return Some(self.buffers.current);
+        }
+        self.buffers
+            .buffers
+            .iter()
+            .find(|buffer| buffer.path.as_deref().is_some_and(|p| same_file(p, path)))
+            .map(|buffer| buffer.id)
+    }
+
+    pub fn open_buffer(&mut self, path: PathBuf) -> Result<()> {
+        let id = match self.find_buffer(&path) {
+            Some(id) => id,
+            None => self.add_buffer(path)?,
+        };
+        self.switch_buffer(id);
+        Ok(())
+    }
+
+    pub fn reload_buffer(&mut self, path: PathBuf) -> Result<()> {
+        let id = self.buffers.current;
+        let buffer = Buffer::open(id, path, self.options.clone())?;
+        self.cancel_change();
+        *self.buffers.get_mut(id) = buffer;
+        self.swap_buffer(id);
+        self.cursors.clear();
+        self.visual_marks = None;
+        self.show_buffer_info();
+        Ok(())
+    }
+
+    pub fn is_file_buffer_modified(&self, path: &Path) -> bool {
+        self.find_buffer(path)
+            .is_some_and(|id| self.is_buffer_modified(id))
+    }
+
+    pub fn reload_file_buffer(&mut self, path: &Path) {
+        let Some(id) = self.find_buffer(path) else {
+            return;
+        };
+        if self.is_buffer_modified(id) {
+            return;
+        }
+
+        let current = id == self.buffers.current;
+        if current {
+            self.cancel_change();
+            self.swap_buffer(id);
+        }
+        let buffer = self.buffers.get_mut(id);
+        let path = buffer.path.clone().unwrap_or_else(|| path.to_path_buf());
+        if let Ok(mut reloaded) = Buffer::open(id, path, buffer.options.clone()) {
+            reloaded.cursor = buffer.cursor.clone();
+            reloaded.cursor.clamp(&reloaded.buf);
+            reloaded.offset = buffer.offset;
+            *buffer = reloaded;
+        }
+        if current {
+            self.swap_buffer(id);
+        }
+    }
+
+    pub fn set_args(&mut self, paths: Vec<PathBuf>) {
+        for path in paths.iter().skip(1) {
+            if self.find_buffer(path).is_none() {
+                if let Err(err) = self.add_buffer(path.clone()) {
+                    self.set_message(err.to_string());
+                }
+            }
+        }
+        self.buffers.args = paths.iter().map(|path| absolute_path(path)).collect();
+        self.buffers.arg_index = 0;
+    }
+
+    fn is_buffer_modified(&self, id: usize) -> bool {
+        if id == self.buffers.current {
+            self.is_modified()
+        } else {
+            let index = self.buffers.index_of(id).unwrap();
+            self.buffers.buffers[index].is_modified()
+        }
+    }
+
+    pub fn rename_buffer_paths(&mut self, from: &Path, to: &Path) {
+        let cwd = env::current_dir().unwrap_or_default();
+        let rename = |path: &mut Option<PathBuf>| {
+            let Some(old) = path.as_ref().map(|path| cwd.join(path)) else {
+                return;
+            };
+            if let Ok(rest) = old.strip_prefix(from) {
+                *path = Some(if rest.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(rest)
+                });
+            }
+        };
+
+        rename(&mut self.path);
+        for buffer in self.buffers.buffers.iter_mut() {
+            if buffer.id != self.buffers.current {
+                rename(&mut buffer.path);
+            }
+        }
+    }
+
+    pub fn buffer_paths(&self) -> Vec<PathBuf> {
+        let others = self
+            .buffers
+            .buffers
+            .iter()
+            .filter(|buffer| buffer.id != self.buffers.current)
+            .filter_map(|buffer| buffer.path.clone());
+        self.path.clone().into_iter().chain(others).collect()
+    }
+
+    fn parse_buffer(&self, arg: &str) -> Result<usize> {
+        if let Ok(id) = arg.parse::<usize>() {
+            return self
+                .buffers
+                .index_of(id)
+                .map(|_| id)
+                .ok_or_else(|| anyhow!("Buffer {} does not exist", id));
+        }
+
+        let matches: Vec<usize> = self
+            .buffers
+            .buffers
+            .iter()
+            .filter(|buffer| {
+                let path = if buffer.id == self.buffers.current {
+                    self.path.as_deref()
+                } else {
+                    buffer.path.as_deref()
+                };
+                display_name(path).contains(arg)
+            })
+            .map(|buffer| buffer.id)
+            .collect();
+        match matches.as_slice() {
+            [id] => Ok(*id),
+            [] => Err(anyhow!("No matching buffer for {}", arg)),
+            _ => Err(anyhow!("More than one match for {}", arg)),
+        }
+    }
+
+    fn write_all(&mut self, _args: &str) -> Result<()> {
+        let current = self.buffers.current;
+        let mut count = 0;
+        for buffer in self.buffers.buffers.iter_mut() {
+            if buffer.id == current || !buffer.is_modified() {
+                continue;
+            }
+            let path = buffer
+                .path
+                .as_ref()
+                .ok_or_else(|| anyhow!("No file name for buffer {}", buffer.id))?;
+            write(path, buffer.buf.to_string())
+                .map_err(|err| anyhow!("Can't open file for writing: {}", err))?;
+            buffer.saved_buf = buffer.buf.clone();
+            count += 1;
+        }
+
+        if self.is_modified() {
+            let path = self.current_path()?;
+            self.cmd_range = None;
+            self.write_to(&path, false)?;
+            count += 1;
+        }
+        self.set_message(format!("{} buffers written", count));
+        Ok(())
+    }
+
+    // :ls
+    fn list_buffers(&mut self, _args: &str) -> Result<()> {
+        let lines: Vec<String> = self
+            .buffers
+            .buffers
+            .iter()
+            .map(|buffer| {
+                let current = buffer.id == self.buffers.current;
+                let (path, cursor_y) = if current {
+                    (self.path.as_deref(), self.cursor.get_display(&self.buf).1)
+                } else {
+                    (
+                        buffer.path.as_deref(),
+                        buffer.cursor.get_display(&buffer.buf).1,
+                    )
+                };
+                let flag = if current {
+                    "%a"
+                } else if Some(buffer.id) == self.buffers.alternate {
+                    "# "
+                } else {
+                    "  "
+                };
+                let modified = if self.is_buffer_modified(buffer.id) {
+                    "+"
+                } else {
+                    " "
+                };
+                format!(
+                    "{:3} {} {} \"{}\" line {}",
+                    buffer.id,
+                    flag,
+                    modified,
+                    display_name(path),
+                    cursor_y + 1
+                )
+            })
+            .collect();
+        self.set_message(lines.join("\n"));
+        Ok(())
+    }
+
+    // :b {n|name}
+    fn ex_buffer(&mut self, args: &str) -> Result<()> {
+        if args.is_empty() {
+            self.show_buffer_info();
+            return Ok(());
+        }
+        let id = self.parse_buffer(args)?;
+        self.switch_buffer(id);
+        Ok(())
+    }
+
+    fn cycle_buffer(&mut self, delta: isize) {
+        let len = self.buffers.buffers.len() as isize;
+        let index = self.buffers.index_of(self.buffers.current).unwrap() as isize;
+        let index = (index + delta).rem_euclid(len) as usize;
+        self.switch_buffer(self.buffers.buffers[index].id);
+    }
+
+    fn ex_buffer_next(&mut self, args: &str) -> Result<()> {
+        let count = args.parse::<isize>().unwrap_or(1);
+        self.cycle_buffer(count);
+        Ok(())
+    }
+
+    fn ex_buffer_prev(&mut self, args: &str) -> Result<()> {
+        let count = args.parse::<isize>().unwrap_or(1);
+        self.cycle_buffer(-count);
+        Ok(())
+    }
+
+    fn ex_buffer_delete(&mut self, args: &str) -> Result<()> {
+        let (force, args) = match args.strip_prefix('!') {
+            Some(rest) => (true, rest.trim()),
+            None => (false, args),
+        };
+        let id = if args.is_empty() {
+            self.buffers.current
+        } else {
+            self.parse_buffer(args)?
+        };
+
+        if self.is_buffer_modified(id) && !force {
+            return Err(anyhow!(
+                "No write since last change for buffer {} (add ! to override)",
+                id
+            ));
+        }
+
+        self.delete_buffer(id);
+        Ok(())
+    }
+
+    fn ex_quit(&mut self, args: &str) -> Result<()> {
+        let force = args.starts_with('!');
+        let exits = self.window_count() == 1 && self.tab_count() == 1;
+        if exits && !force {
+            if let Some(id) = self
+                .buffer_ids()
+                .into_iter()
+                .find(|id| self.is_buffer_modified(*id))
+            {
+                return Err(anyhow!(
+                    "No write since last change for buffer {} (add ! to override)",
+                    id
+                ));
+            }
+        }
+
+        self.quit();
+        Ok(())
+    }
+
+    pub fn delete_buffer(&mut self, id: usize) {
+        if id == self.buffers.current {
+            let index = self.buffers.index_of(id).unwrap();
+            let next = self
+                .buffers
+                .alternate
+                .filter(|alternate| self.buffers.index_of(*alternate).is_some())
+                .or_else(|| self.buffers.buffers.get(index + 1).map(|buffer| buffer.id))
+                .or_else(|| {
+                    index
+                        .checked_sub(1)
+                        .map(|index| self.buffers.buffers[index].id)
+                });
+            let next = match next {
+                Some(next) => next,
+                None => self.new_buffer(),
+            };
+            self.switch_buffer(next);
+        }
+
+        let index = self.buffers.index_of(id).unwrap();
+        self.buffers.buffers.remove(index);
+        self.replace_window_buffer(id, self.buffers.current);
+        if self.buffers.alternate == Some(id) {
+            self.buffers.alternate = None;
+        }
+    }
+
+    fn cmd_alternate_buffer(&mut self) {
+        let id = match self.count {
+            Some(count) => self.parse_buffer(&count.to_string()),
+            None => self
+                .buffers
+                .alternate
+                .ok_or_else(|| anyhow!("No alternate file")),
+        };
+        match id {
+            Ok(id) => self.switch_buffer(id),
+            Err(err) => self.set_message(err.to_string()),
+        }
+    }
+
+    fn move_arg(&mut self, delta: isize) -> Result<()> {
+        let index = self.buffers.arg_index as isize + delta;
+        if self.buffers.args.is_empty() || index < 0 {
+            return Err(anyhow!("Cannot go before first file"));
+        }
+        if index as usize >= self.buffers.args.len() {
+            return Err(anyhow!("Cannot go beyond last file"));
+        }
+
+        self.buffers.arg_index = index as usize;
+        self.open_buffer(self.buffers.args[index as usize].clone())
+    }
+
+    fn ex_next(&mut self, args: &str) -> Result<()> {
+        self.move_arg(args.parse::<isize>().unwrap_or(1))
+    }
+
+    fn ex_prev(&mut self, args: &str) -> Result<()> {
+        self.move_arg(-args.parse::<isize>().unwrap_or(1))
+    }
+
+    fn ex_args(&mut self, _args: &str) -> Result<()> {
+        let names: Vec<String> = self
+            .buffers
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let name = display_name(Some(path));
+                if i == self.buffers.arg_index {
+                    format!("[{}]", name)
+                } else {
+                    name
+                }
+            })
+            .collect();
+        self.set_message(names.join(" "));
+        Ok(())
+    }
+
+    pub fn register_buffer_cmds(cmds: &mut EditorCommand) {
+        type BufferCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let buffer_cmds: &[(&[&str], BufferCommand)] = &[
+            (&["ls", "buffers", "files"], EditorState::list_buffers),
+            (&["b", "buffer"], EditorState::ex_buffer),
+            (&["bn", "bnext"], EditorState::ex_buffer_next),
+            (
+                &["bp", "bprevious", "bN", "bNext"],
+                EditorState::ex_buffer_prev,
+            ),
+            (&["bd", "bdelete"], EditorState::ex_buffer_delete),
+            (&["n", "next"], EditorState::ex_next),
+            (&["N", "Next", "prev", "previous"], EditorState::ex_prev),
+            (&["args"], EditorState::ex_args),
+            (&["wa", "wall"], EditorState::write_all),
+            (&["q", "quit"], EditorState::ex_quit),
+        ];
+
+        for (names, f) in buffer_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+
+        cmds.register(
+            "alternate_buffer",
+            Box::new(|editor| editor.cmd_alternate_buffer()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switch_buffers() {
+        let mut state = EditorState::new("a\n".to_string(), Some(PathBuf::from("/tmp/ce-a")));
+        let b = state
+            .add_buffer(PathBuf::from("/tmp/ce-not-exist-b"))
+            .unwrap();
+        assert_eq!(b, 2);
+
+        state.buf.set_line(0, "x".to_string());
+        state.switch_buffer(b);
+        assert_eq!(state.buf.to_string(), "\n");
+        assert_eq!(state.get_path(), Some(PathBuf::from("/tmp/ce-not-exist-b")));
+        assert!(state.is_buffer_modified(1));
+
+        state.cmd_alternate_buffer();
+        assert_eq!(state.buffers.current, 1);
+        assert_eq!(state.buf.to_string(), "x\n");
+
+        assert!(state.ex_buffer_delete("").is_err());
+        state.ex_buffer_delete("!").unwrap();
+        assert_eq!(state.buffers.current, 2);
+        state.ex_buffer_delete("").unwrap();
+        assert_eq!(state.buffers.current, 3);
+        assert_eq!(state.get_path(), None);
+    }
+
+    #[test]
+    fn test_quit_with_modified_buffer() {
+        let mut state = EditorState::new("a\n".to_string(), None);
+        state.buf.set_line(0, "x".to_string());
+        let b = state.new_buffer();
+        state.switch_buffer(b);
+
+        assert!(state.ex_quit("").is_err());
+        assert!(!state.is_quit);
+        state.ex_quit("!").unwrap();
+        assert!(state.is_quit);
+    }
+
+    #[test]
+    fn test_open_unreadable_file() {
+        let path = env::temp_dir().join(format!("ce-invalid-{}.txt", std::process::id()));
+        write(&path, b"\xff\xfe\n").unwrap();
+
+        let mut state = EditorState::new("a\n".to_string(), None);
+        assert!(state.open_buffer(path.clone()).is_err());
+        assert!(state.find_buffer(&path).is_none());
+        assert_eq!(state.get_path(), None);
+        assert!(state.reload_buffer(path.clone()).is_err());
+        assert_eq!(state.buf.to_string(), "a\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let cwd = env::current_dir().unwrap();
+        let mut state = EditorState::new("a\n".to_string(), Some(PathBuf::from("ce-rel-a")));
+        state.set_args(vec![PathBuf::from("ce-rel-a"), PathBuf::from("ce-rel-b")]);
+        assert_eq!(state.get_path(), Some(cwd.join("ce-rel-a")));
+        assert_eq!(
+            state.buffers.args,
+            vec![cwd.join("ce-rel-a"), cwd.join("ce-rel-b")]
+        );
+        assert_eq!(
+            state.buffer_paths(),
+            vec![cwd.join("ce-rel-a"), cwd.join("ce-rel-b")]
+        );
+    }
+}
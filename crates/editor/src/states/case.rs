@@ -0,0 +1,73 @@
+use crate::{
+    cmd::EditorCommand,
+    states::{operator::TextRange, EditorState},
+};
+
+pub fn switch_case(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_uppercase() {
+                c.to_lowercase().to_string()
+            } else {
+                c.to_uppercase().to_string()
+            }
+        })
+        .collect()
+}
+
+impl EditorState {
+    pub fn map_range(&mut self, range: TextRange, f: impl Fn(&str) -> String) {
+        let end_y = range.end.y.min(self.buf.line_count() - 1);
+
+        for y in range.start.y..=end_y {
+            let line = self.buf.get_line(y);
+            let start = if range.linewise || y > range.start.y {
+                0
+            } else {
+                range.start.x.min(line.len())
+            };
+            let end = if range.linewise || y < range.end.y {
+                line.len()
+            } else {
+                range.end.x.min(line.len())
+            };
+
+            let new_line = line[..start].to_string() + &f(&line[start..end]) + &line[end..];
+            self.buf.set_line(y, new_line);
+        }
+    }
+
+    pub fn register_case_cmds(cmds: &mut EditorCommand) {
+        cmds.register_operator(
+            "lowercase",
+            Box::new(|editor, range| editor.map_range(range, |s| s.to_lowercase())),
+        );
+        cmds.register_operator(
+            "uppercase",
+            Box::new(|editor, range| editor.map_range(range, |s| s.to_uppercase())),
+        );
+        cmds.register_operator(
+            "switch_case",
+            Box::new(|editor, range| editor.map_range(range, switch_case)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::types::Vec2;
+
+    #[test]
+    fn test_map_range() {
+        let mut state = EditorState::new("abc def\nGhi jkl".to_string(), None);
+
+        state.map_range(TextRange::chars(Vec2::new(4, 0), Vec2::new(3, 1)), |s| {
+            s.to_uppercase()
+        });
+        assert_eq!(state.buf.to_string(), "abc DEF\nGHI jkl");
+
+        state.map_range(TextRange::lines(1, 1), switch_case);
+        assert_eq!(state.buf.to_string(), "abc DEF\nghi JKL");
+    }
+}
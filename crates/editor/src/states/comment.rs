@@ -0,0 +1,114 @@
+use utils::text::{indent_len, indent_width, index_at_col};
+
+use crate::{cmd::EditorCommand, states::EditorState};
+
+impl EditorState {
+    fn comment_markers(&self) -> (String, String) {
+        let cms = &self.options.commentstring;
+        let (left, right) = cms.split_once("%s").unwrap_or((cms, ""));
+
+        (left.trim().to_string(), right.trim().to_string())
+    }
+
+    fn is_commented(line: &str, left: &str, right: &str) -> bool {
+        let code = line.trim();
+        code.len() >= left.len() + right.len() && code.starts_with(left) && code.ends_with(right)
+    }
+
+    pub fn toggle_comment(&mut self, start: usize, end: usize) {
+        let end = end.min(self.buf.line_count() - 1);
+        let (left, right) = self.comment_markers();
+        let ys: Vec<usize> = (start..=end)
+            .filter(|&y| !self.buf.get_line(y).trim().is_empty())
+            .collect();
+
+        let uncomment = ys
+            .iter()
+            .all(|&y| Self::is_commented(self.buf.get_line(y), &left, &right));
+
+        if uncomment {
+            for &y in &ys {
+                let line = self.buf.get_line(y);
+                let (indent, code) = line.split_at(indent_len(line));
+                let code = &code.trim_end()[left.len()..];
+                let code = &code[..code.len() - right.len()];
+                let code = code.strip_prefix(' ').unwrap_or(code);
+                let code = code.strip_suffix(' ').unwrap_or(code);
+
+                let line = if code.is_empty() {
+                    String::new()
+                } else {
+                    indent.to_string() + code
+                };
+                self.buf.set_line(y, line);
+            }
+            return;
+        }
+
+        let tabstop = self.options.tabstop;
+        let Some(col) = ys
+            .iter()
+            .map(|&y| indent_width(self.buf.get_line(y), tabstop))
+            .min()
+        else {
+            return;
+        };
+
+        for &y in &ys {
+            let line = self.buf.get_line(y);
+            let x = index_at_col(line, col, tabstop);
+            let mut commented = format!("{}{} {}", &line[..x], left, &line[x..]);
+            if !right.is_empty() {
+                commented = format!("{} {}", commented, right);
+            }
+            self.buf.set_line(y, commented);
+        }
+    }
+
+    pub fn register_comment_cmds(cmds: &mut EditorCommand) {
+        cmds.register_operator(
+            "comment",
+            Box::new(|editor, range| editor.toggle_comment(range.start.y, range.end.y)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::filetype::FileType;
+
+    #[test]
+    fn test_toggle_comment() {
+        let mut state = EditorState::new("fn f() {\n    a();\n\n  b();\n}".to_string(), None);
+        state.options.set_filetype(FileType::Rust);
+
+        state.toggle_comment(1, 3);
+        assert_eq!(
+            state.buf.to_string(),
+            "fn f() {\n  //   a();\n\n  // b();\n}"
+        );
+        state.toggle_comment(1, 3);
+        assert_eq!(state.buf.to_string(), "fn f() {\n    a();\n\n  b();\n}");
+    }
+
+    #[test]
+    fn test_toggle_mixed_comment() {
+        let mut state = EditorState::new("# a\nb".to_string(), None);
+        state.options.set_filetype(FileType::Python);
+
+        state.toggle_comment(0, 1);
+        assert_eq!(state.buf.to_string(), "# # a\n# b");
+    }
+
+    #[test]
+    fn test_toggle_block_comment() {
+        let mut state = EditorState::new("<p>a</p>".to_string(), None);
+        state.options.set_filetype(FileType::Html);
+
+        state.toggle_comment(0, 0);
+        assert_eq!(state.buf.to_string(), "<!-- <p>a</p> -->");
+        state.toggle_comment(0, 0);
+        assert_eq!(state.buf.to_string(), "<p>a</p>");
+    }
+}
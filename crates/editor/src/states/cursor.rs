@@ -1,5 +1,4 @@
-use anyhow::Result;
-use utils::{cli::terminal_size, types::Vec2};
+use utils::text::{display_width, index_at_col};
 
 use crate::{cmd::EditorCommand, states::buf::CodeBuffer};
 
@@ -10,15 +9,11 @@ pub struct EditorCursor {
 }
 
 impl EditorCursor {
-    pub fn move_by(
-        &mut self,
-        buf: &CodeBuffer,
-        offset: &mut Vec2<usize>,
-        x: isize,
-        y: isize,
-    ) -> Result<()> {
-        let (_, term_h) = terminal_size()?;
-        let term_h = term_h - 1;
+    pub fn new(x: usize, y: usize) -> Self {
+        Self { x, y }
+    }
+
+    pub fn move_by(&mut self, buf: &CodeBuffer, x: isize, y: isize) {
         let buf_len = buf.line_count();
         let line_len = buf.line_length(self.y);
 
@@ -46,23 +41,11 @@ impl EditorCursor {
             } else {
                 self.y -= -y as usize;
             }
-
-            if self.y < offset.y {
-                offset.y = self.y;
-            }
+        } else if self.y + y as usize > buf_len - 1 {
+            self.y = buf_len - 1;
         } else {
-            if self.y + y as usize > buf_len - 1 {
-                self.y = buf_len - 1;
-            } else {
-                self.y += y as usize;
-            }
-
-            if self.y >= offset.y + term_h {
-                offset.y = self.y - term_h + 1;
-            }
+            self.y += y as usize;
         }
-
-        Ok(())
     }
 
     pub fn move_x_to(&mut self, buf: &CodeBuffer, x: usize) {
@@ -78,13 +61,17 @@ impl EditorCursor {
     pub fn move_y_to(&mut self, buf: &CodeBuffer, y: usize) {
         let buf_len = buf.line_count();
 
-        if y > buf_len {
-            self.y = buf_len;
+        if y >= buf_len {
+            self.y = buf_len - 1;
         } else {
             self.y = y;
         }
     }
 
+    pub fn clamp(&mut self, buf: &CodeBuffer) {
+        self.move_y_to(buf, self.y);
+    }
+
     pub fn get_display(&self, buf: &CodeBuffer) -> (usize, usize) {
         let line_len = buf.line_length(self.y);
         let x = if self.x > line_len { line_len } else { self.x };
@@ -92,30 +79,101 @@ impl EditorCursor {
         (x, self.y)
     }
 
-    pub fn cmd_left(&mut self, buf: &CodeBuffer, offset: &mut Vec2<usize>) {
-        let _ = self.move_by(buf, offset, -1, 0);
+    // Brackets are ASCII, so a byte search never stops inside a character
+    pub fn matching_bracket(&self, buf: &CodeBuffer) -> Option<(usize, usize)> {
+        let (cursor_x, cursor_y) = self.get_display(buf);
+        let line = buf.get_line(cursor_y).as_bytes();
+        let start_x = (cursor_x..line.len()).find(|&x| b"()[]{}".contains(&line[x]))?;
+
+        let (open, close, forward) = match line[start_x] {
+            b'(' => (b'(', b')', true),
+            b'[' => (b'[', b']', true),
+            b'{' => (b'{', b'}', true),
+            b')' => (b'(', b')', false),
+            b']' => (b'[', b']', false),
+            _ => (b'{', b'}', false),
+        };
+
+        let mut depth = 0;
+        let mut y = cursor_y;
+        let mut chars = line;
+        let mut x = start_x as isize;
+        loop {
+            while x >= 0 && (x as usize) < chars.len() {
+                let c = chars[x as usize];
+                if c == open {
+                    depth += if forward { 1 } else { -1 };
+                } else if c == close {
+                    depth += if forward { -1 } else { 1 };
+                }
+                if depth == 0 {
+                    return Some((x as usize, y));
+                }
+                x += if forward { 1 } else { -1 };
+            }
+
+            if forward && y + 1 < buf.line_count() {
+                y += 1;
+                chars = buf.get_line(y).as_bytes();
+                x = 0;
+            } else if !forward && y > 0 {
+                y -= 1;
+                chars = buf.get_line(y).as_bytes();
+                x = chars.len() as isize - 1;
+            } else {
+                return None;
+            }
+        }
     }
 
-    pub fn cmd_right(&mut self, buf: &CodeBuffer, offset: &mut Vec2<usize>) {
-        let _ = self.move_by(buf, offset, 1, 0);
+    pub fn cmd_match_paren(&mut self, buf: &CodeBuffer) {
+        if let Some((x, y)) = self.matching_bracket(buf) {
+            self.move_y_to(buf, y);
+            self.move_x_to(buf, x);
+        }
     }
 
-    pub fn cmd_up(&mut self, buf: &CodeBuffer, offset: &mut Vec2<usize>) {
-        let _ = self.move_by(buf, offset, 0, -1);
+    pub fn cmd_left(&mut self, buf: &CodeBuffer) {
+        self.move_by(buf, -1, 0);
     }
 
-    pub fn cmd_down(&mut self, buf: &CodeBuffer, offset: &mut Vec2<usize>) {
-        let _ = self.move_by(buf, offset, 0, 1);
+    pub fn cmd_right(&mut self, buf: &CodeBuffer) {
+        self.move_by(buf, 1, 0);
     }
 
-    pub fn cmd_top(&mut self, buf: &CodeBuffer, offset: &mut Vec2<usize>) {
+    pub fn move_y_keep_col(&mut self, buf: &CodeBuffer, y: isize, tabstop: usize) {
+        let line = buf.get_line(self.y);
+        let col = if self.x <= line.len() {
+            display_width(&line[..self.x], tabstop)
+        } else {
+            display_width(line, tabstop) + self.x - line.len()
+        };
+
+        self.move_by(buf, 0, y);
+
+        let line = buf.get_line(self.y);
+        let width = display_width(line, tabstop);
+        self.x = if col < width {
+            index_at_col(line, col, tabstop)
+        } else {
+            line.len() + col - width
+        };
+    }
+
+    pub fn cmd_up(&mut self, buf: &CodeBuffer, tabstop: usize) {
+        self.move_y_keep_col(buf, -1, tabstop);
+    }
+
+    pub fn cmd_down(&mut self, buf: &CodeBuffer, tabstop: usize) {
+        self.move_y_keep_col(buf, 1, tabstop);
+    }
+
+    pub fn cmd_top(&mut self, buf: &CodeBuffer) {
         self.move_y_to(buf, 0);
-        offset.y = 0;
     }
 
-    pub fn cmd_bottom(&mut self, buf: &CodeBuffer, offset: &mut Vec2<usize>) {
+    pub fn cmd_bottom(&mut self, buf: &CodeBuffer) {
         self.move_y_to(buf, buf.line_count() - 1);
-        offset.y = buf.line_count() - 1;
     }
 
     pub fn cmd_line_start(&mut self, buf: &CodeBuffer) {
@@ -129,27 +187,28 @@ impl EditorCursor {
     pub fn register_cmds(cmds: &mut EditorCommand) {
         cmds.register(
             "left",
-            Box::new(|editor| editor.cursor.cmd_left(&editor.buf, &mut editor.offset)),
+            Box::new(|editor| editor.cursor.cmd_left(&editor.buf)),
         );
         cmds.register(
             "right",
-            Box::new(|editor| editor.cursor.cmd_right(&editor.buf, &mut editor.offset)),
+            Box::new(|editor| editor.cursor.cmd_right(&editor.buf)),
         );
         cmds.register(
             "up",
-            Box::new(|editor| editor.cursor.cmd_up(&editor.buf, &mut editor.offset)),
+            Box::new(|editor| editor.cursor.cmd_up(&editor.buf, editor.options.tabstop)),
         );
         cmds.register(
             "down",
-            Box::new(|editor| editor.cursor.cmd_down(&editor.buf, &mut editor.offset)),
+            Box::new(|editor| editor.cursor.cmd_down(&editor.buf, editor.options.tabstop)),
         );
+        cmds.register("top", Box::new(|editor| editor.cursor.cmd_top(&editor.buf)));
         cmds.register(
-            "top",
-            Box::new(|editor| editor.cursor.cmd_top(&editor.buf, &mut editor.offset)),
+            "bottom",
+            Box::new(|editor| editor.cursor.cmd_bottom(&editor.buf)),
         );
         cmds.register(
-            "bottom",
-            Box::new(|editor| editor.cursor.cmd_bottom(&editor.buf, &mut editor.offset)),
+            "match_paren",
+            Box::new(|editor| editor.cursor.cmd_match_paren(&editor.buf)),
         );
         cmds.register(
             "line_start",
@@ -0,0 +1,459 @@
+use utils::{text::indent_len, types::Vec2};
+
+use crate::{
+    cmd::EditorCommand,
+    states::{
+        case::switch_case,
+        mode::EditorMode,
+        operator::TextRange,
+        register::{Register, Registers},
+        EditorState,
+    },
+};
+
+impl EditorState {
+    fn clamp_range(&self, range: TextRange) -> (Vec2<usize>, Vec2<usize>) {
+        let last = self.buf.line_count() - 1;
+        let start = Vec2::new(
+            range.start.x.min(self.buf.line_length(range.start.y)),
+            range.start.y,
+        );
+        let mut end = range.end;
+
+        if end.x > self.buf.line_length(end.y) {
+            end = if end.y < last {
+                Vec2::new(0, end.y + 1)
+            } else {
+                Vec2::new(self.buf.line_length(end.y), end.y)
+            };
+        }
+
+        (start, end)
+    }
+
+    pub fn range_text(&self, range: TextRange) -> Register {
+        if range.linewise {
+            let end = range.end.y.min(self.buf.line_count() - 1);
+            let lines: Vec<&str> = (range.start.y..=end)
+                .map(|y| self.buf.get_line(y))
+                .collect();
+            return Register {
+                text: lines.join("\n"),
+                linewise: true,
+            };
+        }
+
+        let (start, end) = self.clamp_range(range);
+        let text = if start.y == end.y {
+            self.buf.get_line(start.y)[start.x..end.x].to_string()
+        } else {
+            let mut lines = vec![&self.buf.get_line(start.y)[start.x..]];
+            lines.extend((start.y + 1..end.y).map(|y| self.buf.get_line(y)));
+            lines.push(&self.buf.get_line(end.y)[..end.x]);
+            lines.join("\n")
+        };
+
+        Register {
+            text,
+            linewise: false,
+        }
+    }
+
+    pub fn delete_range(&mut self, range: TextRange) -> Register {
+        let deleted = self.range_text(range);
+
+        if range.linewise {
+            let end = range.end.y.min(self.buf.line_count() - 1);
+            for _ in range.start.y..=end {
+                self.buf.remove_line(range.start.y);
+            }
+            return deleted;
+        }
+
+        let (start, end) = self.clamp_range(range);
+        let line =
+            self.buf.get_line(start.y)[..start.x].to_string() + &self.buf.get_line(end.y)[end.x..];
+        self.buf.set_line(start.y, line);
+        for _ in start.y..end.y {
+            self.buf.remove_line(start.y + 1);
+        }
+
+        deleted
+    }
+
+    pub fn cut_range(&mut self, range: TextRange) {
+        let deleted = self.delete_range(range);
+        let name = self.get_register();
+        self.registers.delete(name, deleted);
+    }
+
+    pub fn yank_range(&mut self, range: TextRange) {
+        let yanked = self.range_text(range);
+        let name = self.get_register();
+        self.registers.yank(name, yanked);
+    }
+
+    fn clamp_cursor_to_line(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let line = self.buf.get_line(cursor_y);
+
+        if cursor_x >= line.len() {
+            let x = line.char_indices().last().map_or(0, |(x, _)| x);
+            self.cursor.move_x_to(&self.buf, x);
+        }
+    }
+
+    fn cmd_delete_char(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let line = self.buf.get_line(cursor_y);
+        let end = line[cursor_x..]
+            .char_indices()
+            .nth(self.get_count())
+            .map_or(line.len(), |(i, _)| cursor_x + i);
+        if cursor_x == end {
+            return;
+        }
+
+        self.cut_range(TextRange::chars(
+            Vec2::new(cursor_x, cursor_y),
+            Vec2::new(end, cursor_y),
+        ));
+        self.clamp_cursor_to_line();
+    }
+
+    fn cmd_delete_char_before(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let line = self.buf.get_line(cursor_y);
+        let start = line[..cursor_x]
+            .char_indices()
+            .rev()
+            .nth(self.get_count() - 1)
+            .map_or(0, |(i, _)| i);
+        if start == cursor_x {
+            return;
+        }
+
+        self.cut_range(TextRange::chars(
+            Vec2::new(start, cursor_y),
+            Vec2::new(cursor_x, cursor_y),
+        ));
+        self.cursor.move_x_to(&self.buf, start);
+    }
+
+    fn delete_to_end(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let end_y = (cursor_y + self.get_count() - 1).min(self.buf.line_count() - 1);
+        let end_x = self.buf.line_length(end_y);
+
+        self.cut_range(TextRange::chars(
+            Vec2::new(cursor_x, cursor_y),
+            Vec2::new(end_x, end_y),
+        ));
+        self.cursor.move_x_to(&self.buf, cursor_x);
+    }
+
+    fn cmd_delete_to_end(&mut self) {
+        self.delete_to_end();
+        self.clamp_cursor_to_line();
+    }
+
+    fn cmd_change_to_end(&mut self) {
+        self.delete_to_end();
+        self.set_mode(EditorMode::Insert);
+    }
+
+    fn cmd_substitute_char(&mut self) {
+        let (cursor_x, _) = self.cursor.get_display(&self.buf);
+        self.cmd_delete_char();
+        self.cursor.move_x_to(&self.buf, cursor_x);
+        self.set_mode(EditorMode::Insert);
+    }
+
+    fn cmd_change_line(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let end_y = (cursor_y + self.get_count() - 1).min(self.buf.line_count() - 1);
+        let first = self.buf.get_line(cursor_y);
+        let indent = if self.options.autoindent {
+            first[..indent_len(first)].to_string()
+        } else {
+            String::new()
+        };
+
+        let deleted = self.range_text(TextRange::lines(cursor_y, end_y));
+        let name = self.get_register();
+        self.registers.delete(name, deleted);
+
+        if end_y > cursor_y {
+            self.delete_range(TextRange::lines(cursor_y + 1, end_y));
+        }
+        self.buf.set_line(cursor_y, indent.clone());
+
+        self.cursor.move_x_to(&self.buf, indent.len());
+        self.set_mode(EditorMode::Insert);
+    }
+
+    fn cmd_join_lines(&mut self, spaces: bool) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let joins = self.get_count().max(2) - 1;
+        self.join_lines(cursor_y, joins, spaces);
+    }
+
+    pub fn join_lines(&mut self, cursor_y: usize, joins: usize, spaces: bool) {
+        let mut x = self.buf.line_length(cursor_y);
+
+        for _ in 0..joins {
+            if cursor_y + 1 >= self.buf.line_count() {
+                break;
+            }
+
+            let line = self.buf.get_line(cursor_y);
+            let next = self.buf.get_line(cursor_y + 1);
+            x = line.len();
+
+            let joined = if spaces {
+                let next = next.trim_start();
+                let sep = if line.is_empty()
+                    || next.is_empty()
+                    || line.ends_with([' ', '\t'])
+                    || next.starts_with(')')
+                {
+                    ""
+                } else {
+                    " "
+                };
+                line.to_string() + sep + next
+            } else {
+                line.to_string() + next
+            };
+            self.buf.set_line(cursor_y, joined);
+            self.buf.remove_line(cursor_y + 1);
+        }
+
+        self.cursor.move_y_to(&self.buf, cursor_y);
+        self.cursor.move_x_to(&self.buf, x);
+        self.clamp_cursor_to_line();
+    }
+
+    fn cmd_toggle_case(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let line = self.buf.get_line(cursor_y);
+        let count = self.get_count();
+
+        let toggled = switch_case(&line[cursor_x..].chars().take(count).collect::<String>());
+        let rest: String = line[cursor_x..].chars().skip(count).collect();
+        let new_line = line[..cursor_x].to_string() + &toggled + &rest;
+        self.buf.set_line(cursor_y, new_line);
+
+        self.cursor.move_x_to(&self.buf, cursor_x + toggled.len());
+        self.clamp_cursor_to_line();
+    }
+
+    fn cmd_delete_line(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let end_y = cursor_y + self.get_count() - 1;
+
+        self.cut_range(TextRange::lines(cursor_y, end_y));
+        self.cursor.move_y_to(&self.buf, cursor_y);
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let x = indent_len(self.buf.get_line(cursor_y));
+        self.cursor.move_x_to(&self.buf, x);
+    }
+
+    fn cmd_copy_line(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let end_y = cursor_y + self.get_count() - 1;
+        self.yank_range(TextRange::lines(cursor_y, end_y));
+    }
+
+    fn cmd_paste(&mut self, after: bool) {
+        let name = self.get_register().unwrap_or('"');
+        let Some(register) = self.registers.get(name).cloned() else {
+            self.set_message(format!("Nothing in register {}", name));
+            return;
+        };
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let count = self.get_count();
+
+        if register.linewise {
+            let y = if after { cursor_y + 1 } else { cursor_y };
+            let lines: Vec<&str> = register.text.split('\n').collect();
+            for (i, line) in lines.repeat(count).into_iter().enumerate() {
+                self.buf.insert_line(y + i, line.to_string());
+            }
+
+            self.cursor.move_y_to(&self.buf, y);
+            let x = indent_len(self.buf.get_line(y));
+            self.cursor.move_x_to(&self.buf, x);
+            return;
+        }
+
+        let line = self.buf.get_line(cursor_y);
+        let x = match line[cursor_x..].chars().next() {
+            Some(c) if after => cursor_x + c.len_utf8(),
+            _ => cursor_x,
+        };
+        let text = register.text.repeat(count);
+        let tail = line[x..].to_string();
+        let head = line[..x].to_string();
+
+        let parts: Vec<&str> = text.split('\n').collect();
+        if let [part] = parts[..] {
+            self.buf.set_line(cursor_y, head + part + &tail);
+            self.cursor
+                .move_x_to(&self.buf, (x + part.len()).saturating_sub(1));
+            return;
+        }
+
+        let last = parts.len() - 1;
+        self.buf.set_line(cursor_y, head + parts[0]);
+        for (i, part) in parts[1..last].iter().enumerate() {
+            self.buf.insert_line(cursor_y + 1 + i, part.to_string());
+        }
+        self.buf
+            .insert_line(cursor_y + last, parts[last].to_string() + &tail);
+        self.cursor.move_x_to(&self.buf, x);
+    }
+
+    fn cmd_visual_copy(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let range =
+            TextRange::from_visual(&self.buf, self.visual_start, Vec2::new(cursor_x, cursor_y));
+
+        self.yank_range(range);
+        self.finish_operator(range);
+    }
+
+    fn cmd_visual_delete(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let range =
+            TextRange::from_visual(&self.buf, self.visual_start, Vec2::new(cursor_x, cursor_y));
+
+        self.cut_range(range);
+        self.finish_operator(range);
+        self.clamp_cursor_to_line();
+    }
+
+    pub fn select_register(&mut self, name: char) {
+        if Registers::is_valid(name) {
+            self.register = Some(name);
+        } else {
+            self.set_message(format!("Invalid register name: {}", name));
+        }
+    }
+
+    pub fn get_register(&self) -> Option<char> {
+        self.register
+    }
+
+    pub fn clear_register(&mut self) {
+        self.register = None;
+    }
+
+    pub fn register_edit_cmds(cmds: &mut EditorCommand) {
+        cmds.register("delete_char", Box::new(|editor| editor.cmd_delete_char()));
+        cmds.register(
+            "delete_char_before",
+            Box::new(|editor| editor.cmd_delete_char_before()),
+        );
+        cmds.register(
+            "delete_to_end",
+            Box::new(|editor| editor.cmd_delete_to_end()),
+        );
+        cmds.register(
+            "change_to_end",
+            Box::new(|editor| editor.cmd_change_to_end()),
+        );
+        cmds.register(
+            "substitute_char",
+            Box::new(|editor| editor.cmd_substitute_char()),
+        );
+        cmds.register("change_line", Box::new(|editor| editor.cmd_change_line()));
+        cmds.register("join_lines", Box::new(|editor| editor.cmd_join_lines(true)));
+        cmds.register(
+            "join_lines_raw",
+            Box::new(|editor| editor.cmd_join_lines(false)),
+        );
+        cmds.register("toggle_case", Box::new(|editor| editor.cmd_toggle_case()));
+        cmds.register("delete_line", Box::new(|editor| editor.cmd_delete_line()));
+        cmds.register("copy_line", Box::new(|editor| editor.cmd_copy_line()));
+        cmds.register("paste_after", Box::new(|editor| editor.cmd_paste(true)));
+        cmds.register("paste_before", Box::new(|editor| editor.cmd_paste(false)));
+        cmds.register("visual_yank", Box::new(|editor| editor.cmd_visual_copy()));
+        cmds.register(
+            "visual_delete",
+            Box::new(|editor| editor.cmd_visual_delete()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_chars() {
+        let mut state = EditorState::new("abcdef".to_string(), None);
+        state.cursor.move_x_to(&state.buf, 4);
+
+        state.push_count(2);
+        state.cmd_delete_char();
+        assert_eq!(state.buf.to_string(), "abcd");
+        assert_eq!(state.cursor.get_display(&state.buf), (3, 0));
+        assert_eq!(state.registers.get('-').unwrap().text, "ef");
+
+        state.cmd_delete_char_before();
+        assert_eq!(state.buf.to_string(), "ad");
+        assert_eq!(state.cursor.get_display(&state.buf), (1, 0));
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let mut state = EditorState::new("a\n    b\n)\nc".to_string(), None);
+
+        state.push_count(3);
+        state.cmd_join_lines(true);
+        assert_eq!(state.buf.to_string(), "a b)\nc");
+        state.clear_count();
+        state.cmd_join_lines(false);
+        assert_eq!(state.buf.to_string(), "a b)c");
+    }
+
+    #[test]
+    fn test_delete_and_paste_lines() {
+        let mut state = EditorState::new("a\nb\nc".to_string(), None);
+
+        state.select_register('x');
+        state.cmd_delete_line();
+        state.clear_register();
+        assert_eq!(state.buf.to_string(), "b\nc");
+
+        state.select_register('x');
+        state.push_count(2);
+        state.cmd_paste(true);
+        assert_eq!(state.buf.to_string(), "b\na\na\nc");
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 1));
+    }
+
+    #[test]
+    fn test_paste_chars() {
+        let mut state = EditorState::new("abc".to_string(), None);
+
+        state.cmd_delete_to_end();
+        assert_eq!(state.buf.to_string(), "");
+        state.cmd_paste(false);
+        state.cmd_paste(true);
+        assert_eq!(state.buf.to_string(), "abcabc");
+        assert_eq!(state.cursor.get_display(&state.buf), (5, 0));
+    }
+
+    #[test]
+    fn test_change_line() {
+        let mut state = EditorState::new("  a\n  b\nc".to_string(), None);
+
+        state.push_count(2);
+        state.cmd_change_line();
+        assert_eq!(state.buf.to_string(), "  \nc");
+        assert_eq!(state.cursor.get_display(&state.buf), (2, 0));
+        assert_eq!(state.registers.get('1').unwrap().text, "  a\n  b");
+    }
+}
@@ -0,0 +1,375 @@
+use std::{cmp::Ordering, sync::LazyLock};
+
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+use utils::text::indent_len;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{operator::TextRange, EditorState},
+};
+
+// Run by Editor, since commands can't feed keys
+#[derive(Clone)]
+pub struct NormalKeys {
+    pub lines: (usize, usize),
+    pub keys: String,
+}
+
+static SORT_NUMBER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"-?\d+").unwrap());
+
+#[derive(Default)]
+struct SortOptions {
+    reverse: bool,
+    numeric: bool,
+    ignore_case: bool,
+    unique: bool,
+    pattern: Option<Regex>,
+    use_match: bool,
+    column: Option<usize>,
+}
+
+impl SortOptions {
+    fn parse(args: &str) -> Result<Self> {
+        let mut options = Self::default();
+        let mut pattern = None;
+        let mut chars = args.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '!' => options.reverse = true,
+                'n' => options.numeric = true,
+                'i' => options.ignore_case = true,
+                'u' => options.unique = true,
+                'r' => options.use_match = true,
+                'k' => {
+                    let mut column = String::new();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                        column.push(c);
+                    }
+                    match column.parse() {
+                        Ok(column) if column > 0 => options.column = Some(column),
+                        _ => return Err(anyhow!("Column number required after k")),
+                    }
+                }
+                '/' => {
+                    let rest = &args[i + 1..];
+                    let end = rest.find('/').unwrap_or(rest.len());
+                    pattern = Some(rest[..end].to_string());
+                    for _ in 0..rest[..end].chars().count() + 1 {
+                        chars.next();
+                    }
+                }
+                ' ' => {}
+                _ => return Err(anyhow!("Invalid argument: {}", &args[i..])),
+            }
+        }
+
+        if let Some(pattern) = pattern {
+            options.pattern = Some(
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(options.ignore_case)
+                    .build()?,
+            );
+        }
+
+        Ok(options)
+    }
+
+    fn key<'a>(&self, line: &'a str) -> &'a str {
+        let mut key = line;
+
+        if let Some(column) = self.column {
+            let start = key
+                .split_whitespace()
+                .nth(column - 1)
+                .map(|field| field.as_ptr() as usize - key.as_ptr() as usize);
+            key = start.map_or("", |start| &key[start..]);
+        }
+
+        if let Some(re) = &self.pattern {
+            key = match re.find(key) {
+                Some(m) if self.use_match => m.as_str(),
+                Some(m) => &key[m.end()..],
+                None if self.use_match => "",
+                None => key,
+            };
+        }
+
+        key
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        let (a, b) = (self.key(a), self.key(b));
+
+        if self.numeric {
+            let number = |s: &str| {
+                SORT_NUMBER_RE
+                    .find(s)
+                    .and_then(|m| m.as_str().parse::<i128>().ok())
+            };
+            number(a).cmp(&number(b))
+        } else if self.ignore_case {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        } else {
+            a.cmp(b)
+        }
+    }
+}
+
+impl EditorState {
+    fn ex_lines(&self) -> (usize, usize) {
+        match self.cmd_range {
+            Some(range) => (range.start.y, range.end.y),
+            None => {
+                let (_, cursor_y) = self.cursor.get_display(&self.buf);
+                (cursor_y, cursor_y)
+            }
+        }
+    }
+
+    pub fn replace_lines(&mut self, start: usize, end: usize, lines: Vec<String>) {
+        let all = start == 0 && end + 1 >= self.buf.line_count();
+        for _ in start..=end {
+            self.buf.remove_line(start);
+        }
+        for (i, line) in lines.into_iter().enumerate() {
+            if all && i == 0 {
+                self.buf.set_line(0, line);
+            } else {
+                self.buf.insert_line(start + i, line);
+            }
+        }
+    }
+
+    fn sort_lines(&mut self, args: &str) -> Result<()> {
+        let options = SortOptions::parse(args)?;
+        let (start, end) = match self.cmd_range {
+            Some(range) => (range.start.y, range.end.y),
+            None => (0, self.buf.line_count() - 1),
+        };
+
+        let mut lines: Vec<String> = (start..=end)
+            .map(|y| self.buf.get_line(y).to_string())
+            .collect();
+        lines.sort_by(|a, b| {
+            let order = options.compare(a, b);
+            if options.reverse {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        if options.unique {
+            lines.dedup_by(|a, b| options.compare(a, b) == Ordering::Equal);
+        }
+
+        self.replace_lines(start, end, lines);
+        self.cursor.move_y_to(&self.buf, start);
+        self.cursor.move_x_to(&self.buf, 0);
+
+        Ok(())
+    }
+
+    fn parse_destination(&self, args: &str) -> Result<usize> {
+        match self.parse_address(args.trim())? {
+            (Some(line), "") if line <= self.buf.line_count() => Ok(line),
+            (None, "") => Err(anyhow!("Argument required")),
+            _ => Err(anyhow!("Invalid address: {}", args)),
+        }
+    }
+
+    fn move_lines(&mut self, args: &str) -> Result<()> {
+        let (start, end) = self.ex_lines();
+        let dest = self.parse_destination(args)?;
+        if dest > start && dest <= end {
+            return Err(anyhow!("Cannot move a range of lines into itself"));
+        }
+        if dest == start || dest == end + 1 {
+            self.cursor.move_y_to(&self.buf, end);
+            return Ok(());
+        }
+
+        let lines: Vec<String> = (start..=end).map(|_| self.buf.remove_line(start)).collect();
+        let count = lines.len();
+        let at = if dest > end { dest - count } else { dest };
+        for (i, line) in lines.into_iter().enumerate() {
+            self.buf.insert_line(at + i, line);
+        }
+
+        self.cursor.move_y_to(&self.buf, at + count - 1);
+        Ok(())
+    }
+
+    fn copy_lines(&mut self, args: &str) -> Result<()> {
+        let (start, end) = self.ex_lines();
+        let dest = self.parse_destination(args)?;
+
+        let lines: Vec<String> = (start..=end)
+            .map(|y| self.buf.get_line(y).to_string())
+            .collect();
+        let count = lines.len();
+        for (i, line) in lines.into_iter().enumerate() {
+            self.buf.insert_line(dest + i, line);
+        }
+
+        self.cursor.move_y_to(&self.buf, dest + count - 1);
+        Ok(())
+    }
+
+    fn register_and_count(&mut self, args: &str) -> Result<(usize, usize)> {
+        let (start, end) = self.ex_lines();
+        let mut args = args.trim();
+
+        if let Some(c) = args.chars().next().filter(|c| !c.is_ascii_digit()) {
+            self.select_register(c);
+            args = args[c.len_utf8()..].trim_start();
+        }
+        if args.is_empty() {
+            return Ok((start, end));
+        }
+
+        match args.parse::<usize>() {
+            Ok(count) if count > 0 => Ok((end, end + count - 1)),
+            _ => Err(anyhow!("Trailing characters: {}", args)),
+        }
+    }
+
+    fn ex_delete(&mut self, args: &str) -> Result<()> {
+        let (start, end) = self.register_and_count(args)?;
+        self.cut_range(TextRange::lines(start, end));
+        self.clear_register();
+
+        self.cursor.move_y_to(&self.buf, start);
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let x = indent_len(self.buf.get_line(cursor_y));
+        self.cursor.move_x_to(&self.buf, x);
+        Ok(())
+    }
+
+    fn ex_yank(&mut self, args: &str) -> Result<()> {
+        let (start, end) = self.register_and_count(args)?;
+        self.yank_range(TextRange::lines(start, end));
+        self.clear_register();
+        Ok(())
+    }
+
+    fn ex_join(&mut self, args: &str) -> Result<()> {
+        let (spaces, args) = match args.strip_prefix('!') {
+            Some(args) => (false, args.trim()),
+            None => (true, args),
+        };
+        let (start, end) = self.ex_lines();
+        let joins = if args.is_empty() {
+            (end - start).max(1)
+        } else {
+            match args.parse::<usize>() {
+                Ok(count) if count > 0 => (end - start) + count - 1,
+                _ => return Err(anyhow!("Trailing characters: {}", args)),
+            }
+        };
+
+        self.join_lines(start, joins, spaces);
+        Ok(())
+    }
+
+    pub fn ex_normal(&mut self, keys: &str) {
+        let lines = self.ex_lines();
+        self.normal_keys = Some(NormalKeys {
+            lines,
+            keys: keys.to_string(),
+        });
+    }
+
+    pub fn take_normal_keys(&mut self) -> Option<NormalKeys> {
+        self.normal_keys.take()
+    }
+
+    pub fn register_ex_cmds(cmds: &mut EditorCommand) {
+        type ExCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let ex_cmds: &[(&[&str], ExCommand)] = &[
+            (&["sort", "sor"], EditorState::sort_lines),
+            (&["move", "m", "mo"], EditorState::move_lines),
+            (&["copy", "co", "t"], EditorState::copy_lines),
+            (&["delete", "d", "de", "del"], EditorState::ex_delete),
+            (&["yank", "y", "ya"], EditorState::ex_yank),
+            (&["join", "j"], EditorState::ex_join),
+        ];
+
+        for (names, f) in ex_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+
+        cmds.register_with_args("norm", Box::new(|editor, keys| editor.ex_normal(keys)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(
+        state: &mut EditorState,
+        range: (usize, usize),
+        f: fn(&mut EditorState, &str) -> Result<()>,
+        args: &str,
+    ) {
+        state.cmd_range = Some(TextRange::lines(range.0, range.1));
+        f(state, args).unwrap();
+        state.cmd_range = None;
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut state = EditorState::new("b 10\nA 9\na 2\nb 10".to_string(), None);
+
+        run(&mut state, (0, 3), EditorState::sort_lines, "");
+        assert_eq!(state.buf.to_string(), "A 9\na 2\nb 10\nb 10");
+        run(&mut state, (0, 3), EditorState::sort_lines, "n u");
+        assert_eq!(state.buf.to_string(), "a 2\nA 9\nb 10");
+        run(&mut state, (0, 2), EditorState::sort_lines, "! i");
+        assert_eq!(state.buf.to_string(), "b 10\nA 9\na 2");
+        run(&mut state, (0, 2), EditorState::sort_lines, "k2");
+        assert_eq!(state.buf.to_string(), "b 10\na 2\nA 9");
+        run(&mut state, (0, 2), EditorState::sort_lines, r"/\d/ r");
+        assert_eq!(state.buf.to_string(), "b 10\na 2\nA 9");
+    }
+
+    #[test]
+    fn test_move_and_copy() {
+        let mut state = EditorState::new("a\nb\nc\nd".to_string(), None);
+
+        run(&mut state, (0, 1), EditorState::move_lines, "$");
+        assert_eq!(state.buf.to_string(), "c\nd\na\nb");
+        run(&mut state, (2, 3), EditorState::move_lines, "0");
+        assert_eq!(state.buf.to_string(), "a\nb\nc\nd");
+        run(&mut state, (3, 3), EditorState::copy_lines, "1");
+        assert_eq!(state.buf.to_string(), "a\nd\nb\nc\nd");
+        assert!(state.move_lines("2").is_ok());
+        state.cmd_range = Some(TextRange::lines(0, 2));
+        assert!(state.move_lines("2").is_err());
+    }
+
+    #[test]
+    fn test_delete_yank_join() {
+        let mut state = EditorState::new("a\nb\nc\nd".to_string(), None);
+
+        run(&mut state, (0, 0), EditorState::ex_delete, "x 2");
+        assert_eq!(state.buf.to_string(), "c\nd");
+        assert_eq!(state.registers.get('x').unwrap().text, "a\nb");
+        run(&mut state, (0, 1), EditorState::ex_yank, "");
+        assert_eq!(state.registers.get('0').unwrap().text, "c\nd");
+        run(&mut state, (0, 0), EditorState::ex_join, "");
+        assert_eq!(state.buf.to_string(), "c d");
+    }
+}
@@ -0,0 +1,725 @@
+use std::{
+    collections::HashSet,
+    env,
+    fs::{copy, create_dir_all, read_dir, remove_dir_all, remove_file, rename, File},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ignore::WalkBuilder;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{buffers::display_name, file::expand_path, EditorState},
+};
+
+const EXPLORER_WIDTH: usize = 30;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    path: PathBuf,
+    depth: usize,
+    is_dir: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExplorerAction {
+    Create,
+    Rename,
+    Copy,
+    Move,
+    Delete,
+}
+
+#[derive(Clone)]
+struct ExplorerPrompt {
+    action: ExplorerAction,
+    target: PathBuf,
+    input: String,
+}
+
+#[derive(Clone)]
+pub struct Explorer {
+    root: PathBuf,
+    expanded: HashSet<PathBuf>,
+    entries: Vec<Entry>,
+    selected: usize,
+    offset: usize,
+    height: usize,
+    visible: bool,
+    focused: bool,
+    show_ignored: bool,
+    prompt: Option<ExplorerPrompt>,
+    pending_window_key: bool,
+    trash: PathBuf,
+}
+
+impl Explorer {
+    pub fn new() -> Self {
+        Self {
+            root: PathBuf::new(),
+            expanded: HashSet::new(),
+            entries: Vec::new(),
+            selected: 0,
+            offset: 0,
+            height: 0,
+            visible: false,
+            focused: false,
+            show_ignored: false,
+            prompt: None,
+            pending_window_key: false,
+            trash: trash_dir(),
+        }
+    }
+
+    fn selected_entry(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    fn refresh(&mut self) {
+        let selected = self.selected_entry().map(|entry| entry.path.clone());
+        self.entries.clear();
+        self.push_entries(&self.root.clone(), 0);
+        match selected {
+            Some(path) => self.select_path(&path),
+            None => self.selected = 0,
+        }
+    }
+
+    fn push_entries(&mut self, dir: &Path, depth: usize) {
+        for (path, is_dir) in list_dir(dir, self.show_ignored) {
+            let expanded = is_dir && self.expanded.contains(&path);
+            self.entries.push(Entry {
+                path: path.clone(),
+                depth,
+                is_dir,
+            });
+            if expanded {
+                self.push_entries(&path, depth + 1);
+            }
+        }
+    }
+
+    fn select_path(&mut self, path: &Path) {
+        match self.entries.iter().position(|entry| entry.path == path) {
+            Some(index) => self.selected = index,
+            None => self.selected = self.selected.min(self.entries.len().saturating_sub(1)),
+        }
+        self.scroll_to_selected();
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+        self.scroll_to_selected();
+    }
+
+    fn scroll_to_selected(&mut self) {
+        let height = self.height.max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+    }
+
+    fn toggle_expanded(&mut self, path: &Path) {
+        if !self.expanded.remove(path) {
+            self.expanded.insert(path.to_path_buf());
+        }
+        self.refresh();
+    }
+
+    fn collapse(&mut self) {
+        let Some(entry) = self.selected_entry().cloned() else {
+            return;
+        };
+        if entry.is_dir && self.expanded.contains(&entry.path) {
+            self.toggle_expanded(&entry.path);
+        } else if let Some(parent) = entry.path.parent().filter(|_| entry.depth > 0) {
+            self.select_path(parent);
+        }
+    }
+
+    fn target_dir(&self) -> PathBuf {
+        match self.selected_entry() {
+            Some(entry) if entry.is_dir => entry.path.clone(),
+            Some(entry) => entry.path.parent().unwrap_or(&self.root).to_path_buf(),
+            None => self.root.clone(),
+        }
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    fn start_prompt(&mut self, action: ExplorerAction) {
+        let target = match action {
+            ExplorerAction::Create => self.target_dir(),
+            _ => match self.selected_entry() {
+                Some(entry) => entry.path.clone(),
+                None => return,
+            },
+        };
+        let input = match action {
+            ExplorerAction::Rename => target
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            ExplorerAction::Copy | ExplorerAction::Move => self.relative(&target),
+            ExplorerAction::Create | ExplorerAction::Delete => String::new(),
+        };
+        self.prompt = Some(ExplorerPrompt {
+            action,
+            target,
+            input,
+        });
+    }
+
+    fn prompt_line(&self) -> Option<String> {
+        let prompt = self.prompt.as_ref()?;
+        let name = self.relative(&prompt.target);
+        let line = match prompt.action {
+            ExplorerAction::Create => format!("Create in {}/: {}", name, prompt.input),
+            ExplorerAction::Rename => format!("Rename {} to: {}", name, prompt.input),
+            ExplorerAction::Copy => format!("Copy {} to: {}", name, prompt.input),
+            ExplorerAction::Move => format!("Move {} to: {}", name, prompt.input),
+            ExplorerAction::Delete => format!("Delete {}? (y/n)", name),
+        };
+        Some(line)
+    }
+
+    fn destination(&self, target: &Path, input: &str) -> Result<PathBuf> {
+        if input.trim().is_empty() {
+            return Err(anyhow!("Argument required"));
+        }
+        let dest = self.root.join(input.trim());
+        if dest.is_dir() {
+            let name = target.file_name().ok_or_else(|| anyhow!("Invalid path"))?;
+            return Ok(dest.join(name));
+        }
+        Ok(dest)
+    }
+
+    fn run_action(&mut self, prompt: &ExplorerPrompt) -> Result<PathBuf> {
+        let target = &prompt.target;
+        let input = prompt.input.trim();
+        match prompt.action {
+            ExplorerAction::Create => {
+                if input.is_empty() {
+                    return Err(anyhow!("Argument required"));
+                }
+                let path = target.join(input);
+                if path.exists() {
+                    return Err(anyhow!("File exists: {}", self.relative(&path)));
+                }
+                // A trailing / creates a directory
+                if input.ends_with('/') {
+                    create_dir_all(&path)?;
+                } else {
+                    if let Some(parent) = path.parent() {
+                        create_dir_all(parent)?;
+                    }
+                    File::create(&path)?;
+                }
+                self.expanded.insert(target.clone());
+                Ok(path)
+            }
+            ExplorerAction::Rename => {
+                if input.is_empty() || input.contains('/') {
+                    return Err(anyhow!("Invalid name: {}", input));
+                }
+                let path = target.with_file_name(input);
+                move_path(target, &path)?;
+                Ok(path)
+            }
+            ExplorerAction::Copy => {
+                let path = self.destination(target, input)?;
+                copy_path(target, &path)?;
+                Ok(path)
+            }
+            ExplorerAction::Move => {
+                let path = self.destination(target, input)?;
+                move_path(target, &path)?;
+                Ok(path)
+            }
+            ExplorerAction::Delete => {
+                let trashed = move_to_trash(target, &self.trash)?;
+                Ok(trashed)
+            }
+        }
+    }
+}
+
+fn trash_dir() -> PathBuf {
+    let data_home = match env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").unwrap_or_default()).join(".local/share"),
+    };
+    data_home.join("ce").join("trash")
+}
+
+fn list_dir(dir: &Path, show_ignored: bool) -> Vec<(PathBuf, bool)> {
+    let walker = WalkBuilder::new(dir)
+        .max_depth(Some(1))
+        .hidden(false)
+        .require_git(false)
+        .ignore(!show_ignored)
+        .git_ignore(!show_ignored)
+        .git_global(!show_ignored)
+        .git_exclude(!show_ignored)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+
+    let mut entries: Vec<(PathBuf, bool)> = walker
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.depth() == 1)
+        .map(|entry| {
+            let is_dir = entry.path().is_dir();
+            (entry.into_path(), is_dir)
+        })
+        .collect();
+    entries.sort_by(|(a, a_dir), (b, b_dir)| b_dir.cmp(a_dir).then_with(|| a.cmp(b)));
+    entries
+}
+
+fn is_inside(from: &Path, to: &Path) -> bool {
+    let (Ok(from), Some(to)) = (
+        from.canonicalize(),
+        to.ancestors().find_map(|path| path.canonicalize().ok()),
+    ) else {
+        return false;
+    };
+    from.is_dir() && to.starts_with(from)
+}
+
+fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        return Err(anyhow!("File exists: {}", to.display()));
+    }
+    if is_inside(from, to) {
+        return Err(anyhow!("Cannot copy into itself: {}", to.display()));
+    }
+    if from.is_dir() {
+        create_dir_all(to)?;
+        for entry in read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        copy(from, to)?;
+    }
+    Ok(())
+}
+
+// rename fails across file systems
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        return Err(anyhow!("File exists: {}", to.display()));
+    }
+    if is_inside(from, to) {
+        return Err(anyhow!("Cannot move into itself: {}", to.display()));
+    }
+    if let Some(parent) = to.parent() {
+        create_dir_all(parent)?;
+    }
+    match rename(from, to) {
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+            copy_path(from, to)?;
+            if from.is_dir() {
+                remove_dir_all(from)?;
+            } else {
+                remove_file(from)?;
+            }
+        }
+        result => result?,
+    }
+    Ok(())
+}
+
+fn move_to_trash(path: &Path, trash: &Path) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid path"))?
+        .to_string_lossy();
+    let stamp = Utc::now().format("%Y%m%d%H%M%S%3f");
+    let dest = trash.join(format!("{}.{}", name, stamp));
+    move_path(path, &dest)?;
+    Ok(dest)
+}
+
+impl EditorState {
+    pub fn is_explorer_visible(&self) -> bool {
+        self.explorer.visible
+    }
+
+    pub fn is_explorer_focused(&self) -> bool {
+        self.explorer.visible && self.explorer.focused
+    }
+
+    pub fn explorer_width(&self) -> usize {
+        if self.explorer.visible {
+            EXPLORER_WIDTH.min(self.tabs_screen_size().x / 2) + 1
+        } else {
+            0
+        }
+    }
+
+    pub fn set_explorer_height(&mut self, height: usize) {
+        self.explorer.height = height.saturating_sub(1);
+        self.explorer.scroll_to_selected();
+    }
+
+    pub fn open_explorer(&mut self, dir: Option<PathBuf>) -> Result<()> {
+        let root = match dir {
+            Some(dir) => dir,
+            None => env::current_dir()?,
+        };
+        if !root.is_dir() {
+            return Err(anyhow!("Not a directory: {}", root.display()));
+        }
+        if root != self.explorer.root {
+            self.explorer.root = root;
+            self.explorer.expanded.clear();
+            self.explorer.selected = 0;
+            self.explorer.offset = 0;
+        }
+        self.explorer.refresh();
+        self.explorer.visible = true;
+        self.focus_explorer();
+        self.layout_tabs();
+        Ok(())
+    }
+
+    pub fn close_explorer(&mut self) {
+        self.explorer.visible = false;
+        self.explorer.focused = false;
+        self.explorer.prompt = None;
+        self.layout_tabs();
+    }
+
+    fn toggle_explorer(&mut self) -> Result<()> {
+        if self.explorer.visible {
+            self.close_explorer();
+            Ok(())
+        } else {
+            self.open_explorer(None)
+        }
+    }
+
+    pub fn focus_explorer(&mut self) {
+        self.cancel_change();
+        self.cursors.clear();
+        self.explorer.focused = true;
+    }
+
+    fn unfocus_explorer(&mut self) {
+        self.explorer.focused = false;
+        self.explorer.pending_window_key = false;
+    }
+
+    pub fn explorer_lines(&self) -> Vec<(String, bool)> {
+        let explorer = &self.explorer;
+        let root = format!("{}/", display_name(Some(&explorer.root)));
+        let mut lines = vec![(root, false)];
+        for (index, entry) in explorer
+            .entries
+            .iter()
+            .enumerate()
+            .skip(explorer.offset)
+            .take(explorer.height)
+        {
+            let marker = match (entry.is_dir, explorer.expanded.contains(&entry.path)) {
+                (true, true) => "▾ ",
+                (true, false) => "▸ ",
+                (false, _) => "  ",
+            };
+            let name = entry
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let suffix = if entry.is_dir { "/" } else { "" };
+            let line = format!("{}{}{}{}", "  ".repeat(entry.depth), marker, name, suffix);
+            lines.push((line, index == explorer.selected));
+        }
+        lines
+    }
+
+    pub fn explorer_cursor_row(&self) -> usize {
+        self.explorer.selected.saturating_sub(self.explorer.offset) + 1
+    }
+
+    pub fn explorer_prompt(&self) -> Option<String> {
+        self.explorer.prompt_line()
+    }
+
+    fn open_explorer_entry(&mut self, how: Option<char>) -> Result<()> {
+        let Some(entry) = self.explorer.selected_entry().cloned() else {
+            return Ok(());
+        };
+        if entry.is_dir {
+            if how.is_none() {
+                self.explorer.toggle_expanded(&entry.path);
+            }
+            return Ok(());
+        }
+
+        self.unfocus_explorer();
+        match how {
+            Some('s') => self.split_window(false, Some(entry.path))?,
+            Some('v') => self.split_window(true, Some(entry.path))?,
+            Some('t') => self.new_tab(Some(entry.path)),
+            _ => self.open_buffer(entry.path)?,
+        }
+        Ok(())
+    }
+
+    fn finish_explorer_prompt(&mut self) -> Result<()> {
+        let Some(prompt) = self.explorer.prompt.take() else {
+            return Ok(());
+        };
+        let path = self.explorer.run_action(&prompt)?;
+        match prompt.action {
+            ExplorerAction::Rename | ExplorerAction::Move => {
+                self.rename_buffer_paths(&prompt.target, &path);
+            }
+            ExplorerAction::Delete => {
+                self.set_message(format!(
+                    "Moved {} to {}",
+                    self.explorer.relative(&prompt.target),
+                    path.display()
+                ));
+            }
+            _ => {}
+        }
+
+        self.explorer.refresh();
+        if prompt.action != ExplorerAction::Delete {
+            self.explorer.select_path(&path);
+        }
+        Ok(())
+    }
+
+    fn on_explorer_prompt_key(&mut self, code: KeyCode) -> Result<()> {
+        let Some(prompt) = self.explorer.prompt.as_mut() else {
+            return Ok(());
+        };
+        if prompt.action == ExplorerAction::Delete {
+            return match code {
+                KeyCode::Char('y') => self.finish_explorer_prompt(),
+                _ => {
+                    self.explorer.prompt = None;
+                    Ok(())
+                }
+            };
+        }
+
+        match code {
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Enter => return self.finish_explorer_prompt(),
+            KeyCode::Esc => self.explorer.prompt = None,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn on_explorer_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        let result = if self.explorer.prompt.is_some() {
+            self.on_explorer_prompt_key(code)
+        } else {
+            match self.on_explorer_nav_key(code, modifiers) {
+                Some(result) => result,
+                None => return false,
+            }
+        };
+        if let Err(err) = result {
+            self.set_message(err.to_string());
+        }
+        true
+    }
+
+    fn on_explorer_nav_key(
+        &mut self,
+        code: KeyCode,
+        modifiers: KeyModifiers,
+    ) -> Option<Result<()>> {
+        if self.explorer.pending_window_key {
+            self.explorer.pending_window_key = false;
+            if let KeyCode::Char('l' | 'w') = code {
+                self.unfocus_explorer();
+            }
+            return Some(Ok(()));
+        }
+        if modifiers == KeyModifiers::CONTROL {
+            if code == KeyCode::Char('w') {
+                self.explorer.pending_window_key = true;
+            }
+            return Some(Ok(()));
+        }
+
+        let explorer = &mut self.explorer;
+        match code {
+            KeyCode::Char('j') | KeyCode::Down => explorer.move_selection(1),
+            KeyCode::Char('k') | KeyCode::Up => explorer.move_selection(-1),
+            KeyCode::Char('g') => explorer.move_selection(isize::MIN / 2),
+            KeyCode::Char('G') => explorer.move_selection(isize::MAX / 2),
+            KeyCode::Char('h') | KeyCode::Left => explorer.collapse(),
+            KeyCode::Enter | KeyCode::Char('l' | 'o') | KeyCode::Right => {
+                return Some(self.open_explorer_entry(None));
+            }
+            KeyCode::Char(c @ ('s' | 'v' | 't')) => return Some(self.open_explorer_entry(Some(c))),
+            KeyCode::Char('a') => explorer.start_prompt(ExplorerAction::Create),
+            KeyCode::Char('r') => explorer.start_prompt(ExplorerAction::Rename),
+            KeyCode::Char('c') => explorer.start_prompt(ExplorerAction::Copy),
+            KeyCode::Char('m') => explorer.start_prompt(ExplorerAction::Move),
+            KeyCode::Char('d') => explorer.start_prompt(ExplorerAction::Delete),
+            KeyCode::Char('R') => explorer.refresh(),
+            KeyCode::Char('.') => {
+                explorer.show_ignored = !explorer.show_ignored;
+                explorer.refresh();
+            }
+            KeyCode::Char('q') => self.close_explorer(),
+            KeyCode::Esc | KeyCode::Tab => self.unfocus_explorer(),
+            _ => {
+                self.unfocus_explorer();
+                return None;
+            }
+        }
+        Some(Ok(()))
+    }
+
+    pub fn register_explorer_cmds(cmds: &mut EditorCommand) {
+        type ExplorerCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let explorer_cmds: &[(&[&str], ExplorerCommand)] = &[
+            (&["Ex", "Explore"], |editor, args| {
+                let dir = Some(args).filter(|args| !args.is_empty());
+                editor.open_explorer(dir.map(expand_path).transpose()?)
+            }),
+            (&["Lex", "Lexplore"], |editor, _| editor.toggle_explorer()),
+        ];
+
+        for (names, f) in explorer_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, write};
+
+    use super::*;
+
+    fn names(state: &EditorState) -> Vec<String> {
+        state
+            .explorer
+            .entries
+            .iter()
+            .map(|entry| state.explorer.relative(&entry.path))
+            .collect()
+    }
+
+    #[test]
+    fn test_explorer() {
+        let dir = env::temp_dir().join(format!("ce-explorer-test-{}", std::process::id()));
+        create_dir_all(dir.join("src")).unwrap();
+        write(dir.join(".gitignore"), "target/\n").unwrap();
+        create_dir_all(dir.join("target")).unwrap();
+        write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        write(dir.join("b.txt"), "b\n").unwrap();
+
+        let mut state = EditorState::new(String::new(), None);
+        state.explorer.trash = dir.join("trash");
+        state.open_explorer(Some(dir.clone())).unwrap();
+        assert_eq!(names(&state), vec!["src", ".gitignore", "b.txt"]);
+        assert!(state.is_explorer_focused());
+
+        state.on_explorer_key(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(
+            names(&state),
+            vec!["src", "src/main.rs", ".gitignore", "b.txt"]
+        );
+        state.on_explorer_key(KeyCode::Char('.'), KeyModifiers::NONE);
+        assert!(names(&state).contains(&"target".to_string()));
+        state.on_explorer_key(KeyCode::Char('.'), KeyModifiers::NONE);
+
+        // Create
+        for c in "anew.txt\n".chars() {
+            let code = if c == '\n' {
+                KeyCode::Enter
+            } else {
+                KeyCode::Char(c)
+            };
+            state.on_explorer_key(code, KeyModifiers::NONE);
+        }
+        assert!(dir.join("src/new.txt").is_file());
+        assert_eq!(names(&state)[state.explorer.selected], "src/new.txt");
+
+        // Rename
+        state.on_explorer_key(KeyCode::Char('r'), KeyModifiers::NONE);
+        for _ in 0.."new.txt".len() {
+            state.on_explorer_key(KeyCode::Backspace, KeyModifiers::NONE);
+        }
+        for c in "lib.rs".chars() {
+            state.on_explorer_key(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        state.on_explorer_key(KeyCode::Enter, KeyModifiers::NONE);
+        assert!(!dir.join("src/new.txt").exists());
+        assert!(dir.join("src/lib.rs").is_file());
+
+        // Trash
+        state.on_explorer_key(KeyCode::Char('d'), KeyModifiers::NONE);
+        state.on_explorer_key(KeyCode::Char('n'), KeyModifiers::NONE);
+        assert!(dir.join("src/lib.rs").exists());
+        state.on_explorer_key(KeyCode::Char('d'), KeyModifiers::NONE);
+        state.on_explorer_key(KeyCode::Char('y'), KeyModifiers::NONE);
+        assert!(!dir.join("src/lib.rs").exists());
+        assert_eq!(read_dir(dir.join("trash")).unwrap().count(), 1);
+
+        // Copy and open
+        state.explorer.select_path(&dir.join("b.txt"));
+        state.on_explorer_key(KeyCode::Char('c'), KeyModifiers::NONE);
+        state.explorer.prompt.as_mut().unwrap().input = "src".to_string();
+        state.on_explorer_key(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(read_to_string(dir.join("src/b.txt")).unwrap(), "b\n");
+        state.on_explorer_key(KeyCode::Char('l'), KeyModifiers::NONE);
+        assert!(!state.is_explorer_focused());
+        assert_eq!(state.get_path(), Some(dir.join("src/b.txt")));
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_into_itself() {
+        let dir = env::temp_dir().join(format!("ce-explorer-self-{}", std::process::id()));
+        create_dir_all(dir.join("a")).unwrap();
+
+        assert!(copy_path(&dir.join("a"), &dir.join("a/b")).is_err());
+        assert!(move_path(&dir.join("a"), &dir.join("a/b/c")).is_err());
+        assert!(!dir.join("a/b").exists());
+        move_path(&dir.join("a"), &dir.join("ab")).unwrap();
+        assert!(dir.join("ab").is_dir());
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
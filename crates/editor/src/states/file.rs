@@ -0,0 +1,335 @@
+use std::{
+    env,
+    fs::{read_to_string, OpenOptions},
+    io::Write,
+    path::{self, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    cmd::EditorCommand,
+    states::{filetype::FileType, operator::TextRange, EditorState},
+};
+
+pub fn expand_path(s: &str) -> Result<PathBuf> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(anyhow!("Argument required"));
+    }
+
+    let mut expanded = String::new();
+    let mut rest = s;
+    if rest == "~" || rest.starts_with("~/") {
+        expanded.push_str(&env::var("HOME").map_err(|_| anyhow!("HOME is not set"))?);
+        rest = &rest[1..];
+    }
+
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, after) = match after.strip_prefix('{') {
+            Some(after) => {
+                let end = after
+                    .find('}')
+                    .ok_or_else(|| anyhow!("Missing }}: {}", s))?;
+                (&after[..end], &after[end + 1..])
+            }
+            None => {
+                let len = after
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                    .count();
+                (&after[..len], &after[len..])
+            }
+        };
+
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            expanded.push_str(&env::var(name).unwrap_or_default());
+        }
+        rest = after;
+    }
+    expanded.push_str(rest);
+
+    Ok(env::current_dir()?.join(expanded))
+}
+
+// Buffer paths are absolute so :cd doesn't change the file they point to
+pub fn absolute_path(path: &Path) -> PathBuf {
+    path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn split_bang(args: &str) -> (bool, &str) {
+    match args.strip_prefix('!') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, args),
+    }
+}
+
+impl EditorState {
+    pub fn is_modified(&self) -> bool {
+        self.buf != self.saved_buf
+    }
+
+    pub fn current_path(&self) -> Result<PathBuf> {
+        self.get_path().ok_or_else(|| anyhow!("No file name"))
+    }
+
+    pub fn insert_lines_below(&mut self, text: &str) {
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let y = match self.cmd_range {
+            Some(range) => range.end.y,
+            None => self.cursor.get_display(&self.buf).1,
+        };
+
+        for (i, line) in text.split('\n').enumerate() {
+            self.buf.insert_line(y + 1 + i, line.to_string());
+        }
+        self.cursor.move_y_to(&self.buf, y + 1);
+        self.cursor.move_x_to(&self.buf, 0);
+    }
+
+    pub fn write_to(&mut self, path: &PathBuf, append: bool) -> Result<()> {
+        let range = self
+            .cmd_range
+            .unwrap_or(TextRange::lines(0, self.buf.line_count() - 1));
+        let whole = range.start.y == 0 && range.end.y == self.buf.line_count() - 1;
+        let text = if whole {
+            self.buf.to_string()
+        } else {
+            self.range_text(range).text + "\n"
+        };
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .map_err(|err| anyhow!("Can't open file for writing: {}", err))?;
+        file.write_all(text.as_bytes())?;
+
+        if whole && !append && self.get_path().is_none_or(|current| &current == path) {
+            self.saved_buf = self.buf.clone();
+        }
+        let lines = range.end.y - range.start.y + 1;
+        let verb = if append { "appended" } else { "written" };
+        self.set_message(format!("\"{}\" {}L {}", path.display(), lines, verb));
+        Ok(())
+    }
+
+    fn check_overwrite(&self, path: &Path, force: bool) -> Result<()> {
+        if !force && path.exists() && self.get_path().as_deref() != Some(path) {
+            return Err(anyhow!("File exists (add ! to override)"));
+        }
+        Ok(())
+    }
+
+    fn ex_write(&mut self, args: &str) -> Result<()> {
+        // `:w !cmd` has no space after !, `:w! file` does
+        if let Some(cmd) = args.strip_prefix('!') {
+            if cmd.starts_with(|c: char| !c.is_whitespace()) {
+                return self.write_command(cmd);
+            }
+        }
+        let (force, args) = split_bang(args);
+
+        let (append, args) = match args.strip_prefix(">>") {
+            Some(rest) => (true, rest.trim()),
+            None => (false, args),
+        };
+        if args.is_empty() {
+            let path = self.current_path()?;
+            return self.write_to(&path, append);
+        }
+
+        let path = expand_path(args)?;
+        if !append {
+            self.check_overwrite(&path, force)?;
+        }
+        self.write_to(&path, append)?;
+        if self.get_path().is_none() {
+            self.set_path(path);
+        }
+        Ok(())
+    }
+
+    fn ex_write_quit(&mut self, args: &str) -> Result<()> {
+        self.ex_write(args)?;
+        self.quit();
+        Ok(())
+    }
+
+    fn ex_exit(&mut self, args: &str) -> Result<()> {
+        if self.is_modified() || !args.trim_start_matches('!').trim().is_empty() {
+            self.ex_write(args)?;
+        }
+        self.quit();
+        Ok(())
+    }
+
+    fn ex_update(&mut self, args: &str) -> Result<()> {
+        if self.is_modified() {
+            self.ex_write(args)?;
+        }
+        Ok(())
+    }
+
+    // :e[dit][!] [file]
+    fn ex_edit(&mut self, args: &str) -> Result<()> {
+        let (force, args) = split_bang(args);
+        if !args.is_empty() {
+            return self.open_buffer(expand_path(args)?);
+        }
+
+        let path = self.current_path()?;
+        if self.is_modified() && !force {
+            return Err(anyhow!("No write since last change (add ! to override)"));
+        }
+        self.reload_buffer(path)
+    }
+
+    fn ex_saveas(&mut self, args: &str) -> Result<()> {
+        let (force, args) = split_bang(args);
+        let path = expand_path(args)?;
+        self.check_overwrite(&path, force)?;
+        self.cmd_range = None;
+        self.write_to(&path, false)?;
+        self.saved_buf = self.buf.clone();
+        self.options.set_filetype(FileType::detect(&path));
+        self.set_path(path);
+        Ok(())
+    }
+
+    fn ex_read(&mut self, args: &str) -> Result<()> {
+        if let Some(cmd) = args.strip_prefix('!') {
+            return self.read_command(cmd.trim());
+        }
+
+        let path = if args.is_empty() {
+            self.current_path()?
+        } else {
+            expand_path(args)?
+        };
+        let text = read_to_string(&path)
+            .map_err(|err| anyhow!("Can't open file {}: {}", path.display(), err))?;
+        self.insert_lines_below(&text);
+        Ok(())
+    }
+
+    fn ex_cd(&mut self, args: &str) -> Result<()> {
+        let dir = expand_path(if args.is_empty() { "~" } else { args })?;
+        env::set_current_dir(&dir).map_err(|err| anyhow!("Can't change directory: {}", err))?;
+        self.set_message(dir.display().to_string());
+        Ok(())
+    }
+
+    fn ex_pwd(&mut self, _args: &str) -> Result<()> {
+        self.set_message(env::current_dir()?.display().to_string());
+        Ok(())
+    }
+
+    pub fn register_file_cmds(cmds: &mut EditorCommand) {
+        type FileCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let file_cmds: &[(&[&str], FileCommand)] = &[
+            (&["w", "write"], EditorState::ex_write),
+            (&["wq"], EditorState::ex_write_quit),
+            (&["x", "xit", "exit"], EditorState::ex_exit),
+            (&["update"], EditorState::ex_update),
+            (&["e", "edit"], EditorState::ex_edit),
+            (&["sav", "saveas"], EditorState::ex_saveas),
+            (&["r", "read"], EditorState::ex_read),
+            (&["cd"], EditorState::ex_cd),
+            (&["pwd"], EditorState::ex_pwd),
+        ];
+
+        for (names, f) in file_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_path() {
+        let home = env::var("HOME").unwrap();
+        let cwd = env::current_dir().unwrap();
+
+        assert_eq!(
+            expand_path("~/a.txt").unwrap(),
+            PathBuf::from(&home).join("a.txt")
+        );
+        assert_eq!(expand_path("a/b").unwrap(), cwd.join("a/b"));
+        assert_eq!(
+            expand_path("/x/${HOME}").unwrap(),
+            PathBuf::from(format!("/x/{}", home))
+        );
+        assert_eq!(
+            expand_path("/x/$HOME/y").unwrap(),
+            PathBuf::from(format!("/x/{}/y", home))
+        );
+        assert!(expand_path("").is_err());
+    }
+
+    #[test]
+    fn test_write_and_edit() {
+        let dir = env::temp_dir().join(format!("ce-file-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        let path_str = path.to_str().unwrap();
+
+        let mut state = EditorState::new("a\nb\nc\n".to_string(), None);
+        assert!(state.ex_write("").is_err());
+        state.ex_write(path_str).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "a\nb\nc\n");
+        assert!(!state.is_modified());
+
+        state.cmd_range = Some(TextRange::lines(1, 1));
+        state.ex_write(&format!(">> {}", path_str)).unwrap();
+        state.cmd_range = None;
+        assert_eq!(read_to_string(&path).unwrap(), "a\nb\nc\nb\n");
+
+        state.buf.set_line(0, "x".to_string());
+        assert!(state.ex_edit("").is_err());
+        state.ex_edit("!").unwrap();
+        assert_eq!(state.buf.to_string(), "a\nb\nc\nb\n");
+
+        state.ex_read(path_str).unwrap();
+        assert_eq!(state.buf.to_string(), "a\na\nb\nc\nb\nb\nc\nb\n");
+
+        assert!(state
+            .ex_saveas(&format!("{}/none/b.txt", dir.display()))
+            .is_err());
+        assert_eq!(state.get_path(), Some(path.clone()));
+        assert!(state.is_modified());
+        let other = dir.join("b.txt");
+        state.ex_saveas(other.to_str().unwrap()).unwrap();
+        assert_eq!(state.get_path(), Some(other.clone()));
+        assert!(!state.is_modified());
+
+        state.buf.set_line(0, "y".to_string());
+        assert!(state.ex_write(path_str).is_err());
+        assert!(state.ex_saveas(path_str).is_err());
+        assert_eq!(read_to_string(&path).unwrap(), "a\nb\nc\nb\n");
+        assert_eq!(state.get_path(), Some(other));
+        state.ex_write(&format!("! {}", path_str)).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), state.buf.to_string());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    path::Path,
+};
+
+#[derive(Default, Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum FileType {
+    #[default]
+    Text,
+    Rust,
+    C,
+    Cpp,
+    Go,
+    JavaScript,
+    TypeScript,
+    Python,
+    Shell,
+    Lua,
+    Toml,
+    Yaml,
+    Json,
+    Markdown,
+    Html,
+    Make,
+}
+
+pub struct IndentRules {
+    pub openers: &'static [char],
+    pub closers: &'static [char],
+    pub indent_after: &'static [char],
+    pub dedent_after: &'static [&'static str],
+}
+
+const BRACKETS: IndentRules = IndentRules {
+    openers: &['{', '(', '['],
+    closers: &['}', ')', ']'],
+    indent_after: &[],
+    dedent_after: &[],
+};
+
+impl FileType {
+    pub const ALL: &'static [FileType] = &[
+        FileType::Text,
+        FileType::Rust,
+        FileType::C,
+        FileType::Cpp,
+        FileType::Go,
+        FileType::JavaScript,
+        FileType::TypeScript,
+        FileType::Python,
+        FileType::Shell,
+        FileType::Lua,
+        FileType::Toml,
+        FileType::Yaml,
+        FileType::Json,
+        FileType::Markdown,
+        FileType::Html,
+        FileType::Make,
+    ];
+
+    pub fn detect(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "Makefile" | "makefile" | "GNUmakefile" => return FileType::Make,
+            "Cargo.lock" => return FileType::Toml,
+            ".bashrc" | ".zshrc" | ".profile" | ".bash_profile" => return FileType::Shell,
+            _ => {}
+        }
+
+        match ext.as_str() {
+            "rs" => FileType::Rust,
+            "c" | "h" => FileType::C,
+            "cc" | "cpp" | "cxx" | "hpp" | "hh" => FileType::Cpp,
+            "go" => FileType::Go,
+            "js" | "mjs" | "cjs" | "jsx" => FileType::JavaScript,
+            "ts" | "tsx" => FileType::TypeScript,
+            "py" | "pyw" => FileType::Python,
+            "sh" | "bash" | "zsh" => FileType::Shell,
+            "lua" => FileType::Lua,
+            "toml" => FileType::Toml,
+            "yml" | "yaml" => FileType::Yaml,
+            "json" => FileType::Json,
+            "md" | "markdown" => FileType::Markdown,
+            "html" | "htm" | "xml" => FileType::Html,
+            "mk" => FileType::Make,
+            _ => FileType::Text,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|ft| ft.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileType::Text => "text",
+            FileType::Rust => "rust",
+            FileType::C => "c",
+            FileType::Cpp => "cpp",
+            FileType::Go => "go",
+            FileType::JavaScript => "javascript",
+            FileType::TypeScript => "typescript",
+            FileType::Python => "python",
+            FileType::Shell => "sh",
+            FileType::Lua => "lua",
+            FileType::Toml => "toml",
+            FileType::Yaml => "yaml",
+            FileType::Json => "json",
+            FileType::Markdown => "markdown",
+            FileType::Html => "html",
+            FileType::Make => "make",
+        }
+    }
+
+    pub fn pairs(&self) -> &'static str {
+        match self {
+            FileType::Rust => "()[]{}\"\"",
+            FileType::Html => "()[]{}\"\"''<>",
+            FileType::Text => "()[]{}\"\"",
+            FileType::Make => "()[]{}\"\"''",
+            _ => "()[]{}\"\"''``",
+        }
+    }
+
+    pub fn comment_string(&self) -> &'static str {
+        match self {
+            FileType::Rust
+            | FileType::Cpp
+            | FileType::Go
+            | FileType::JavaScript
+            | FileType::TypeScript
+            | FileType::Json => "// %s",
+            FileType::C => "/* %s */",
+            FileType::Lua => "-- %s",
+            FileType::Markdown | FileType::Html => "<!-- %s -->",
+            _ => "# %s",
+        }
+    }
+
+    pub fn indent_rules(&self) -> IndentRules {
+        match self {
+            FileType::Python => IndentRules {
+                indent_after: &[':'],
+                dedent_after: &["return", "pass", "break", "continue", "raise"],
+                ..BRACKETS
+            },
+            FileType::Yaml => IndentRules {
+                indent_after: &[':'],
+                ..BRACKETS
+            },
+            FileType::Text | FileType::Markdown => IndentRules {
+                openers: &[],
+                closers: &[],
+                ..BRACKETS
+            },
+            _ => BRACKETS,
+        }
+    }
+}
+
+impl Display for FileType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    io::{stdin, stdout, Read, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{anyhow, Result};
+use utils::cli::{cleanup_terminal, init_terminal};
+
+use crate::{
+    cmd::EditorCommand,
+    states::{mode::EditorMode, operator::TextRange, EditorState},
+};
+
+impl EditorState {
+    pub fn shell_command(&self, cmd: &str) -> Command {
+        let mut command = Command::new(&self.options.shell);
+        command.arg("-c").arg(cmd);
+        command
+    }
+
+    pub fn run_filter(&self, cmd: &str, input: String) -> Result<String> {
+        let mut child = self
+            .shell_command(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Feed input on a thread so reading the output can't deadlock
+        let mut child_stdin = child.stdin.take().unwrap();
+        let writer = thread::spawn(move || child_stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output()?;
+        // Commands may exit without reading all input
+        let _ = writer.join();
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.lines().next().unwrap_or_default();
+            return Err(anyhow!("Shell returned {}: {}", output.status, message));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    pub fn run_interactive(&mut self, cmd: &str, input: Option<String>) -> Result<()> {
+        cleanup_terminal()?;
+        println!();
+
+        let result = (|| -> Result<()> {
+            let mut command = self.shell_command(cmd);
+            if input.is_some() {
+                command.stdin(Stdio::piped());
+            }
+            let mut child = command.spawn()?;
+            // Feed input on a thread so a command that never reads it can't block
+            let writer = match (child.stdin.take(), input) {
+                (Some(mut child_stdin), Some(input)) => Some(thread::spawn(move || {
+                    child_stdin.write_all(input.as_bytes())
+                })),
+                _ => None,
+            };
+            let status = child.wait()?;
+            if let Some(writer) = writer {
+                let _ = writer.join();
+            }
+            if !status.success() {
+                println!("\nshell returned {}", status.code().unwrap_or(-1));
+            }
+            Ok(())
+        })();
+
+        print!("\nPress ENTER to continue");
+        stdout().flush()?;
+        let mut buf = [0; 1];
+        while stdin().read(&mut buf).is_ok_and(|n| n > 0) && buf[0] != b'\n' {}
+
+        init_terminal()?;
+        result
+    }
+
+    fn filter_lines(&mut self, start: usize, end: usize, cmd: &str) -> Result<()> {
+        let input = self.range_text(TextRange::lines(start, end)).text + "\n";
+        let output = self.run_filter(cmd, input)?;
+        let output = output.strip_suffix('\n').unwrap_or(&output);
+
+        let lines = if output.is_empty() {
+            Vec::new()
+        } else {
+            output.split('\n').map(|line| line.to_string()).collect()
+        };
+        let count = lines.len();
+        self.replace_lines(start, end, lines);
+
+        self.cursor.move_y_to(&self.buf, start);
+        self.cursor.move_x_to(&self.buf, 0);
+        self.set_message(format!("{} lines filtered", count));
+        Ok(())
+    }
+
+    // :[range]!cmd
+    fn ex_bang(&mut self, cmd: &str) -> Result<()> {
+        if cmd.is_empty() {
+            return Err(anyhow!("Argument required"));
+        }
+
+        match self.cmd_range {
+            Some(range) => self.filter_lines(range.start.y, range.end.y, cmd),
+            None => self.run_interactive(cmd, None),
+        }
+    }
+
+    pub fn read_command(&mut self, cmd: &str) -> Result<()> {
+        let output = self.run_filter(cmd, String::new())?;
+        self.insert_lines_below(&output);
+        Ok(())
+    }
+
+    pub fn write_command(&mut self, cmd: &str) -> Result<()> {
+        let range = self
+            .cmd_range
+            .unwrap_or(TextRange::lines(0, self.buf.line_count() - 1));
+        let input = self.range_text(range).text + "\n";
+        self.run_interactive(cmd, Some(input))
+    }
+
+    pub fn register_filter_cmds(cmds: &mut EditorCommand) {
+        cmds.register_with_args(
+            "!",
+            Box::new(|editor, cmd| {
+                if let Err(err) = editor.ex_bang(cmd) {
+                    editor.set_message(err.to_string());
+                }
+            }),
+        );
+        cmds.register_operator(
+            "filter",
+            Box::new(|editor, range| {
+                editor.cmd_buf = if editor.get_mode() == EditorMode::Visual {
+                    "'<,'>!".to_string()
+                } else if range.start.y == range.end.y {
+                    ".!".to_string()
+                } else {
+                    format!(".,.+{}!", range.end.y - range.start.y)
+                };
+                editor.set_mode(EditorMode::Command);
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_lines() {
+        // Shell built-ins only, so the test needs no external commands
+        let mut state = EditorState::new("c\nb\na\nz".to_string(), None);
+
+        state
+            .filter_lines(0, 2, "while read -r l; do echo \"<$l>\"; done")
+            .unwrap();
+        assert_eq!(state.buf.to_string(), "<c>\n<b>\n<a>\nz");
+
+        state
+            .filter_lines(1, 2, "while read -r l; do :; done; exit 1")
+            .unwrap_err();
+        assert_eq!(state.buf.to_string(), "<c>\n<b>\n<a>\nz");
+
+        state.read_command("echo x; echo y").unwrap();
+        assert_eq!(state.buf.to_string(), "<c>\nx\ny\n<b>\n<a>\nz");
+    }
+}
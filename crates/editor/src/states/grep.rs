@@ -0,0 +1,423 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{read, read_to_string, write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Result};
+use ignore::{WalkBuilder, WalkState};
+use regex::Regex;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{
+        buf::CodeBuffer,
+        buffers::display_name,
+        file::expand_path,
+        quickfix::{QuickfixItem, QuickfixList},
+        EditorState,
+    },
+};
+
+// A NUL in the first block marks a binary file
+const BINARY_CHECK_LEN: usize = 8192;
+
+#[derive(Clone, Debug)]
+struct ReplaceHit {
+    path: PathBuf,
+    line: usize,
+    old: String,
+    new: String,
+}
+
+#[derive(Clone)]
+pub struct ReplacePlan {
+    buffer: usize,
+    hits: Vec<ReplaceHit>,
+}
+
+fn split_pattern(args: &str) -> Result<(String, Vec<&str>)> {
+    let args = args.trim();
+    if args.is_empty() {
+        return Err(anyhow!("Argument required"));
+    }
+
+    let (pattern, rest) = match args.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let end = args[1..]
+                .find(quote)
+                .ok_or_else(|| anyhow!("Missing quote: {}", args))?;
+            (&args[1..end + 1], &args[end + 2..])
+        }
+        _ => args.split_once(char::is_whitespace).unwrap_or((args, "")),
+    };
+    Ok((pattern.to_string(), rest.split_whitespace().collect()))
+}
+
+fn search_paths(paths: &[&str]) -> Result<Vec<PathBuf>> {
+    if paths.is_empty() {
+        return Ok(vec![env::current_dir()?]);
+    }
+    paths.iter().map(|path| expand_path(path)).collect()
+}
+
+fn search_file(path: PathBuf, re: &Regex) -> Vec<QuickfixItem> {
+    let Ok(bytes) = read(&path) else {
+        return Vec::new();
+    };
+    if bytes[..bytes.len().min(BINARY_CHECK_LEN)].contains(&0) {
+        return Vec::new();
+    }
+    let text = String::from_utf8_lossy(&bytes);
+
+    text.lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let m = re.find(text)?;
+            Some(QuickfixItem {
+                path: path.clone(),
+                line,
+                col: m.start(),
+                text: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn search_files(re: &Regex, paths: &[PathBuf]) -> Vec<QuickfixItem> {
+    let Some((first, rest)) = paths.split_first() else {
+        return Vec::new();
+    };
+    let mut builder = WalkBuilder::new(first);
+    for path in rest {
+        builder.add(path);
+    }
+
+    let hits = Mutex::new(Vec::new());
+    builder.require_git(false).build_parallel().run(|| {
+        Box::new(|entry| {
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+            if entry.file_type().is_some_and(|t| t.is_file()) {
+                let found = search_file(entry.into_path(), re);
+                if !found.is_empty() {
+                    hits.lock().unwrap().extend(found);
+                }
+            }
+            WalkState::Continue
+        })
+    });
+
+    let mut hits = hits.into_inner().unwrap();
+    hits.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    hits
+}
+
+fn split_replace(args: &str) -> Result<(String, String, Vec<&str>)> {
+    let args = args.trim();
+    let delimiter = args
+        .chars()
+        .next()
+        .ok_or_else(|| anyhow!("Argument required"))?;
+    let mut parts = args[delimiter.len_utf8()..].splitn(3, delimiter);
+    let pattern = parts.next().unwrap_or_default();
+    let replacement = parts
+        .next()
+        .ok_or_else(|| anyhow!("Invalid argument: {}", args))?;
+    let rest = parts.next().unwrap_or_default();
+    if pattern.is_empty() {
+        return Err(anyhow!("Invalid argument: {}", args));
+    }
+    Ok((
+        pattern.to_string(),
+        replacement.to_string(),
+        rest.split_whitespace().collect(),
+    ))
+}
+
+fn remaining_hits(preview: &CodeBuffer) -> Vec<(String, usize)> {
+    let mut remaining = Vec::new();
+    let mut file = None;
+    for y in 1..preview.line_count() {
+        let line = preview.get_line(y);
+        if line.is_empty() {
+            continue;
+        }
+        match line.strip_prefix("  ") {
+            Some(hit) => {
+                let number = hit.trim_start().split(':').next().unwrap_or_default();
+                if let (Some(file), Ok(number)) = (&file, number.parse::<usize>()) {
+                    remaining.push((String::clone(file), number - 1));
+                }
+            }
+            None => file = Some(line.to_string()),
+        }
+    }
+    remaining
+}
+
+impl EditorState {
+    fn ex_grep(&mut self, location: bool, args: &str) -> Result<()> {
+        let (pattern, paths) = split_pattern(args)?;
+        let re = Regex::new(&pattern)?;
+        let hits = search_files(&re, &search_paths(&paths)?);
+        if hits.is_empty() {
+            return Err(anyhow!("Pattern not found: {}", pattern));
+        }
+
+        self.set_list(location, QuickfixList::new(hits));
+        self.jump_to_item(location, 0)
+    }
+
+    fn ex_replace(&mut self, args: &str) -> Result<()> {
+        let (pattern, replacement, paths) = split_replace(args)?;
+        let re = Regex::new(&pattern)?;
+        let hits: Vec<ReplaceHit> = search_files(&re, &search_paths(&paths)?)
+            .into_iter()
+            .map(|hit| ReplaceHit {
+                new: re.replace_all(&hit.text, replacement.as_str()).to_string(),
+                old: hit.text,
+                path: hit.path,
+                line: hit.line,
+            })
+            .filter(|hit| hit.old != hit.new)
+            .collect();
+        if hits.is_empty() {
+            return Err(anyhow!("Pattern not found: {}", pattern));
+        }
+
+        let mut lines = vec![format!(
+            "Replace /{}/ with \"{}\": delete lines to exclude them, then :ReplaceApply",
+            pattern, replacement
+        )];
+        let mut file = None;
+        for hit in &hits {
+            if file != Some(&hit.path) {
+                file = Some(&hit.path);
+                lines.push(String::new());
+                lines.push(display_name(Some(&hit.path)));
+            }
+            lines.push(format!(
+                "  {}: {} → {}",
+                hit.line + 1,
+                hit.old.trim(),
+                hit.new.trim()
+            ));
+        }
+
+        let id = self.new_buffer();
+        self.switch_buffer(id);
+        self.buf = CodeBuffer::new(lines.join("\n") + "\n");
+        self.saved_buf = self.buf.clone();
+        self.replace_plan = Some(ReplacePlan { buffer: id, hits });
+        Ok(())
+    }
+
+    // Read every file first so nothing is written if one can't be read
+    fn ex_replace_apply(&mut self, _args: &str) -> Result<()> {
+        let plan = self
+            .replace_plan
+            .take()
+            .ok_or_else(|| anyhow!("No replace in progress"))?;
+        if !self.buffer_ids().contains(&plan.buffer) {
+            return Err(anyhow!("Replace preview was closed"));
+        }
+        let remaining = remaining_hits(self.buffer_text(plan.buffer));
+
+        let mut files: BTreeMap<PathBuf, Vec<&ReplaceHit>> = BTreeMap::new();
+        for hit in &plan.hits {
+            let key = (display_name(Some(&hit.path)), hit.line);
+            if remaining.contains(&key) {
+                files.entry(hit.path.clone()).or_default().push(hit);
+            }
+        }
+
+        let mut changed = 0;
+        let mut skipped = Vec::new();
+        let mut contents = Vec::new();
+        for (path, hits) in &files {
+            // Skip files with unsaved changes in a buffer
+            if self.is_file_buffer_modified(path) {
+                skipped.push(display_name(Some(path)));
+                continue;
+            }
+            let text = match read_to_string(path) {
+                Ok(text) => text,
+                Err(err) => {
+                    let message = format!("Can't read {}: {}", display_name(Some(path)), err);
+                    self.replace_plan = Some(plan);
+                    return Err(anyhow!(message));
+                }
+            };
+            let mut lines: Vec<String> = text.split_inclusive('\n').map(String::from).collect();
+            for hit in hits {
+                let Some(line) = lines.get_mut(hit.line) else {
+                    continue;
+                };
+                let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+                // Leave lines that changed since the search
+                if line.trim_end_matches(['\r', '\n']) == hit.old {
+                    *line = format!("{}{}", hit.new, ending);
+                    changed += 1;
+                }
+            }
+            contents.push((path.clone(), lines.concat()));
+        }
+
+        // Keep lines that failed to write so :ReplaceApply can retry
+        let mut failed = Vec::new();
+        for (path, text) in contents {
+            match write(&path, text) {
+                Ok(()) => self.reload_file_buffer(&path),
+                Err(err) => failed.push((path, err)),
+            }
+        }
+        let written = files.len() - skipped.len() - failed.len();
+        if !failed.is_empty() {
+            let message = failed
+                .iter()
+                .map(|(path, err)| format!("{}: {}", display_name(Some(path)), err))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let hits = plan
+                .hits
+                .into_iter()
+                .filter(|hit| failed.iter().any(|(path, _)| path == &hit.path))
+                .collect();
+            self.replace_plan = Some(ReplacePlan {
+                buffer: plan.buffer,
+                hits,
+            });
+            return Err(anyhow!(
+                "{} files changed, can't write: {}",
+                written,
+                message
+            ));
+        }
+
+        self.delete_buffer(plan.buffer);
+        let mut message = format!("{} lines changed in {} files", changed, written);
+        if !skipped.is_empty() {
+            message += &format!(" (skipped modified buffers: {})", skipped.join(", "));
+        }
+        self.set_message(message);
+        Ok(())
+    }
+
+    pub fn register_grep_cmds(cmds: &mut EditorCommand) {
+        type GrepCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let grep_cmds: &[(&[&str], GrepCommand)] = &[
+            (&["gr", "grep"], |editor, args| editor.ex_grep(false, args)),
+            (&["lgr", "lgrep"], |editor, args| editor.ex_grep(true, args)),
+            (&["Replace"], EditorState::ex_replace),
+            (&["ReplaceApply"], EditorState::ex_replace_apply),
+        ];
+
+        for (names, f) in grep_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all};
+
+    use super::*;
+
+    #[test]
+    fn test_split_args() {
+        let (pattern, paths) = split_pattern("'a b' src lib").unwrap();
+        assert_eq!(pattern, "a b");
+        assert_eq!(paths, vec!["src", "lib"]);
+        assert_eq!(split_pattern("foo").unwrap(), ("foo".to_string(), vec![]));
+
+        let (pattern, replacement, paths) = split_replace("#a/b#c# src").unwrap();
+        assert_eq!((pattern.as_str(), replacement.as_str()), ("a/b", "c"));
+        assert_eq!(paths, vec!["src"]);
+        assert!(split_replace("/a").is_err());
+    }
+
+    #[test]
+    fn test_grep_and_replace() {
+        let dir = env::temp_dir().join(format!("ce-grep-test-{}", std::process::id()));
+        create_dir_all(dir.join("ignored")).unwrap();
+        write(dir.join(".gitignore"), "ignored/\n").unwrap();
+        write(dir.join("a.txt"), "foo\nbar\nfoo bar\n").unwrap();
+        write(dir.join("b.txt"), "éfoo\n").unwrap();
+        write(dir.join("bin"), b"foo\0").unwrap();
+        write(dir.join("ignored/c.txt"), "foo\n").unwrap();
+        let dir_str = dir.to_str().unwrap();
+
+        let mut state = EditorState::new(String::new(), None);
+        state.ex_grep(false, &format!("fo+ {}", dir_str)).unwrap();
+        assert_eq!(state.quickfix.len(), 3);
+        assert_eq!(state.get_path(), Some(dir.join("a.txt")));
+        state.jump_to_item(false, 1).unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 2));
+        state.jump_to_item(false, 2).unwrap();
+        assert_eq!(state.get_path(), Some(dir.join("b.txt")));
+        assert_eq!(state.cursor.get_display(&state.buf), ("é".len(), 0));
+        assert!(state.jump_to_item(false, 3).is_err());
+        state.jump_to_item(false, 0).unwrap();
+        assert_eq!(state.get_path(), Some(dir.join("a.txt")));
+
+        state.ex_replace(&format!("/foo/baz/ {}", dir_str)).unwrap();
+        assert_eq!(state.buf.line_count(), 9);
+        let y = (0..state.buf.line_count())
+            .find(|y| state.buf.get_line(*y).starts_with("  3:"))
+            .unwrap();
+        state.buf.remove_line(y);
+        state.ex_replace_apply("").unwrap();
+
+        assert_eq!(
+            read_to_string(dir.join("a.txt")).unwrap(),
+            "baz\nbar\nfoo bar\n"
+        );
+        assert_eq!(read_to_string(dir.join("b.txt")).unwrap(), "ébaz\n");
+        assert_eq!(read_to_string(dir.join("ignored/c.txt")).unwrap(), "foo\n");
+        assert_eq!(state.get_path(), Some(dir.join("a.txt")));
+        assert_eq!(state.buf.to_string(), "baz\nbar\nfoo bar\n");
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_replace_unreadable_file() {
+        let dir = env::temp_dir().join(format!("ce-replace-test-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("a.txt"), "foo\n").unwrap();
+        write(dir.join("b.txt"), "foo\n").unwrap();
+
+        let mut state = EditorState::new(String::new(), None);
+        state
+            .ex_replace(&format!("/foo/bar/ {}", dir.to_str().unwrap()))
+            .unwrap();
+        std::fs::remove_file(dir.join("b.txt")).unwrap();
+        create_dir_all(dir.join("b.txt")).unwrap();
+        assert!(state.ex_replace_apply("").is_err());
+        assert_eq!(read_to_string(dir.join("a.txt")).unwrap(), "foo\n");
+        assert!(state.replace_plan.is_some());
+
+        remove_dir_all(dir.join("b.txt")).unwrap();
+        write(dir.join("b.txt"), "foo\n").unwrap();
+        state.ex_replace_apply("").unwrap();
+        assert_eq!(read_to_string(dir.join("a.txt")).unwrap(), "bar\n");
+        assert_eq!(read_to_string(dir.join("b.txt")).unwrap(), "bar\n");
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    cmd::EditorCommand,
+    states::{
+        buf::CodeBuffer, cursor::EditorCursor, mode::EditorMode, multicursor::Selection,
+        EditorState,
+    },
+};
+
+#[derive(Clone)]
+struct Snapshot {
+    buf: CodeBuffer,
+    cursor: EditorCursor,
+    cursors: Vec<Selection>,
+}
+
+#[derive(Clone)]
+struct Pending {
+    version: usize,
+    cursor: EditorCursor,
+    cursors: Vec<Selection>,
+}
+
+#[derive(Clone, Default)]
+pub struct History {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    base: Option<CodeBuffer>,
+    current: Option<Pending>,
+}
+
+impl EditorState {
+    pub fn begin_change(&mut self) {
+        if self.history.current.is_some() {
+            return;
+        }
+        // The buffer was replaced outside the history
+        if self.history.base.as_ref().map(CodeBuffer::version) != Some(self.buf.version()) {
+            self.history.base = Some(self.buf.clone());
+        }
+        self.history.current = Some(Pending {
+            version: self.buf.version(),
+            cursor: self.cursor.clone(),
+            cursors: self.cursors.clone(),
+        });
+    }
+
+    // Insert mode is one change until it ends
+    pub fn end_change(&mut self) {
+        if self.get_mode() == EditorMode::Insert {
+            return;
+        }
+
+        let Some(pending) = self.history.current.take() else {
+            return;
+        };
+        if pending.version == self.buf.version() {
+            return;
+        }
+        let Some(base) = self.history.base.replace(self.buf.clone()) else {
+            return;
+        };
+        self.history.undo.push(Snapshot {
+            buf: base,
+            cursor: pending.cursor,
+            cursors: pending.cursors,
+        });
+        self.history.redo.clear();
+
+        let excess = self
+            .history
+            .undo
+            .len()
+            .saturating_sub(self.options.undolevels);
+        self.history.undo.drain(..excess);
+    }
+
+    pub fn cancel_change(&mut self) {
+        self.history.current = None;
+    }
+
+    fn undo(&mut self) {
+        self.history.current = None;
+
+        for _ in 0..self.get_count() {
+            let Some(snapshot) = self.history.undo.pop() else {
+                self.set_message("Already at oldest change".to_string());
+                return;
+            };
+            let current = Snapshot {
+                buf: std::mem::replace(&mut self.buf, snapshot.buf),
+                cursor: self.cursor.clone(),
+                cursors: std::mem::replace(&mut self.cursors, snapshot.cursors),
+            };
+            self.history.redo.push(current);
+            self.cursor = snapshot.cursor;
+        }
+    }
+
+    fn redo(&mut self) {
+        self.history.current = None;
+
+        for _ in 0..self.get_count() {
+            let Some(snapshot) = self.history.redo.pop() else {
+                self.set_message("Already at newest change".to_string());
+                return;
+            };
+            let current = Snapshot {
+                buf: std::mem::replace(&mut self.buf, snapshot.buf),
+                cursor: self.cursor.clone(),
+                cursors: std::mem::replace(&mut self.cursors, snapshot.cursors),
+            };
+            self.history.undo.push(current);
+            self.cursor = snapshot.cursor;
+        }
+    }
+
+    pub fn register_history_cmds(cmds: &mut EditorCommand) {
+        cmds.register("undo", Box::new(|editor| editor.undo()));
+        cmds.register("redo", Box::new(|editor| editor.redo()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_redo() {
+        let mut state = EditorState::new("abc".to_string(), None);
+
+        state.begin_change();
+        state.buf.insert('x', 0, 0);
+        state.end_change();
+        state.begin_change();
+        state.buf.insert('y', 0, 0);
+        state.end_change();
+        assert_eq!(state.buf.to_string(), "yxabc");
+
+        state.undo();
+        assert_eq!(state.buf.to_string(), "xabc");
+        state.undo();
+        state.undo();
+        assert_eq!(state.buf.to_string(), "abc");
+        assert_eq!(
+            state.get_message(),
+            Some(&"Already at oldest change".to_string())
+        );
+
+        state.redo();
+        assert_eq!(state.buf.to_string(), "xabc");
+    }
+
+    #[test]
+    fn test_undolevels() {
+        let mut state = EditorState::new("abc".to_string(), None);
+        state.options.set("undolevels=2").unwrap();
+
+        state.begin_change();
+        state.end_change();
+        assert!(state.history.undo.is_empty());
+
+        for c in ['x', 'y', 'z'] {
+            state.begin_change();
+            state.buf.insert(c, 0, 0);
+            state.end_change();
+        }
+        assert_eq!(state.history.undo.len(), 2);
+
+        state.undo();
+        state.undo();
+        state.undo();
+        assert_eq!(state.buf.to_string(), "xabc");
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+
+use utils::text::{display_width, indent_len, indent_width, make_indent};
+
+use crate::{
+    cmd::EditorCommand,
+    states::{buf::CodeBuffer, mode::EditorMode, options::EditorOptions, EditorState},
+};
+
+impl EditorOptions {
+    pub fn detect_indent(&mut self, buf: &CodeBuffer) {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        let mut deltas: HashMap<usize, usize> = HashMap::new();
+        let mut prev_width = 0;
+
+        for y in 0..buf.line_count() {
+            let line = buf.get_line(y);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if line.starts_with('\t') {
+                tab_lines += 1;
+            } else if line.starts_with(' ') {
+                space_lines += 1;
+            }
+
+            let width = indent_width(line, self.tabstop);
+            if !line.starts_with('\t') && width > prev_width {
+                *deltas.entry(width - prev_width).or_default() += 1;
+            }
+            prev_width = width;
+        }
+
+        if tab_lines > space_lines {
+            self.expandtab = false;
+            self.shiftwidth = 0;
+        } else if space_lines > 0 {
+            let width = [2, 4, 8, 3]
+                .into_iter()
+                .max_by_key(|width| deltas.get(width).copied().unwrap_or_default());
+            if let Some(width) = width.filter(|width| deltas.contains_key(width)) {
+                self.expandtab = true;
+                self.shiftwidth = width;
+            }
+        }
+    }
+}
+
+impl EditorState {
+    pub fn shift_lines(&mut self, start: usize, end: usize, levels: isize) {
+        let sw = self.options.shift_width();
+        let end = end.min(self.buf.line_count() - 1);
+
+        for y in start..=end {
+            let line = self.buf.get_line(y);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let width = indent_width(line, self.options.tabstop);
+            let width = if levels < 0 {
+                width.saturating_sub(sw * -levels as usize)
+            } else {
+                width + sw * levels as usize
+            };
+            self.set_indent(y, width);
+        }
+
+        self.cursor.move_y_to(&self.buf, start);
+        let x = indent_len(self.buf.get_line(start));
+        self.cursor.move_x_to(&self.buf, x);
+    }
+
+    fn shift_count_lines(&mut self, levels: isize) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let count = self.get_count();
+        self.shift_lines(cursor_y, cursor_y + count - 1, levels);
+    }
+
+    fn shift_block(&mut self, levels: isize) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        if let Some((_, y)) = self.cursor.matching_bracket(&self.buf) {
+            self.shift_lines(cursor_y.min(y), cursor_y.max(y), levels);
+        }
+    }
+
+    fn shift_visual(&mut self, levels: isize) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let start = self.visual_start.y.min(cursor_y);
+        let end = self.visual_start.y.max(cursor_y);
+        let levels = levels * self.get_count() as isize;
+
+        self.shift_lines(start, end, levels);
+        self.set_mode(EditorMode::Normal);
+    }
+
+    pub fn compute_indent(&self, y: usize) -> usize {
+        let rules = self.options.filetype.indent_rules();
+        let sw = self.options.shift_width();
+
+        let Some(prev_y) = (0..y)
+            .rev()
+            .find(|&prev_y| !self.buf.get_line(prev_y).trim().is_empty())
+        else {
+            return 0;
+        };
+        let prev = self.buf.get_line(prev_y);
+        let mut width = indent_width(prev, self.options.tabstop);
+
+        if self.options.smartindent {
+            let code = prev.trim().trim_start_matches(rules.closers);
+            let depth = code.chars().fold(0, |depth, c| {
+                if rules.openers.contains(&c) {
+                    depth + 1
+                } else if rules.closers.contains(&c) {
+                    depth - 1
+                } else {
+                    depth
+                }
+            });
+            let first_word = code.split_whitespace().next().unwrap_or_default();
+
+            if depth > 0 || code.ends_with(rules.indent_after) {
+                width += sw;
+            } else if rules.dedent_after.contains(&first_word) {
+                width = width.saturating_sub(sw);
+            }
+
+            if self.buf.get_line(y).trim_start().starts_with(rules.closers) {
+                width = width.saturating_sub(sw);
+            }
+        }
+
+        width
+    }
+
+    pub fn set_indent(&mut self, y: usize, width: usize) {
+        let line = self.buf.get_line(y);
+        let indent = make_indent(width, self.options.tabstop, self.options.expandtab);
+        let rest: String = line.chars().skip(indent_len(line)).collect();
+        self.buf.set_line(y, indent + &rest);
+    }
+
+    pub fn reindent_lines(&mut self, start: usize, end: usize) {
+        let end = end.min(self.buf.line_count() - 1);
+
+        for y in start..=end {
+            if self.buf.get_line(y).trim().is_empty() {
+                self.buf.set_line(y, String::new());
+            } else {
+                let width = self.compute_indent(y);
+                self.set_indent(y, width);
+            }
+        }
+    }
+
+    pub fn insert_newline(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        self.buf.split_line(cursor_x, cursor_y);
+        self.cursor.move_by(&self.buf, 0, 1);
+
+        if !self.options.autoindent {
+            self.cursor.move_x_to(&self.buf, 0);
+            return;
+        }
+
+        let prev = self.buf.get_line(cursor_y);
+        let width = if prev.trim().is_empty() {
+            // Carry the indent over from a blank line and clear its spaces
+            let width = indent_width(prev, self.options.tabstop);
+            self.buf.set_line(cursor_y, String::new());
+            width
+        } else {
+            self.compute_indent(cursor_y + 1)
+        };
+
+        self.set_indent(cursor_y + 1, width);
+        let x = indent_len(self.buf.get_line(cursor_y + 1));
+        self.cursor.move_x_to(&self.buf, x);
+    }
+
+    pub fn open_line(&mut self, above: bool) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let y = if above { cursor_y } else { cursor_y + 1 };
+        self.buf.insert_line(y, String::new());
+
+        if self.options.autoindent {
+            let width = if above {
+                indent_width(self.buf.get_line(y + 1), self.options.tabstop)
+            } else {
+                self.compute_indent(y)
+            };
+            self.set_indent(y, width);
+        }
+
+        self.cursor.move_y_to(&self.buf, y);
+        let x = self.buf.line_length(y);
+        self.cursor.move_x_to(&self.buf, x);
+        self.set_mode(EditorMode::Insert);
+    }
+
+    pub fn dedent_closer(&mut self, c: char) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let rules = self.options.filetype.indent_rules();
+        let line = self.buf.get_line(cursor_y);
+
+        if !self.options.smartindent
+            || !rules.closers.contains(&c)
+            || line.trim_start() != c.to_string() + &line[cursor_x..]
+        {
+            return;
+        }
+
+        let old_len = indent_len(line);
+        let width = self.compute_indent(cursor_y);
+        self.set_indent(cursor_y, width);
+        let new_len = indent_len(self.buf.get_line(cursor_y));
+        self.cursor
+            .move_by(&self.buf, new_len as isize - old_len as isize, 0);
+    }
+
+    pub fn insert_tab(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let soft_tab = self.options.soft_tab_width();
+
+        if soft_tab == 0 {
+            self.buf.insert('\t', cursor_x, cursor_y);
+            self.cursor.move_by(&self.buf, 1, 0);
+            return;
+        }
+
+        let line = self.buf.get_line(cursor_y);
+        let col = display_width(&line[..cursor_x], self.options.tabstop);
+        let spaces = soft_tab - col % soft_tab;
+        self.buf.insert_str(&" ".repeat(spaces), cursor_x, cursor_y);
+        self.cursor.move_by(&self.buf, spaces as isize, 0);
+    }
+
+    pub fn delete_soft_tab(&mut self) -> bool {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let soft_tab = self.options.soft_tab_width();
+        let line = self.buf.get_line(cursor_y);
+
+        if soft_tab == 0 || cursor_x == 0 || !line[..cursor_x].ends_with(' ') {
+            return false;
+        }
+
+        let spaces = line[..cursor_x]
+            .chars()
+            .rev()
+            .take_while(|c| *c == ' ')
+            .count();
+        let col = display_width(&line[..cursor_x], self.options.tabstop);
+        let len = match col % soft_tab {
+            0 => soft_tab,
+            rem => rem,
+        };
+        if spaces < len {
+            return false;
+        }
+
+        for _ in 0..len {
+            self.buf.delete(cursor_x - len, cursor_y);
+        }
+        self.cursor.move_by(&self.buf, -(len as isize), 0);
+
+        true
+    }
+
+    pub fn register_indent_cmds(cmds: &mut EditorCommand) {
+        cmds.register("indent", Box::new(|editor| editor.shift_count_lines(1)));
+        cmds.register("de-indent", Box::new(|editor| editor.shift_count_lines(-1)));
+        cmds.register("block_indent", Box::new(|editor| editor.shift_block(1)));
+        cmds.register("block_de-indent", Box::new(|editor| editor.shift_block(-1)));
+        cmds.register("visual_indent", Box::new(|editor| editor.shift_visual(1)));
+        cmds.register(
+            "visual_de-indent",
+            Box::new(|editor| editor.shift_visual(-1)),
+        );
+        cmds.register("insert_below", Box::new(|editor| editor.open_line(false)));
+        cmds.register("insert_above", Box::new(|editor| editor.open_line(true)));
+        cmds.register_operator(
+            "reindent",
+            Box::new(|editor, range| editor.reindent_lines(range.start.y, range.end.y)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::filetype::FileType;
+
+    #[test]
+    fn test_detect_indent() {
+        let mut options = EditorOptions::default();
+        options.detect_indent(&CodeBuffer::new(
+            "fn main() {\n    if a {\n        b();\n    }\n}".to_string(),
+        ));
+        assert!(options.expandtab);
+        assert_eq!(options.shiftwidth, 4);
+
+        let mut options = EditorOptions::default();
+        options.detect_indent(&CodeBuffer::new("a:\n\tb\n\tc".to_string()));
+        assert!(!options.expandtab);
+        assert_eq!(options.shift_width(), 8);
+    }
+
+    #[test]
+    fn test_shift_lines() {
+        let mut state = EditorState::new("a\n  b\n\nc".to_string(), None);
+        state.options.shiftwidth = 4;
+        state.options.tabstop = 4;
+
+        state.shift_lines(0, 3, 1);
+        assert_eq!(state.buf.to_string(), "    a\n      b\n\n    c");
+
+        state.options.expandtab = false;
+        state.shift_lines(1, 1, 1);
+        assert_eq!(state.buf.get_line(1), "\t\t  b");
+
+        state.shift_lines(0, 1, -1);
+        assert_eq!(state.buf.to_string(), "a\n\t  b\n\n    c");
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let mut state = EditorState::new("é(ü[a]\n) {}".to_string(), None);
+        state.cursor.move_x_to(&state.buf, "é".len());
+        assert_eq!(state.cursor.matching_bracket(&state.buf), Some((0, 1)));
+
+        state.cursor.move_x_to(&state.buf, "é(ü".len());
+        state.cursor.cmd_match_paren(&state.buf);
+        assert_eq!(state.cursor.get_display(&state.buf), ("é(ü[a".len(), 0));
+        state.cursor.cmd_match_paren(&state.buf);
+        assert_eq!(state.cursor.get_display(&state.buf), ("é(ü".len(), 0));
+    }
+
+    #[test]
+    fn test_reindent_lines() {
+        let mut state = EditorState::new(
+            "fn main() {\nif a {\nb(\nc,\n);\n} else {\nd();\n}\n}".to_string(),
+            None,
+        );
+        state.options.filetype = FileType::Rust;
+        state.options.shiftwidth = 4;
+
+        state.reindent_lines(0, 8);
+        assert_eq!(
+            state.buf.to_string(),
+            "fn main() {\n    if a {\n        b(\n            c,\n        );\n    } else {\n        d();\n    }\n}"
+        );
+    }
+
+    #[test]
+    fn test_insert_newline() {
+        let mut state = EditorState::new("def f():".to_string(), None);
+        state.options.filetype = FileType::Python;
+        state.options.shiftwidth = 4;
+
+        state.cursor.move_x_to(&state.buf, 8);
+        state.insert_newline();
+        state.buf.insert_str("return 1", 4, 1);
+        state.cursor.move_x_to(&state.buf, 12);
+        state.insert_newline();
+        assert_eq!(state.buf.to_string(), "def f():\n    return 1\n");
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 2));
+    }
+}
@@ -0,0 +1,437 @@
+use super::{
+    filetype::FileType,
+    syntax::{StringRule, SyntaxDef, PLAIN},
+};
+
+const fn string(open: &'static str, close: &'static str, multiline: bool) -> StringRule {
+    StringRule {
+        open,
+        close,
+        escape: true,
+        multiline,
+    }
+}
+
+const fn raw_string(open: &'static str, close: &'static str, multiline: bool) -> StringRule {
+    StringRule {
+        open,
+        close,
+        escape: false,
+        multiline,
+    }
+}
+
+const RUST: SyntaxDef = SyntaxDef {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_comments: true,
+    strings: &[
+        raw_string("r#\"", "\"#", true),
+        raw_string("r\"", "\"", true),
+        string("b\"", "\"", true),
+        string("\"", "\"", true),
+    ],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "static", "struct", "super", "trait", "type", "unsafe",
+        "use", "where", "while", "yield",
+    ],
+    types: &[
+        "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "str", "u8",
+        "u16", "u32", "u64", "u128", "usize",
+    ],
+    constants: &["true", "false"],
+    capitalized_types: true,
+    char_literals: true,
+    bang_macros: true,
+    attributes: true,
+    ..PLAIN
+};
+
+const C_KEYWORDS: &[&str] = &[
+    "break", "case", "const", "continue", "default", "do", "else", "enum", "extern", "for", "goto",
+    "if", "inline", "register", "return", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "volatile", "while",
+];
+
+const C_TYPES: &[&str] = &[
+    "bool", "char", "double", "float", "int", "long", "short", "signed", "size_t", "unsigned",
+    "void", "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t",
+    "uint64_t", "FILE",
+];
+
+const C_STRINGS: &[StringRule] = &[string("\"", "\"", false), string("'", "'", false)];
+
+const C: SyntaxDef = SyntaxDef {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    strings: C_STRINGS,
+    keywords: C_KEYWORDS,
+    types: C_TYPES,
+    constants: &["NULL", "true", "false", "EOF"],
+    line_macro: Some('#'),
+    ..PLAIN
+};
+
+const CPP: SyntaxDef = SyntaxDef {
+    keywords: &[
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "constexpr",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "explicit",
+        "extern",
+        "for",
+        "friend",
+        "goto",
+        "if",
+        "inline",
+        "namespace",
+        "new",
+        "noexcept",
+        "operator",
+        "override",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "this",
+        "throw",
+        "try",
+        "typedef",
+        "typename",
+        "union",
+        "using",
+        "virtual",
+        "volatile",
+        "while",
+    ],
+    types: &[
+        "auto", "bool", "char", "double", "float", "int", "long", "short", "signed", "size_t",
+        "unsigned", "void", "string", "vector", "map",
+    ],
+    constants: &["nullptr", "NULL", "true", "false"],
+    ..C
+};
+
+const GO: SyntaxDef = SyntaxDef {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    strings: &[
+        string("\"", "\"", false),
+        string("'", "'", false),
+        raw_string("`", "`", true),
+    ],
+    keywords: &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "fallthrough",
+        "for",
+        "func",
+        "go",
+        "goto",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "type",
+        "var",
+    ],
+    types: &[
+        "bool",
+        "byte",
+        "complex64",
+        "complex128",
+        "error",
+        "float32",
+        "float64",
+        "int",
+        "int8",
+        "int16",
+        "int32",
+        "int64",
+        "rune",
+        "string",
+        "uint",
+        "uint8",
+        "uint16",
+        "uint32",
+        "uint64",
+        "uintptr",
+        "any",
+    ],
+    constants: &["true", "false", "nil", "iota"],
+    ..PLAIN
+};
+
+const JS_STRINGS: &[StringRule] = &[
+    string("\"", "\"", false),
+    string("'", "'", false),
+    string("`", "`", true),
+];
+
+const JAVASCRIPT: SyntaxDef = SyntaxDef {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    strings: JS_STRINGS,
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "let",
+        "new",
+        "of",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "try",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    constants: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+    capitalized_types: true,
+    ident_chars: "$",
+    ..PLAIN
+};
+
+const TYPESCRIPT: SyntaxDef = SyntaxDef {
+    keywords: &[
+        "abstract",
+        "as",
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "declare",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "export",
+        "extends",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "implements",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "keyof",
+        "let",
+        "namespace",
+        "new",
+        "of",
+        "private",
+        "protected",
+        "public",
+        "readonly",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "try",
+        "type",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    types: &[
+        "any", "bigint", "boolean", "never", "number", "object", "string", "symbol", "unknown",
+    ],
+    decorator: Some('@'),
+    ..JAVASCRIPT
+};
+
+const PYTHON: SyntaxDef = SyntaxDef {
+    line_comments: &["#"],
+    strings: &[
+        string("\"\"\"", "\"\"\"", true),
+        string("'''", "'''", true),
+        string("\"", "\"", false),
+        string("'", "'", false),
+    ],
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+        "yield",
+    ],
+    types: &[
+        "bool", "bytes", "dict", "float", "int", "list", "object", "set", "str", "tuple",
+    ],
+    constants: &["True", "False", "None", "self"],
+    capitalized_types: true,
+    decorator: Some('@'),
+    ..PLAIN
+};
+
+const SHELL: SyntaxDef = SyntaxDef {
+    line_comments: &["#"],
+    word_comments: true,
+    strings: &[string("\"", "\"", true), raw_string("'", "'", true)],
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "readonly", "return", "select", "then", "until", "while",
+    ],
+    constants: &["true", "false"],
+    variable_prefix: Some('$'),
+    ..PLAIN
+};
+
+const LUA: SyntaxDef = SyntaxDef {
+    line_comments: &["--"],
+    block_comment: Some(("--[[", "]]")),
+    strings: &[
+        raw_string("[[", "]]", true),
+        string("\"", "\"", false),
+        string("'", "'", false),
+    ],
+    keywords: &[
+        "and", "break", "do", "else", "elseif", "end", "for", "function", "goto", "if", "in",
+        "local", "not", "or", "repeat", "return", "then", "until", "while",
+    ],
+    constants: &["true", "false", "nil", "self"],
+    ..PLAIN
+};
+
+const TOML: SyntaxDef = SyntaxDef {
+    line_comments: &["#"],
+    strings: &[
+        string("\"\"\"", "\"\"\"", true),
+        raw_string("'''", "'''", true),
+        string("\"", "\"", false),
+        raw_string("'", "'", false),
+    ],
+    constants: &["true", "false", "inf", "nan"],
+    ident_chars: "-",
+    key_suffix: Some('='),
+    section_headers: true,
+    ..PLAIN
+};
+
+const YAML: SyntaxDef = SyntaxDef {
+    line_comments: &["#"],
+    word_comments: true,
+    strings: &[string("\"", "\"", false), raw_string("'", "'", false)],
+    constants: &["true", "false", "null", "yes", "no", "on", "off"],
+    ident_chars: "-./",
+    key_suffix: Some(':'),
+    ..PLAIN
+};
+
+const JSON: SyntaxDef = SyntaxDef {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    strings: &[string("\"", "\"", false)],
+    constants: &["true", "false", "null"],
+    key_suffix: Some(':'),
+    keys_anywhere: true,
+    ..PLAIN
+};
+
+const HTML: SyntaxDef = SyntaxDef {
+    block_comment: Some(("<!--", "-->")),
+    strings: &[raw_string("\"", "\"", true), raw_string("'", "'", true)],
+    ident_chars: "-:",
+    key_suffix: Some('='),
+    keys_anywhere: true,
+    tags: true,
+    ..PLAIN
+};
+
+const MAKE: SyntaxDef = SyntaxDef {
+    line_comments: &["#"],
+    strings: &[string("\"", "\"", false), raw_string("'", "'", false)],
+    keywords: &[
+        "define", "endef", "ifeq", "ifneq", "ifdef", "ifndef", "else", "endif", "include",
+        "export", "override",
+    ],
+    ident_chars: "-.",
+    variable_prefix: Some('$'),
+    ..PLAIN
+};
+
+impl FileType {
+    pub fn syntax(&self) -> Option<&'static SyntaxDef> {
+        match self {
+            FileType::Text | FileType::Markdown => None,
+            FileType::Rust => Some(&RUST),
+            FileType::C => Some(&C),
+            FileType::Cpp => Some(&CPP),
+            FileType::Go => Some(&GO),
+            FileType::JavaScript => Some(&JAVASCRIPT),
+            FileType::TypeScript => Some(&TYPESCRIPT),
+            FileType::Python => Some(&PYTHON),
+            FileType::Shell => Some(&SHELL),
+            FileType::Lua => Some(&LUA),
+            FileType::Toml => Some(&TOML),
+            FileType::Yaml => Some(&YAML),
+            FileType::Json => Some(&JSON),
+            FileType::Html => Some(&HTML),
+            FileType::Make => Some(&MAKE),
+        }
+    }
+}
@@ -1,45 +1,160 @@
-use std::{fs::write, path::PathBuf};
+use std::path::PathBuf;
 
 use buf::CodeBuffer;
+use buffers::BufferList;
 use chrono::{DateTime, Utc};
 use crossterm::event::{KeyCode, KeyModifiers};
 use cursor::EditorCursor;
+use ex::NormalKeys;
+use explorer::Explorer;
+use file::absolute_path;
+use filetype::FileType;
+use grep::ReplacePlan;
+use history::History;
 use mode::EditorMode;
+use multicursor::Selection;
+use operator::{PendingOperator, TextRange};
+use options::EditorOptions;
+use picker::Picker;
+use quickfix::{ListView, QuickfixList};
+use register::Registers;
+use start::StartScreen;
+use syntax::Highlighter;
+use syntax_tree::SyntaxTree;
+use tabs::TabList;
+use theme::Theme;
 use utils::types::Vec2;
+use window::{Rect, WindowList};
 
+pub mod autopairs;
 pub mod buf;
+pub mod buffers;
+pub mod case;
+pub mod comment;
 pub mod cursor;
+pub mod edit;
+pub mod ex;
+pub mod explorer;
+pub mod file;
+pub mod filetype;
+pub mod filter;
+pub mod grep;
+pub mod history;
+pub mod indent;
+pub mod languages;
 pub mod mode;
+pub mod multicursor;
+pub mod number;
+pub mod operator;
+pub mod options;
+pub mod picker;
+pub mod quickfix;
+pub mod range;
+pub mod register;
+pub mod scroll;
+pub mod session;
+pub mod start;
+pub mod syntax;
+pub mod syntax_tree;
+pub mod tabs;
+pub mod theme;
+pub mod window;
+pub mod wrap;
 
 #[derive(Clone)]
 pub struct EditorState {
     pub buf: CodeBuffer,
+    saved_buf: CodeBuffer,
     mode: EditorMode,
     pub cursor: EditorCursor,
+    pub cursors: Vec<Selection>,
     pub offset: Vec2<usize>,
+    pub view_size: Vec2<usize>,
+    pub options: EditorOptions,
+    history: History,
+    highlighter: Highlighter,
+    syntax_tree: SyntaxTree,
     key_buf: Vec<(DateTime<Utc>, (KeyCode, KeyModifiers))>,
+    count: Option<usize>,
+    pub registers: Registers,
+    register: Option<char>,
+    pub selecting_register: bool,
     pub visual_start: Vec2<usize>,
+    pub visual_marks: Option<(Vec2<usize>, Vec2<usize>)>,
+    pub pending_op: Option<PendingOperator>,
     pub cmd_buf: String,
+    pub cmd_range: Option<TextRange>,
+    normal_keys: Option<NormalKeys>,
     path: Option<PathBuf>,
+    buffers: BufferList,
+    windows: WindowList,
+    tabs: TabList,
+    start_screen: Option<StartScreen>,
+    explorer: Explorer,
+    picker: Option<Picker>,
+    command_names: Vec<String>,
+    quickfix: QuickfixList,
+    list_views: Vec<ListView>,
+    replace_plan: Option<ReplacePlan>,
+    theme: Theme,
+    message: Option<String>,
     pub is_quit: bool,
 }
 
 impl EditorState {
     pub fn new(buf: String, path: Option<PathBuf>) -> Self {
+        let buf = CodeBuffer::new(buf);
+        let mut options = EditorOptions::default();
+        options.detect_indent(&buf);
+        if let Some(path) = &path {
+            options.set_filetype(FileType::detect(path));
+        }
+
         Self {
-            buf: CodeBuffer::new(buf),
+            saved_buf: buf.clone(),
+            buf,
             mode: EditorMode::default(),
             cursor: EditorCursor::default(),
+            cursors: Vec::new(),
             offset: Vec2::default(),
+            view_size: Vec2::new(80, 22),
+            options,
+            history: History::default(),
+            highlighter: Highlighter::default(),
+            syntax_tree: SyntaxTree::default(),
             key_buf: Vec::new(),
+            count: None,
+            registers: Registers::default(),
+            register: None,
+            selecting_register: false,
             visual_start: Vec2::default(),
+            visual_marks: None,
+            pending_op: None,
             cmd_buf: String::new(),
-            path,
+            cmd_range: None,
+            normal_keys: None,
+            path: path.map(|path| absolute_path(&path)),
+            buffers: BufferList::new(),
+            windows: WindowList::new(1, Rect::new(0, 0, 80, 23)),
+            tabs: TabList::new(Vec2::new(80, 23)),
+            start_screen: None,
+            explorer: Explorer::new(),
+            picker: None,
+            command_names: Vec::new(),
+            quickfix: QuickfixList::default(),
+            list_views: Vec::new(),
+            replace_plan: None,
+            theme: Theme::default(),
+            message: None,
             is_quit: false,
         }
     }
 
     pub fn set_mode(&mut self, mode: EditorMode) {
+        if self.mode == EditorMode::Visual && mode != EditorMode::Visual {
+            let (x, y) = self.cursor.get_display(&self.buf);
+            self.visual_marks = Some((self.visual_start, Vec2::new(x, y)));
+        }
         self.mode = mode;
     }
 
@@ -48,7 +163,7 @@ impl EditorState {
     }
 
     pub fn set_path(&mut self, path: PathBuf) {
-        self.path = Some(path);
+        self.path = Some(absolute_path(&path));
     }
 
     pub fn get_path(&self) -> Option<PathBuf> {
@@ -71,7 +186,7 @@ impl EditorState {
                 let duration = now.signed_duration_since(*time);
 
                 if duration.num_milliseconds() < 300 {
-                    Some(key.clone())
+                    Some(*key)
                 } else {
                     None
                 }
@@ -79,13 +194,45 @@ impl EditorState {
             .collect()
     }
 
-    pub fn write(&self) {
-        if let Some(path) = &self.get_path() {
-            write(path, self.buf.to_string()).unwrap();
-        }
+    pub fn resize(&mut self, term_w: usize, term_h: usize) {
+        self.set_screen_size(term_w, term_h.saturating_sub(1));
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    pub fn get_message(&self) -> Option<&String> {
+        self.message.as_ref()
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message = None;
+    }
+
+    pub fn push_count(&mut self, digit: usize) {
+        self.count = Some(self.count.unwrap_or_default() * 10 + digit);
+    }
+
+    pub fn has_count(&self) -> bool {
+        self.count.is_some()
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.count.unwrap_or(1).max(1)
+    }
+
+    pub fn clear_count(&mut self) {
+        self.count = None;
     }
 
     pub fn quit(&mut self) {
-        self.is_quit = true;
+        if self.window_count() > 1 {
+            let _ = self.close_window();
+        } else if self.tab_count() > 1 {
+            let _ = self.close_tab();
+        } else {
+            self.is_quit = true;
+        }
     }
 }
@@ -1,5 +1,7 @@
 use std::fmt::{self, Display, Formatter};
 
+use utils::{text::indent_len, types::Vec2};
+
 use crate::cmd::EditorCommand;
 
 #[derive(Default, Clone, Hash, Eq, PartialEq)]
@@ -13,24 +15,63 @@ pub enum EditorMode {
 
 impl EditorMode {
     pub fn register_cmds(cmds: &mut EditorCommand) {
-        cmds.register(
+        cmds.register_with_args(
             "normal",
-            Box::new(|editor| {
+            Box::new(|editor, keys| {
+                if !keys.is_empty() {
+                    editor.ex_normal(keys);
+                    return;
+                }
                 editor.cmd_buf.clear();
                 editor.set_mode(EditorMode::Normal);
             }),
         );
         cmds.register(
             "command",
-            Box::new(|editor| editor.set_mode(EditorMode::Command)),
+            Box::new(|editor| {
+                if editor.get_mode() == EditorMode::Visual {
+                    editor.cmd_buf = "'<,'>".to_string();
+                }
+                editor.set_mode(EditorMode::Command);
+            }),
         );
         cmds.register(
             "insert_before",
             Box::new(|editor| editor.set_mode(EditorMode::Insert)),
         );
+        cmds.register(
+            "insert_after",
+            Box::new(|editor| {
+                editor.cursor.move_by(&editor.buf, 1, 0);
+                editor.set_mode(EditorMode::Insert);
+            }),
+        );
+        cmds.register(
+            "insert_line_start",
+            Box::new(|editor| {
+                let (_, y) = editor.cursor.get_display(&editor.buf);
+                let x = indent_len(editor.buf.get_line(y));
+                editor.cursor.move_x_to(&editor.buf, x);
+                editor.set_mode(EditorMode::Insert);
+            }),
+        );
+        cmds.register(
+            "insert_line_end",
+            Box::new(|editor| {
+                let (_, y) = editor.cursor.get_display(&editor.buf);
+                editor
+                    .cursor
+                    .move_x_to(&editor.buf, editor.buf.line_length(y));
+                editor.set_mode(EditorMode::Insert);
+            }),
+        );
         cmds.register(
             "visual",
-            Box::new(|editor| editor.set_mode(EditorMode::Visual)),
+            Box::new(|editor| {
+                let (x, y) = editor.cursor.get_display(&editor.buf);
+                editor.visual_start = Vec2::new(x, y);
+                editor.set_mode(EditorMode::Visual);
+            }),
         );
     }
 }
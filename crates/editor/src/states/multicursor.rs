@@ -0,0 +1,413 @@
+use regex::Regex;
+use utils::types::Vec2;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{
+        buf::CodeBuffer, cursor::EditorCursor, mode::EditorMode, operator::PendingOperator,
+        operator::TextRange, EditorState,
+    },
+};
+
+pub const PRIMARY_ONLY_COMMANDS: &[&str] = &[
+    "command",
+    "undo",
+    "redo",
+    "window_top",
+    "window_middle",
+    "window_bottom",
+    "scroll_half_down",
+    "scroll_half_up",
+    "scroll_page_down",
+    "scroll_page_up",
+    "scroll_line_down",
+    "scroll_line_up",
+    "scroll_cursor_center",
+    "scroll_cursor_top",
+    "scroll_cursor_bottom",
+    "scroll_column_left",
+    "scroll_column_right",
+    "scroll_cursor_left",
+    "scroll_cursor_right",
+    "add_cursor_next_match",
+    "add_cursor_below",
+    "add_cursor_above",
+    "single_cursor",
+    "alternate_buffer",
+    "window_split",
+    "window_vsplit",
+    "window_close",
+    "window_only",
+    "window_left",
+    "window_down",
+    "window_up",
+    "window_right",
+    "window_next",
+    "window_equalize",
+    "window_increase_height",
+    "window_decrease_height",
+    "window_increase_width",
+    "window_decrease_width",
+    "next_tab",
+    "prev_tab",
+];
+
+#[derive(Clone)]
+pub struct Selection {
+    pub cursor: EditorCursor,
+    pub visual_start: Vec2<usize>,
+    pub pending_op: Option<PendingOperator>,
+}
+
+// Counted from the end so edits at earlier cursors don't move it
+#[derive(Clone, Copy)]
+struct Anchor {
+    lines_from_end: usize,
+    chars_from_end: usize,
+}
+
+impl Anchor {
+    fn new(buf: &CodeBuffer, pos: Vec2<usize>) -> Self {
+        let last = buf.line_count() - 1;
+        let y = pos.y.min(last);
+
+        Self {
+            lines_from_end: last - y,
+            chars_from_end: buf.line_length(y).saturating_sub(pos.x),
+        }
+    }
+
+    fn resolve(&self, buf: &CodeBuffer) -> Vec2<usize> {
+        let y = (buf.line_count() - 1).saturating_sub(self.lines_from_end);
+        Vec2::new(buf.line_length(y).saturating_sub(self.chars_from_end), y)
+    }
+}
+
+struct AnchoredSelection {
+    cursor: Anchor,
+    visual_start: Anchor,
+    pending_op: Option<(PendingOperator, Anchor)>,
+}
+
+impl AnchoredSelection {
+    fn new(buf: &CodeBuffer, selection: Selection) -> Self {
+        let (x, y) = selection.cursor.get_display(buf);
+
+        Self {
+            cursor: Anchor::new(buf, Vec2::new(x, y)),
+            visual_start: Anchor::new(buf, selection.visual_start),
+            pending_op: selection
+                .pending_op
+                .map(|op| (op.clone(), Anchor::new(buf, op.start))),
+        }
+    }
+
+    fn resolve(&self, buf: &CodeBuffer) -> Selection {
+        let pos = self.cursor.resolve(buf);
+
+        Selection {
+            cursor: EditorCursor::new(pos.x, pos.y),
+            visual_start: self.visual_start.resolve(buf),
+            pending_op: self.pending_op.as_ref().map(|(op, start)| PendingOperator {
+                start: start.resolve(buf),
+                ..op.clone()
+            }),
+        }
+    }
+}
+
+fn word_at(line: &str, x: usize) -> Option<(usize, usize)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    if !line[x..].chars().next().is_some_and(is_word) {
+        return None;
+    }
+
+    let start = line[..x]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(x, |(i, _)| i);
+    let end = line[x..]
+        .char_indices()
+        .find(|(_, c)| !is_word(*c))
+        .map_or(line.len(), |(i, _)| x + i);
+
+    Some((start, end))
+}
+
+impl EditorState {
+    fn take_selection(&mut self) -> Selection {
+        Selection {
+            cursor: std::mem::take(&mut self.cursor),
+            visual_start: self.visual_start,
+            pending_op: self.pending_op.take(),
+        }
+    }
+
+    fn put_selection(&mut self, selection: Selection) {
+        self.cursor = selection.cursor;
+        self.visual_start = selection.visual_start;
+        self.pending_op = selection.pending_op;
+    }
+
+    // Inside f, self.cursor is the cursor being run
+    pub fn for_each_cursor(&mut self, mut f: impl FnMut(&mut Self)) {
+        if self.cursors.is_empty() {
+            f(self);
+            return;
+        }
+
+        let primary = self.take_selection();
+        let mut selections: Vec<(bool, Selection)> = std::mem::take(&mut self.cursors)
+            .into_iter()
+            .map(|selection| (false, selection))
+            .collect();
+        selections.push((true, primary));
+        selections.sort_by_key(|(_, selection)| {
+            let (x, y) = selection.cursor.get_display(&self.buf);
+            (y, x)
+        });
+
+        let anchored: Vec<(bool, AnchoredSelection)> = selections
+            .into_iter()
+            .map(|(primary, selection)| (primary, AnchoredSelection::new(&self.buf, selection)))
+            .collect();
+
+        let mut done: Vec<(bool, Selection)> = Vec::new();
+        for (primary, selection) in anchored {
+            self.put_selection(selection.resolve(&self.buf));
+            f(self);
+            done.push((primary, self.take_selection()));
+        }
+
+        let mut positions = Vec::new();
+        for (primary, selection) in done {
+            if primary {
+                self.put_selection(selection);
+            } else {
+                let pos = selection.cursor.get_display(&self.buf);
+                if !positions.contains(&pos) {
+                    positions.push(pos);
+                    self.cursors.push(selection);
+                }
+            }
+        }
+        let primary_pos = self.cursor.get_display(&self.buf);
+        self.cursors
+            .retain(|selection| selection.cursor.get_display(&self.buf) != primary_pos);
+    }
+
+    fn push_cursor(&mut self, cursor: EditorCursor, visual_start: Vec2<usize>) {
+        let selection = self.take_selection();
+        self.cursors.push(selection);
+        self.put_selection(Selection {
+            cursor,
+            visual_start,
+            pending_op: None,
+        });
+    }
+
+    fn has_cursor_at(&self, x: usize, y: usize) -> bool {
+        self.cursor.get_display(&self.buf) == (x, y)
+            || self
+                .cursors
+                .iter()
+                .any(|selection| selection.cursor.get_display(&self.buf) == (x, y))
+    }
+
+    fn add_cursor_next_match(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let line = self.buf.get_line(cursor_y);
+        let visual = self.get_mode() == EditorMode::Visual;
+
+        let (pattern, from) = if visual {
+            let range =
+                TextRange::from_visual(&self.buf, self.visual_start, Vec2::new(cursor_x, cursor_y));
+            if range.start.y != range.end.y {
+                self.set_message("Selection must be within a line".to_string());
+                return;
+            }
+            let end = range.end.x.min(line.len());
+            (regex::escape(&line[range.start.x..end]), (end, cursor_y))
+        } else {
+            let Some((start, end)) = word_at(line, cursor_x) else {
+                self.set_message("No word under cursor".to_string());
+                return;
+            };
+            (
+                format!(r"\b{}\b", regex::escape(&line[start..end])),
+                (end, cursor_y),
+            )
+        };
+        let Ok(re) = Regex::new(&pattern) else {
+            return;
+        };
+
+        let line_count = self.buf.line_count();
+        for i in 0..=line_count {
+            let y = (from.1 + i) % line_count;
+            let line = self.buf.get_line(y);
+            let start = if i == 0 { from.0 } else { 0 };
+
+            for m in re.find_iter(&line[start..]) {
+                let match_start = start + m.start();
+                if m.as_str().is_empty() || self.has_cursor_at(match_start, y) {
+                    continue;
+                }
+
+                let (x, visual_start) = if visual {
+                    let last = m.as_str().char_indices().last().map_or(0, |(i, _)| i);
+                    (match_start + last, Vec2::new(match_start, y))
+                } else {
+                    (match_start, Vec2::new(match_start, y))
+                };
+                self.push_cursor(EditorCursor::new(x, y), visual_start);
+                return;
+            }
+        }
+
+        self.set_message(format!("Pattern not found: {}", pattern));
+    }
+
+    fn add_cursor_vertical(&mut self, down: bool) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        if (down && cursor_y + 1 >= self.buf.line_count()) || (!down && cursor_y == 0) {
+            return;
+        }
+
+        let mut cursor = self.cursor.clone();
+        cursor.move_y_keep_col(&self.buf, if down { 1 } else { -1 }, self.options.tabstop);
+        let visual_start = self.visual_start;
+        self.push_cursor(cursor, visual_start);
+    }
+
+    fn add_cursors_on_matches(&mut self, pattern: &str) {
+        let re = match Regex::new(pattern) {
+            Ok(re) => re,
+            Err(err) => {
+                self.set_message(err.to_string());
+                return;
+            }
+        };
+        let last = self.buf.line_count() - 1;
+        let range = self.cmd_range.unwrap_or(TextRange::lines(0, last));
+
+        let mut matches = Vec::new();
+        for y in range.start.y..=range.end.y.min(last) {
+            let line = self.buf.get_line(y);
+            let start = if range.linewise || y > range.start.y {
+                0
+            } else {
+                range.start.x
+            };
+            let end = if range.linewise || y < range.end.y {
+                line.len()
+            } else {
+                range.end.x.min(line.len())
+            };
+
+            for m in re.find_iter(&line[start.min(end)..end]) {
+                matches.push((start + m.start(), y));
+            }
+        }
+
+        let Some(&(x, y)) = matches.first() else {
+            self.set_message(format!("Pattern not found: {}", pattern));
+            return;
+        };
+
+        self.cursors = matches[1..]
+            .iter()
+            .map(|&(x, y)| Selection {
+                cursor: EditorCursor::new(x, y),
+                visual_start: Vec2::new(x, y),
+                pending_op: None,
+            })
+            .collect();
+        self.cursor = EditorCursor::new(x, y);
+        self.set_message(format!("{} cursors", matches.len()));
+    }
+
+    pub fn extra_cursor_screen_positions(&self) -> Vec<(usize, usize)> {
+        let last_visible = self.last_visible_line();
+
+        self.cursors
+            .iter()
+            .filter_map(|selection| {
+                let (x, y) = selection.cursor.get_display(&self.buf);
+                if y < self.offset.y || y > last_visible {
+                    return None;
+                }
+                let (col, row) = self.screen_pos(x, y)?;
+                (row < self.view_height() && col < self.view_width()).then_some((col, row))
+            })
+            .collect()
+    }
+
+    pub fn register_multicursor_cmds(cmds: &mut EditorCommand) {
+        cmds.register(
+            "add_cursor_next_match",
+            Box::new(|editor| editor.add_cursor_next_match()),
+        );
+        cmds.register(
+            "add_cursor_below",
+            Box::new(|editor| editor.add_cursor_vertical(true)),
+        );
+        cmds.register(
+            "add_cursor_above",
+            Box::new(|editor| editor.add_cursor_vertical(false)),
+        );
+        cmds.register("single_cursor", Box::new(|editor| editor.cursors.clear()));
+        cmds.register_with_args(
+            "cursors",
+            Box::new(|editor, args| editor.add_cursors_on_matches(args)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_with_cursors() {
+        let mut state = EditorState::new("ab ab\nab".to_string(), None);
+        state.add_cursor_next_match();
+        state.add_cursor_next_match();
+        assert_eq!(state.cursors.len(), 2);
+
+        state.for_each_cursor(|state| {
+            let (x, y) = state.cursor.get_display(&state.buf);
+            state.buf.insert_str("x_", x, y);
+            state.cursor.move_by(&state.buf, 2, 0);
+        });
+        assert_eq!(state.buf.to_string(), "x_ab x_ab\nx_ab");
+        assert_eq!(state.cursor.get_display(&state.buf), (2, 1));
+    }
+
+    #[test]
+    fn test_add_cursor_next_match_multibyte() {
+        let mut state = EditorState::new("éb éb".to_string(), None);
+        state.set_mode(EditorMode::Visual);
+
+        state.add_cursor_next_match();
+        assert_eq!(state.cursors.len(), 1);
+        assert_eq!(state.cursor.get_display(&state.buf), (4, 0));
+        assert_eq!(state.visual_start, Vec2::new(4, 0));
+    }
+
+    #[test]
+    fn test_cursors_on_matches() {
+        let mut state = EditorState::new("a1 b2\nc3".to_string(), None);
+        state.add_cursors_on_matches(r"\d");
+        assert_eq!(state.cursor.get_display(&state.buf), (1, 0));
+        assert_eq!(state.cursors.len(), 2);
+
+        state.for_each_cursor(|state| {
+            let (x, y) = state.cursor.get_display(&state.buf);
+            state.buf.delete(x, y);
+        });
+        assert_eq!(state.buf.to_string(), "a b\nc");
+    }
+}
@@ -0,0 +1,245 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use utils::types::Vec2;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{mode::EditorMode, operator::TextRange, EditorState},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NumberKind {
+    Decimal,
+    Hex,
+    Binary,
+    Octal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct NumberMatch {
+    start: usize,
+    end: usize,
+    kind: NumberKind,
+}
+
+static NUMBER_RES: LazyLock<[Regex; 4]> = LazyLock::new(|| {
+    [0, 1, 2, 3].map(|index| {
+        let mut patterns = Vec::new();
+        if index & 1 != 0 {
+            patterns.push("0[xX][0-9a-fA-F]+");
+        }
+        if index & 2 != 0 {
+            patterns.push("0[bB][01]+");
+        }
+        patterns.push(r"-?\d+");
+        Regex::new(&patterns.join("|")).unwrap()
+    })
+});
+
+fn find_number(line: &str, from: usize, nrformats: &str) -> Option<NumberMatch> {
+    let formats: Vec<&str> = nrformats.split(',').collect();
+    let hex = formats.contains(&"hex") as usize;
+    let bin = formats.contains(&"bin") as usize;
+    let re = &NUMBER_RES[hex | bin << 1];
+
+    let m = re.find_iter(line).find(|m| m.end() > from)?;
+    let text = m.as_str();
+    let kind = if text.len() > 2 && text[..2].eq_ignore_ascii_case("0x") {
+        NumberKind::Hex
+    } else if text.len() > 2 && text[..2].eq_ignore_ascii_case("0b") {
+        NumberKind::Binary
+    } else if formats.contains(&"octal")
+        && text.len() > 1
+        && text.starts_with('0')
+        && text.chars().all(|c| ('0'..='7').contains(&c))
+    {
+        NumberKind::Octal
+    } else {
+        NumberKind::Decimal
+    };
+
+    Some(NumberMatch {
+        start: m.start(),
+        end: m.end(),
+        kind,
+    })
+}
+
+fn add_to_number(text: &str, kind: NumberKind, delta: i128) -> Option<String> {
+    match kind {
+        NumberKind::Decimal => {
+            let (negative, digits) = match text.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, text),
+            };
+            let value = digits.parse::<i128>().ok()?;
+            let value = if negative { -value } else { value }.checked_add(delta)?;
+
+            let width = if digits.len() > 1 && digits.starts_with('0') {
+                digits.len()
+            } else {
+                0
+            };
+            let sign = if value < 0 { "-" } else { "" };
+            Some(format!(
+                "{}{:0width$}",
+                sign,
+                value.unsigned_abs(),
+                width = width
+            ))
+        }
+        NumberKind::Hex | NumberKind::Binary => {
+            let (prefix, digits) = text.split_at(2);
+            let radix = if kind == NumberKind::Hex { 16 } else { 2 };
+            let value = u64::from_str_radix(digits, radix).ok()?;
+            let value = (value as i128 + delta).rem_euclid(1 << 64) as u64;
+            let width = digits.len();
+
+            let digits = match kind {
+                NumberKind::Hex => {
+                    let upper = digits
+                        .chars()
+                        .rev()
+                        .find(|c| c.is_ascii_alphabetic())
+                        .is_some_and(|c| c.is_ascii_uppercase());
+                    if upper {
+                        format!("{:0width$X}", value, width = width)
+                    } else {
+                        format!("{:0width$x}", value, width = width)
+                    }
+                }
+                _ => format!("{:0width$b}", value, width = width),
+            };
+            Some(prefix.to_string() + &digits)
+        }
+        NumberKind::Octal => {
+            let value = u64::from_str_radix(text, 8).ok()?;
+            let value = (value as i128 + delta).rem_euclid(1 << 64) as u64;
+            Some(format!("0{:0width$o}", value, width = text.len() - 1))
+        }
+    }
+}
+
+impl EditorState {
+    fn increment_number_at(&mut self, y: usize, from: usize, delta: i128) -> Option<usize> {
+        let line = self.buf.get_line(y);
+        let number = find_number(line, from, &self.options.nrformats)?;
+        let new_number = add_to_number(&line[number.start..number.end], number.kind, delta)?;
+
+        let new_line = line[..number.start].to_string() + &new_number + &line[number.end..];
+        self.buf.set_line(y, new_line);
+
+        Some(number.start + new_number.len() - 1)
+    }
+
+    fn cmd_increment(&mut self, sign: i128) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let delta = sign * self.get_count() as i128;
+
+        if let Some(x) = self.increment_number_at(cursor_y, cursor_x, delta) {
+            self.cursor.move_x_to(&self.buf, x);
+        }
+    }
+
+    fn cmd_visual_increment(&mut self, sign: i128, progressive: bool) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let range =
+            TextRange::from_visual(&self.buf, self.visual_start, Vec2::new(cursor_x, cursor_y));
+        let step = sign * self.get_count() as i128;
+        let mut delta = step;
+
+        for y in range.start.y..=range.end.y {
+            let from = if range.linewise || y > range.start.y {
+                0
+            } else {
+                range.start.x
+            };
+            if self.increment_number_at(y, from, delta).is_some() && progressive {
+                delta += step;
+            }
+        }
+
+        self.set_mode(EditorMode::Normal);
+        self.cursor.move_y_to(&self.buf, range.start.y);
+        self.cursor.move_x_to(&self.buf, range.start.x);
+    }
+
+    pub fn register_number_cmds(cmds: &mut EditorCommand) {
+        cmds.register("increment", Box::new(|editor| editor.cmd_increment(1)));
+        cmds.register("decrement", Box::new(|editor| editor.cmd_increment(-1)));
+        cmds.register(
+            "visual_increment",
+            Box::new(|editor| editor.cmd_visual_increment(1, false)),
+        );
+        cmds.register(
+            "visual_decrement",
+            Box::new(|editor| editor.cmd_visual_increment(-1, false)),
+        );
+        cmds.register(
+            "visual_increment_sequence",
+            Box::new(|editor| editor.cmd_visual_increment(1, true)),
+        );
+        cmds.register(
+            "visual_decrement_sequence",
+            Box::new(|editor| editor.cmd_visual_increment(-1, true)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_to_number() {
+        assert_eq!(add_to_number("9", NumberKind::Decimal, 1).unwrap(), "10");
+        assert_eq!(add_to_number("-1", NumberKind::Decimal, 3).unwrap(), "2");
+        assert_eq!(add_to_number("1", NumberKind::Decimal, -3).unwrap(), "-2");
+        assert_eq!(add_to_number("007", NumberKind::Decimal, 3).unwrap(), "010");
+        assert_eq!(add_to_number("0xff", NumberKind::Hex, 1).unwrap(), "0x100");
+        assert_eq!(add_to_number("0x0A", NumberKind::Hex, 1).unwrap(), "0x0B");
+        assert_eq!(
+            add_to_number("0x00", NumberKind::Hex, -1).unwrap(),
+            "0xffffffffffffffff"
+        );
+        assert_eq!(
+            add_to_number("0b011", NumberKind::Binary, 1).unwrap(),
+            "0b100"
+        );
+        assert_eq!(add_to_number("007", NumberKind::Octal, 1).unwrap(), "010");
+
+        let huge = "9".repeat(40);
+        assert_eq!(add_to_number(&huge, NumberKind::Decimal, 1), None);
+        assert_eq!(
+            add_to_number("0x1ffffffffffffffff", NumberKind::Hex, 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_number() {
+        let number =
+            |line, from| find_number(line, from, "bin,hex").map(|m| (m.start, m.end, m.kind));
+
+        assert_eq!(number("x = 0x1f;", 0), Some((4, 8, NumberKind::Hex)));
+        assert_eq!(number("a-1 b2", 3), Some((5, 6, NumberKind::Decimal)));
+        assert_eq!(number("a-1 b2", 0), Some((1, 3, NumberKind::Decimal)));
+        assert_eq!(number("abc", 0), None);
+        assert_eq!(
+            find_number("017", 0, "octal").map(|m| m.kind),
+            Some(NumberKind::Octal)
+        );
+    }
+
+    #[test]
+    fn test_increment_sequence() {
+        let mut state = EditorState::new("x0\n\nx0\nx0".to_string(), None);
+        state.visual_start = Vec2::new(0, 0);
+        state.set_mode(EditorMode::Visual);
+        state.cursor.move_y_to(&state.buf, 3);
+
+        state.cmd_visual_increment(1, true);
+        assert_eq!(state.buf.to_string(), "x1\n\nx2\nx3");
+    }
+}
@@ -0,0 +1,168 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+use utils::types::Vec2;
+
+use crate::states::{buf::CodeBuffer, mode::EditorMode, EditorState};
+
+pub const MOTIONS: &[&str] = &[
+    "left",
+    "right",
+    "up",
+    "down",
+    "top",
+    "bottom",
+    "line_start",
+    "line_end",
+    "match_paren",
+    "window_top",
+    "window_middle",
+    "window_bottom",
+    "screen_down",
+    "screen_up",
+    "screen_line_start",
+    "screen_line_end",
+    "scroll_half_down",
+    "scroll_half_up",
+    "scroll_page_down",
+    "scroll_page_up",
+    "next_function",
+    "prev_function",
+    "select_function_outer",
+    "select_function_inner",
+    "select_class_outer",
+    "select_class_inner",
+    "select_argument_outer",
+    "select_argument_inner",
+    "expand_selection",
+    "shrink_selection",
+];
+
+pub const LINEWISE_MOTIONS: &[&str] = &[
+    "up",
+    "down",
+    "top",
+    "bottom",
+    "window_top",
+    "window_middle",
+    "window_bottom",
+    "scroll_half_down",
+    "scroll_half_up",
+    "scroll_page_down",
+    "scroll_page_up",
+];
+
+pub const INCLUSIVE_MOTIONS: &[&str] = &[
+    "line_end",
+    "match_paren",
+    "select_function_outer",
+    "select_function_inner",
+    "select_class_outer",
+    "select_class_inner",
+    "select_argument_outer",
+    "select_argument_inner",
+    "expand_selection",
+    "shrink_selection",
+];
+
+#[derive(Clone)]
+pub struct PendingOperator {
+    pub name: String,
+    pub start: Vec2<usize>,
+    pub visual: bool,
+    pub key: Option<(KeyCode, KeyModifiers)>,
+}
+
+// end is exclusive for charwise ranges
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextRange {
+    pub start: Vec2<usize>,
+    pub end: Vec2<usize>,
+    pub linewise: bool,
+}
+
+impl TextRange {
+    pub fn lines(start: usize, end: usize) -> Self {
+        Self {
+            start: Vec2::new(0, start.min(end)),
+            end: Vec2::new(0, start.max(end)),
+            linewise: true,
+        }
+    }
+
+    pub fn chars(start: Vec2<usize>, end: Vec2<usize>) -> Self {
+        Self {
+            start,
+            end,
+            linewise: false,
+        }
+    }
+
+    pub fn from_motion(
+        buf: &CodeBuffer,
+        start: Vec2<usize>,
+        end: Vec2<usize>,
+        motion: &str,
+    ) -> Self {
+        let (start, mut end) = if (start.y, start.x) <= (end.y, end.x) {
+            (start, end)
+        } else {
+            (end, start)
+        };
+
+        if LINEWISE_MOTIONS.contains(&motion) {
+            return Self::lines(start.y, end.y);
+        }
+        if INCLUSIVE_MOTIONS.contains(&motion) {
+            let line = buf.get_line(end.y);
+            end.x += line
+                .get(end.x..)
+                .and_then(|rest| rest.chars().next())
+                .map_or(1, char::len_utf8);
+        }
+
+        Self {
+            start,
+            end,
+            linewise: false,
+        }
+    }
+
+    pub fn from_visual(buf: &CodeBuffer, start: Vec2<usize>, end: Vec2<usize>) -> Self {
+        Self::from_motion(buf, start, end, "line_end")
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        if self.linewise {
+            return (self.start.y..=self.end.y).contains(&y);
+        }
+        (self.start.y, self.start.x) <= (y, x) && (y, x) < (self.end.y, self.end.x)
+    }
+}
+
+impl EditorState {
+    pub fn start_operator(&mut self, name: &str) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let visual = self.get_mode() == EditorMode::Visual;
+        let start = if visual {
+            self.visual_start
+        } else {
+            Vec2::new(cursor_x, cursor_y)
+        };
+
+        self.pending_op = Some(PendingOperator {
+            name: name.to_string(),
+            start,
+            visual,
+            key: None,
+        });
+    }
+
+    pub fn finish_operator(&mut self, range: TextRange) {
+        self.pending_op = None;
+        if self.get_mode() == EditorMode::Visual {
+            self.set_mode(EditorMode::Normal);
+        }
+
+        self.cursor.move_y_to(&self.buf, range.start.y);
+        self.cursor.move_x_to(&self.buf, range.start.x);
+    }
+}
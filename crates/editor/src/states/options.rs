@@ -0,0 +1,192 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    cmd::EditorCommand,
+    states::{filetype::FileType, quickfix::parse_errorformat},
+};
+
+#[derive(Clone)]
+pub struct EditorOptions {
+    pub scrolloff: usize,
+    pub sidescroll: usize,
+    pub sidescrolloff: usize,
+    pub wrap: bool,
+    pub linebreak: bool,
+    pub showbreak: String,
+    pub breakindent: bool,
+    pub tabstop: usize,
+    pub shiftwidth: usize,
+    pub expandtab: bool,
+    pub softtabstop: usize,
+    pub autoindent: bool,
+    pub smartindent: bool,
+    pub filetype: FileType,
+    pub autopairs: bool,
+    pub pairs: String,
+    pub commentstring: String,
+    pub nrformats: String,
+    pub shell: String,
+    pub makeprg: String,
+    pub errorformat: String,
+    pub cursorline: bool,
+    pub undolevels: usize,
+}
+
+impl Default for EditorOptions {
+    fn default() -> Self {
+        Self {
+            scrolloff: 0,
+            sidescroll: 0,
+            sidescrolloff: 0,
+            wrap: false,
+            linebreak: false,
+            showbreak: String::new(),
+            breakindent: false,
+            tabstop: 8,
+            shiftwidth: 2,
+            expandtab: true,
+            softtabstop: 0,
+            autoindent: true,
+            smartindent: true,
+            filetype: FileType::default(),
+            autopairs: true,
+            pairs: FileType::default().pairs().to_string(),
+            commentstring: FileType::default().comment_string().to_string(),
+            nrformats: "bin,hex".to_string(),
+            shell: "sh".to_string(),
+            makeprg: "cargo build".to_string(),
+            errorformat: "rust,python,gcc".to_string(),
+            cursorline: false,
+            undolevels: 1000,
+        }
+    }
+}
+
+impl EditorOptions {
+    pub fn set(&mut self, arg: &str) -> Result<()> {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg, None),
+        };
+
+        match (name, value) {
+            ("scrolloff" | "so", Some(value)) => self.scrolloff = parse_number(name, value)?,
+            ("sidescroll" | "ss", Some(value)) => self.sidescroll = parse_number(name, value)?,
+            ("sidescrolloff" | "siso", Some(value)) => {
+                self.sidescrolloff = parse_number(name, value)?
+            }
+            ("showbreak" | "sbr", Some(value)) => self.showbreak = value.to_string(),
+            ("tabstop" | "ts", Some(value)) => {
+                self.tabstop = parse_number(name, value)?.max(1);
+            }
+            ("shiftwidth" | "sw", Some(value)) => self.shiftwidth = parse_number(name, value)?,
+            ("softtabstop" | "sts", Some(value)) => self.softtabstop = parse_number(name, value)?,
+            ("undolevels" | "ul", Some(value)) => self.undolevels = parse_number(name, value)?,
+            ("filetype" | "ft", Some(value)) => {
+                let filetype = FileType::from_name(value)
+                    .ok_or_else(|| anyhow!("Unknown filetype: {}", value))?;
+                self.set_filetype(filetype);
+            }
+            ("pairs", Some(value)) => {
+                if value.chars().count() % 2 != 0 {
+                    return Err(anyhow!(
+                        "Pairs must be given two characters each: {}",
+                        value
+                    ));
+                }
+                self.pairs = value.to_string();
+            }
+            ("commentstring" | "cms", Some(value)) => {
+                if !value.contains("%s") {
+                    return Err(anyhow!("Commentstring must contain %s: {}", value));
+                }
+                self.commentstring = value.to_string();
+            }
+            ("nrformats" | "nf", Some(value)) => {
+                if let Some(format) = value
+                    .split(',')
+                    .find(|format| !["bin", "octal", "hex", ""].contains(format))
+                {
+                    return Err(anyhow!("Unknown nrformats: {}", format));
+                }
+                self.nrformats = value.to_string();
+            }
+            ("shell" | "sh", Some(value)) => self.shell = value.to_string(),
+            ("makeprg" | "mp", Some(value)) => self.makeprg = value.to_string(),
+            ("errorformat" | "efm", Some(value)) => {
+                parse_errorformat(value)?;
+                self.errorformat = value.to_string();
+            }
+            (name, None) => self.set_bool(name)?,
+            _ => return Err(anyhow!("Unknown option: {}", arg)),
+        }
+
+        Ok(())
+    }
+
+    fn set_bool(&mut self, name: &str) -> Result<()> {
+        let (name, value) = match name.strip_prefix("no") {
+            Some(name) => (name, false),
+            None => (name, true),
+        };
+
+        let option = match name {
+            "wrap" => &mut self.wrap,
+            "linebreak" | "lbr" => &mut self.linebreak,
+            "breakindent" | "bri" => &mut self.breakindent,
+            "expandtab" | "et" => &mut self.expandtab,
+            "autoindent" | "ai" => &mut self.autoindent,
+            "smartindent" | "si" => &mut self.smartindent,
+            "autopairs" | "ap" => &mut self.autopairs,
+            "cursorline" | "cul" => &mut self.cursorline,
+            _ => return Err(anyhow!("Unknown option: {}", name)),
+        };
+        *option = value;
+
+        Ok(())
+    }
+
+    pub fn set_filetype(&mut self, filetype: FileType) {
+        self.filetype = filetype;
+        self.pairs = filetype.pairs().to_string();
+        self.commentstring = filetype.comment_string().to_string();
+    }
+
+    pub fn shift_width(&self) -> usize {
+        if self.shiftwidth == 0 {
+            self.tabstop
+        } else {
+            self.shiftwidth
+        }
+    }
+
+    pub fn soft_tab_width(&self) -> usize {
+        if self.softtabstop > 0 {
+            self.softtabstop
+        } else if self.expandtab {
+            self.shift_width()
+        } else {
+            0
+        }
+    }
+
+    pub fn register_cmds(cmds: &mut EditorCommand) {
+        cmds.register_with_args(
+            "set",
+            Box::new(|editor, args| {
+                for arg in args.split_whitespace() {
+                    if let Err(err) = editor.options.set(arg) {
+                        editor.set_message(err.to_string());
+                        break;
+                    }
+                }
+            }),
+        );
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<usize> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("Number required after =: {}={}", name, value))
+}
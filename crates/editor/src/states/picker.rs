@@ -0,0 +1,537 @@
+use std::{
+    cmp::Reverse,
+    env,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyModifiers};
+use ignore::WalkBuilder;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{buffers::display_name, start::load_recent_files, window::Rect, EditorState},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+enum PickerTarget {
+    File(PathBuf),
+    Buffer(usize),
+    Line(usize),
+    Command(String),
+}
+
+#[derive(Clone, Debug)]
+struct PickerItem {
+    label: String,
+    target: PickerTarget,
+}
+
+#[derive(Clone)]
+struct PickerMatch {
+    index: usize,
+    positions: Vec<usize>,
+}
+
+#[derive(Clone)]
+pub struct Picker {
+    title: &'static str,
+    items: Vec<PickerItem>,
+    query: String,
+    matches: Vec<PickerMatch>,
+    selected: usize,
+    offset: usize,
+    preview: Option<(usize, usize, Vec<String>)>,
+}
+
+impl Picker {
+    fn new(title: &'static str, items: Vec<PickerItem>) -> Self {
+        let mut picker = Self {
+            title,
+            items,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            offset: 0,
+            preview: None,
+        };
+        picker.update_matches();
+        picker
+    }
+
+    fn update_matches(&mut self) {
+        let mut scored: Vec<(i64, PickerMatch)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let (score, positions) = fuzzy_match(&self.query, &item.label)?;
+                Some((score, PickerMatch { index, positions }))
+            })
+            .collect();
+        scored
+            .sort_by_key(|(score, m)| (Reverse(*score), self.items[m.index].label.len(), m.index));
+
+        self.matches = scored.into_iter().map(|(_, m)| m).collect();
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    fn selected_item(&self) -> Option<&PickerItem> {
+        let m = self.matches.get(self.selected)?;
+        Some(&self.items[m.index])
+    }
+
+    fn move_selection(&mut self, delta: isize, height: usize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let len = self.matches.len() as isize;
+        self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+
+        let height = height.max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+    }
+}
+
+fn is_word_start(text: &[char], i: usize) -> bool {
+    if i == 0 {
+        return true;
+    }
+    let prev = text[i - 1];
+    matches!(prev, '/' | '\\' | '_' | '-' | '.' | ' ' | ':')
+        || (prev.is_lowercase() && text[i].is_uppercase())
+}
+
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let normalize = |c: char| {
+        if ignore_case {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            c
+        }
+    };
+    let query: Vec<char> = query.chars().map(normalize).collect();
+    let text: Vec<char> = text.chars().collect();
+    let normalized: Vec<char> = text.iter().map(|c| normalize(*c)).collect();
+
+    // Search forward for the end, then backward for the shortest match
+    let mut qi = 0;
+    let mut end = None;
+    for (i, c) in normalized.iter().enumerate() {
+        if *c == query[qi] {
+            qi += 1;
+            if qi == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    let mut qi = query.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if normalized[i] == query[qi - 1] {
+            qi -= 1;
+            if qi == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::new();
+    for (i, c) in normalized.iter().enumerate().take(end + 1).skip(start) {
+        if positions.len() < query.len() && *c == query[positions.len()] {
+            positions.push(i);
+        }
+    }
+
+    let mut score = 0;
+    for (n, &i) in positions.iter().enumerate() {
+        score += 16;
+        if is_word_start(&text, i) {
+            score += 8;
+        }
+        if n > 0 {
+            let gap = i - positions[n - 1] - 1;
+            if gap == 0 {
+                score += 8;
+            }
+            score -= gap as i64;
+        }
+    }
+    Some((score, positions))
+}
+
+fn project_files() -> Result<Vec<PathBuf>> {
+    let cwd = env::current_dir()?;
+    let mut files: Vec<PathBuf> = WalkBuilder::new(&cwd)
+        .require_git(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn file_items(paths: Vec<PathBuf>) -> Vec<PickerItem> {
+    paths
+        .into_iter()
+        .map(|path| PickerItem {
+            label: display_name(Some(&path)),
+            target: PickerTarget::File(path),
+        })
+        .collect()
+}
+
+fn preview_line(line: &str) -> String {
+    line.replace('\t', "    ")
+}
+
+fn read_preview(path: &PathBuf, height: usize) -> Vec<String> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .take(height)
+        .map_while(Result::ok)
+        .map(|line| preview_line(&line))
+        .collect()
+}
+
+impl EditorState {
+    pub fn is_picker_open(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    pub fn set_command_names(&mut self, names: Vec<String>) {
+        self.command_names = names;
+    }
+
+    fn open_picker(&mut self, title: &'static str, items: Vec<PickerItem>) {
+        self.cancel_change();
+        self.picker = Some(Picker::new(title, items));
+    }
+
+    fn pick_files(&mut self) -> Result<()> {
+        let items = file_items(project_files()?);
+        self.open_picker("Files", items);
+        Ok(())
+    }
+
+    fn pick_buffers(&mut self) {
+        let items = self
+            .buffer_ids()
+            .into_iter()
+            .map(|id| PickerItem {
+                label: format!("{} {}", id, self.buffer_label(id)),
+                target: PickerTarget::Buffer(id),
+            })
+            .collect();
+        self.open_picker("Buffers", items);
+    }
+
+    fn pick_lines(&mut self) {
+        let items = (0..self.buf.line_count())
+            .map(|y| PickerItem {
+                label: format!("{}: {}", y + 1, self.buf.get_line(y).trim_end()),
+                target: PickerTarget::Line(y),
+            })
+            .collect();
+        self.open_picker("Lines", items);
+    }
+
+    fn pick_recent_files(&mut self) {
+        let recent = load_recent_files()
+            .into_iter()
+            .filter(|path| path.exists())
+            .collect();
+        self.open_picker("Recent", file_items(recent));
+    }
+
+    fn pick_commands(&mut self) {
+        let items = self
+            .command_names
+            .iter()
+            .map(|name| PickerItem {
+                label: name.clone(),
+                target: PickerTarget::Command(name.clone()),
+            })
+            .collect();
+        self.open_picker("Commands", items);
+    }
+
+    pub fn picker_rect(&self) -> Rect {
+        let screen = self.tabs_screen_size();
+        let w = (screen.x * 4 / 5).max(screen.x.min(20));
+        let h = (screen.y * 4 / 5).max(screen.y.min(5));
+        Rect::new((screen.x - w) / 2, (screen.y - h) / 2, w, h)
+    }
+
+    fn picker_list_height(&self) -> usize {
+        self.picker_rect().h.saturating_sub(2)
+    }
+
+    pub fn picker_prompt(&self) -> String {
+        let Some(picker) = &self.picker else {
+            return String::new();
+        };
+        format!(
+            "{} {}/{}> {}",
+            picker.title,
+            picker.matches.len(),
+            picker.items.len(),
+            picker.query
+        )
+    }
+
+    pub fn picker_rows(&self) -> Vec<(String, Vec<usize>, bool)> {
+        let Some(picker) = &self.picker else {
+            return Vec::new();
+        };
+        picker
+            .matches
+            .iter()
+            .enumerate()
+            .skip(picker.offset)
+            .take(self.picker_list_height())
+            .map(|(i, m)| {
+                let label = picker.items[m.index].label.clone();
+                (label, m.positions.clone(), i == picker.selected)
+            })
+            .collect()
+    }
+
+    pub fn picker_preview(&mut self) -> Vec<String> {
+        let height = self.picker_list_height();
+        let Some(picker) = self.picker.as_mut() else {
+            return Vec::new();
+        };
+        let Some(index) = picker.matches.get(picker.selected).map(|m| m.index) else {
+            return Vec::new();
+        };
+        match &picker.items[index].target {
+            PickerTarget::File(path) => match &picker.preview {
+                Some((cached, lines, preview)) if *cached == index && *lines == height => {
+                    preview.clone()
+                }
+                _ => {
+                    let preview = read_preview(path, height);
+                    picker.preview = Some((index, height, preview.clone()));
+                    preview
+                }
+            },
+            &PickerTarget::Buffer(id) => {
+                let buf = self.buffer_text(id);
+                (0..buf.line_count().min(height))
+                    .map(|y| preview_line(buf.get_line(y)))
+                    .collect()
+            }
+            &PickerTarget::Line(y) => {
+                let start = y.saturating_sub(height / 2);
+                let end = (start + height).min(self.buf.line_count());
+                (start..end)
+                    .map(|y| preview_line(self.buf.get_line(y)))
+                    .collect()
+            }
+            PickerTarget::Command(_) => Vec::new(),
+        }
+    }
+
+    fn accept_picker(&mut self, how: Option<char>) -> Result<Option<String>> {
+        let Some(picker) = self.picker.take() else {
+            return Ok(None);
+        };
+        let Some(item) = picker.selected_item().cloned() else {
+            return Ok(None);
+        };
+        if let PickerTarget::Command(name) = item.target {
+            return Ok(Some(name));
+        }
+
+        match how {
+            Some('s') => self.split_window(false, None)?,
+            Some('v') => self.split_window(true, None)?,
+            Some('t') => self.push_tab(),
+            _ => {}
+        }
+        match item.target {
+            PickerTarget::File(path) => self.open_buffer(path)?,
+            PickerTarget::Buffer(id) => self.switch_buffer(id),
+            PickerTarget::Line(y) => {
+                self.cursor.move_y_to(&self.buf, y);
+                self.cursor.move_x_to(&self.buf, 0);
+            }
+            PickerTarget::Command(_) => unreachable!(),
+        }
+        Ok(None)
+    }
+
+    pub fn on_picker_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Option<String> {
+        let height = self.picker_list_height();
+        let picker = self.picker.as_mut()?;
+
+        let how = match (code, modifiers) {
+            (KeyCode::Enter, _) => None,
+            (KeyCode::Char('s' | 'x'), KeyModifiers::CONTROL) => Some('s'),
+            (KeyCode::Char('v'), KeyModifiers::CONTROL) => Some('v'),
+            (KeyCode::Char('t'), KeyModifiers::CONTROL) => Some('t'),
+            (KeyCode::Esc, _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                self.picker = None;
+                return None;
+            }
+            (KeyCode::Down | KeyCode::Tab, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
+                picker.move_selection(1, height);
+                return None;
+            }
+            (KeyCode::Up | KeyCode::BackTab, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+                picker.move_selection(-1, height);
+                return None;
+            }
+            (KeyCode::Backspace, _) => {
+                picker.query.pop();
+                picker.update_matches();
+                return None;
+            }
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                picker.query.clear();
+                picker.update_matches();
+                return None;
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                picker.query.push(c);
+                picker.update_matches();
+                return None;
+            }
+            _ => return None,
+        };
+
+        match self.accept_picker(how) {
+            Ok(cmd) => cmd,
+            Err(err) => {
+                self.set_message(err.to_string());
+                None
+            }
+        }
+    }
+
+    pub fn register_picker_cmds(cmds: &mut EditorCommand) {
+        type PickerCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let picker_cmds: &[(&[&str], PickerCommand)] = &[
+            (&["Files"], |editor, _| editor.pick_files()),
+            (&["Buffers"], |editor, _| {
+                editor.pick_buffers();
+                Ok(())
+            }),
+            (&["Lines"], |editor, _| {
+                editor.pick_lines();
+                Ok(())
+            }),
+            (&["History"], |editor, _| {
+                editor.pick_recent_files();
+                Ok(())
+            }),
+            (&["Commands"], |editor, _| {
+                if editor.command_names.is_empty() {
+                    return Err(anyhow!("No commands"));
+                }
+                editor.pick_commands();
+                Ok(())
+            }),
+        ];
+
+        for (names, f) in picker_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("", "abc"), Some((0, vec![])));
+        assert_eq!(fuzzy_match("xyz", "abc"), None);
+        assert_eq!(fuzzy_match("abc", "a_b_c").unwrap().1, vec![0, 2, 4]);
+        assert_eq!(fuzzy_match("ab", "a--ab").unwrap().1, vec![3, 4]);
+        assert!(fuzzy_match("Ab", "ab").is_none());
+        assert!(fuzzy_match("ab", "AB").is_some());
+
+        let (word, _) = fuzzy_match("fb", "foo/bar").unwrap();
+        let (inner, _) = fuzzy_match("fb", "xfxxbx").unwrap();
+        assert!(word > inner);
+        let (tight, _) = fuzzy_match("main", "src/main.rs").unwrap();
+        let (loose, _) = fuzzy_match("main", "src/m_a_i_n.rs").unwrap();
+        assert!(tight > loose);
+    }
+
+    #[test]
+    fn test_picker_lines() {
+        let mut state = EditorState::new("apple\nbanana\ncherry\n".to_string(), None);
+        state.pick_lines();
+        for c in "nan".chars() {
+            state.on_picker_key(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        let rows = state.picker_rows();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].0, "2: banana");
+
+        state.on_picker_key(KeyCode::Enter, KeyModifiers::NONE);
+        assert!(!state.is_picker_open());
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 1));
+
+        state.set_command_names(vec!["quit".to_string(), "write".to_string()]);
+        state.pick_commands();
+        state.on_picker_key(KeyCode::Char('w'), KeyModifiers::NONE);
+        assert_eq!(
+            state.on_picker_key(KeyCode::Enter, KeyModifiers::NONE),
+            Some("write".to_string())
+        );
+    }
+
+    #[test]
+    fn test_file_preview() {
+        let path = env::temp_dir().join(format!("ce-picker-test-{}", std::process::id()));
+        let text: Vec<String> = (0..1000).map(|i| format!("\tline {}", i)).collect();
+        std::fs::write(&path, text.join("\n")).unwrap();
+
+        let mut state = EditorState::new(String::new(), None);
+        state.resize(80, 24);
+        state.open_picker("Files", file_items(vec![path.clone()]));
+        let height = state.picker_list_height();
+        let preview = state.picker_preview();
+        assert_eq!(preview.len(), height);
+        assert_eq!(preview[0], "    line 0");
+
+        std::fs::write(&path, "changed\n").unwrap();
+        assert_eq!(state.picker_preview(), preview);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
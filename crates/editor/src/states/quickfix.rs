@@ -0,0 +1,716 @@
+use std::{env, path::PathBuf, process::Stdio};
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{
+        buffers::{display_name, same_file},
+        EditorState,
+    },
+};
+
+const LIST_WINDOW_HEIGHT: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuickfixItem {
+    pub path: PathBuf,
+    pub line: usize,
+    // Byte offset, like Vim's %c
+    pub col: usize,
+    pub text: String,
+}
+
+#[derive(Clone, Default)]
+pub struct QuickfixList {
+    items: Vec<QuickfixItem>,
+    index: usize,
+}
+
+impl QuickfixList {
+    pub fn new(items: Vec<QuickfixItem>) -> Self {
+        Self { items, index: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn lines(&self) -> String {
+        let lines: Vec<String> = self
+            .items
+            .iter()
+            .map(|item| {
+                format!(
+                    "{}|{} col {}| {}",
+                    display_name(Some(&item.path)),
+                    item.line + 1,
+                    item.col + 1,
+                    item.text
+                )
+            })
+            .collect();
+        lines.join("\n") + "\n"
+    }
+}
+
+#[derive(Clone)]
+pub struct ListView {
+    buffer: usize,
+    owner: usize,
+    location: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PatternKind {
+    Single,
+    Start,
+    Continue,
+    End,
+    Ignore,
+}
+
+#[derive(Clone, Debug)]
+pub struct ErrorPattern {
+    kind: PatternKind,
+    re: Regex,
+}
+
+fn errorformat_preset(name: &str) -> Option<&'static [&'static str]> {
+    let patterns: &[&str] = match name {
+        "rust" | "cargo" => &[
+            r"%Eerror(\[\w+\])?: %m",
+            r"%Wwarning(\[\w+\])?: %m",
+            r"%C\s*--> %f:%l:%c",
+            r"thread '.*' panicked at %f:%l:%c:",
+        ],
+        "gcc" | "clang" => &[r"%f:%l:%c: %m", r"%f:%l: %m"],
+        "python" => &[
+            r#"%E\s*File "%f", line %l.*"#,
+            r"%Z\w+(Error|Exception|Exit|Interrupt|Warning)(: .*)?",
+        ],
+        "generic" => &[r"%f:%l:%c:%m", r"%f:%l:%m"],
+        _ => return None,
+    };
+    Some(patterns)
+}
+
+fn compile_pattern(pattern: &str) -> Result<ErrorPattern> {
+    let (kind, rest) = if let Some(rest) = pattern.strip_prefix("%-G") {
+        (PatternKind::Ignore, rest)
+    } else if let Some(rest) = pattern
+        .strip_prefix("%E")
+        .or_else(|| pattern.strip_prefix("%W"))
+    {
+        (PatternKind::Start, rest)
+    } else if let Some(rest) = pattern.strip_prefix("%C") {
+        (PatternKind::Continue, rest)
+    } else if let Some(rest) = pattern.strip_prefix("%Z") {
+        (PatternKind::End, rest)
+    } else {
+        (PatternKind::Single, pattern)
+    };
+
+    let mut re = String::from("^(?:");
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            re.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('f') => re.push_str(r"(?P<f>[^\s:][^:]*?)"),
+            Some('l') => re.push_str(r"(?P<l>\d+)"),
+            Some('c') => re.push_str(r"(?P<c>\d+)"),
+            Some('m') => re.push_str(r"(?P<m>.*)"),
+            Some('%') => re.push('%'),
+            _ => return Err(anyhow!("Invalid errorformat: {}", pattern)),
+        }
+    }
+    re.push_str(")$");
+
+    let re = Regex::new(&re).map_err(|err| anyhow!("Invalid errorformat: {}", err))?;
+    Ok(ErrorPattern { kind, re })
+}
+
+pub fn parse_errorformat(value: &str) -> Result<Vec<ErrorPattern>> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&',') => {
+                parts.last_mut().unwrap().push(',');
+                chars.next();
+            }
+            ',' => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+
+    let mut patterns = Vec::new();
+    for part in parts.iter().filter(|part| !part.is_empty()) {
+        match errorformat_preset(part) {
+            Some(preset) => {
+                for pattern in preset {
+                    patterns.push(compile_pattern(pattern)?);
+                }
+            }
+            None => patterns.push(compile_pattern(part)?),
+        }
+    }
+    Ok(patterns)
+}
+
+#[derive(Default)]
+struct PartialItem {
+    path: Option<PathBuf>,
+    line: usize,
+    col: usize,
+    text: String,
+}
+
+impl PartialItem {
+    fn fill(&mut self, caps: &regex::Captures, line: &str, whole_line: bool) {
+        let number = |name: &str| {
+            caps.name(name)
+                .and_then(|m| m.as_str().parse::<usize>().ok())
+                .map(|n| n.saturating_sub(1))
+        };
+        if self.path.is_none() {
+            if let Some(f) = caps.name("f") {
+                self.path = Some(PathBuf::from(f.as_str()));
+                self.line = number("l").unwrap_or(0);
+                self.col = number("c").unwrap_or(0);
+            }
+        }
+        if self.text.is_empty() {
+            self.text = match caps.name("m") {
+                Some(m) => m.as_str().to_string(),
+                None if whole_line => line.trim().to_string(),
+                None => String::new(),
+            };
+        }
+    }
+
+    fn finish(self, items: &mut Vec<QuickfixItem>) {
+        let Some(path) = self.path else {
+            return;
+        };
+        let cwd = env::current_dir().unwrap_or_default();
+        items.push(QuickfixItem {
+            path: cwd.join(path),
+            line: self.line,
+            col: self.col,
+            text: self.text,
+        });
+    }
+}
+
+pub fn parse_errors(output: &str, patterns: &[ErrorPattern]) -> Vec<QuickfixItem> {
+    let mut items = Vec::new();
+    let mut pending: Option<PartialItem> = None;
+
+    for line in output.lines() {
+        for pattern in patterns {
+            let Some(caps) = pattern.re.captures(line) else {
+                continue;
+            };
+            match pattern.kind {
+                PatternKind::Ignore => {}
+                PatternKind::Single | PatternKind::Start => {
+                    if let Some(pending) = pending.take() {
+                        pending.finish(&mut items);
+                    }
+                    let mut item = PartialItem::default();
+                    item.fill(&caps, line, pattern.kind == PatternKind::Single);
+                    if pattern.kind == PatternKind::Start {
+                        pending = Some(item);
+                    } else {
+                        item.finish(&mut items);
+                    }
+                }
+                PatternKind::Continue | PatternKind::End => {
+                    let Some(item) = pending.as_mut() else {
+                        continue;
+                    };
+                    item.fill(&caps, line, pattern.kind == PatternKind::End);
+                    if pattern.kind == PatternKind::End {
+                        pending.take().unwrap().finish(&mut items);
+                    }
+                }
+            }
+            break;
+        }
+    }
+    if let Some(pending) = pending {
+        pending.finish(&mut items);
+    }
+    items
+}
+
+impl EditorState {
+    fn list(&self, location: bool) -> &QuickfixList {
+        if location {
+            self.location_list(self.current_window())
+        } else {
+            &self.quickfix
+        }
+    }
+
+    fn list_mut(&mut self, location: bool) -> &mut QuickfixList {
+        if location {
+            self.location_list_mut(self.current_window())
+        } else {
+            &mut self.quickfix
+        }
+    }
+
+    pub fn diagnostic_lines(&self, id: usize) -> Vec<usize> {
+        let Some(path) = self.buffer_path(self.window_buffer(id)) else {
+            return Vec::new();
+        };
+        let location = self.location_list(id);
+        let mut lines: Vec<usize> = self
+            .quickfix
+            .items
+            .iter()
+            .chain(&location.items)
+            .filter(|item| same_file(&item.path, path))
+            .map(|item| item.line)
+            .collect();
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+    }
+
+    fn current_list_view(&self) -> Option<ListView> {
+        let buffer = self.buffers_current();
+        self.list_views
+            .iter()
+            .find(|view| view.buffer == buffer)
+            .cloned()
+    }
+
+    fn leave_list_view(&mut self) {
+        let Some(view) = self.current_list_view() else {
+            return;
+        };
+        if self.window_exists(view.owner) {
+            self.enter_window(view.owner);
+            return;
+        }
+        let current = self.current_window();
+        match self.window_ids().into_iter().find(|id| *id != current) {
+            Some(id) => self.enter_window(id),
+            None => {
+                let _ = self.split_window(false, None);
+            }
+        }
+    }
+
+    pub fn set_list(&mut self, location: bool, list: QuickfixList) {
+        self.leave_list_view();
+        *self.list_mut(location) = list;
+        self.refresh_list_views();
+    }
+
+    fn refresh_list_views(&mut self) {
+        let buffers = self.buffer_ids();
+        self.list_views
+            .retain(|view| buffers.contains(&view.buffer));
+
+        for view in self.list_views.clone() {
+            let list = if view.location {
+                if !self.window_exists(view.owner) {
+                    continue;
+                }
+                self.location_list(view.owner)
+            } else {
+                &self.quickfix
+            };
+            let text = list.lines();
+            self.set_buffer_text(view.buffer, text);
+        }
+    }
+
+    pub fn jump_to_item(&mut self, location: bool, index: usize) -> Result<()> {
+        self.leave_list_view();
+        let list = self.list_mut(location);
+        if list.is_empty() {
+            return Err(anyhow!("No Errors"));
+        }
+        let item = list
+            .items
+            .get(index)
+            .cloned()
+            .ok_or_else(|| anyhow!("No more items"))?;
+        list.index = index;
+        let count = list.len();
+
+        self.open_buffer(item.path.clone())?;
+        self.cursor.move_y_to(&self.buf, item.line);
+        // Snap to the start of a multi-byte character
+        let line = self.buf.get_line(self.cursor.get_display(&self.buf).1);
+        let mut x = item.col.min(line.len());
+        while !line.is_char_boundary(x) {
+            x -= 1;
+        }
+        self.cursor.move_x_to(&self.buf, x);
+        self.set_message(format!("({} of {}) {}", index + 1, count, item.text.trim()));
+        Ok(())
+    }
+
+    fn jump_by(&mut self, location: bool, args: &str, forward: bool) -> Result<()> {
+        self.leave_list_view();
+        let count = args.parse::<usize>().unwrap_or(1);
+        let index = self.list(location).index;
+        let index = if forward {
+            index + count
+        } else {
+            index
+                .checked_sub(count)
+                .ok_or_else(|| anyhow!("No more items"))?
+        };
+        self.jump_to_item(location, index)
+    }
+
+    fn jump_to_number(&mut self, location: bool, args: &str) -> Result<()> {
+        self.leave_list_view();
+        let index = match args.parse::<usize>() {
+            Ok(n) => n.max(1) - 1,
+            Err(_) if args.is_empty() => self.list(location).index,
+            Err(_) => return Err(anyhow!("Invalid argument: {}", args)),
+        };
+        self.jump_to_item(location, index)
+    }
+
+    // :clist
+    fn show_list(&mut self, location: bool) -> Result<()> {
+        let list = self.list(location);
+        if list.is_empty() {
+            return Err(anyhow!("No Errors"));
+        }
+        let message: Vec<String> = list
+            .lines()
+            .lines()
+            .enumerate()
+            .map(|(i, line)| format!("{:>3} {}", i + 1, line))
+            .collect();
+        self.set_message(message.join("\n"));
+        Ok(())
+    }
+
+    fn open_list_view(&mut self, location: bool) {
+        self.leave_list_view();
+        let owner = self.current_window();
+        let existing = self
+            .list_views
+            .iter()
+            .find(|view| view.location == location && (!location || view.owner == owner))
+            .map(|view| view.buffer)
+            .filter(|buffer| self.buffer_ids().contains(buffer));
+
+        let index = self.list(location).index;
+        let text = self.list(location).lines();
+        match existing.and_then(|buffer| Some((buffer, self.window_showing(buffer)?))) {
+            Some((buffer, window)) => {
+                self.set_buffer_text(buffer, text);
+                self.enter_window(window);
+                if let Some(view) = self.list_views.iter_mut().find(|v| v.buffer == buffer) {
+                    view.owner = owner;
+                }
+            }
+            None => {
+                let _ = self.split_window(false, None);
+                self.set_window_height(LIST_WINDOW_HEIGHT);
+                let buffer = match existing {
+                    Some(buffer) => buffer,
+                    None => self.new_buffer(),
+                };
+                self.switch_buffer(buffer);
+                self.clear_message();
+                self.set_buffer_text(buffer, text);
+                self.list_views.retain(|view| view.buffer != buffer);
+                self.list_views.push(ListView {
+                    buffer,
+                    owner,
+                    location,
+                });
+            }
+        }
+        self.cursor.move_y_to(&self.buf, index);
+        self.cursor.move_x_to(&self.buf, 0);
+    }
+
+    fn close_list_view(&mut self, location: bool) -> Result<()> {
+        self.leave_list_view();
+        let owner = self.current_window();
+        let Some(index) = self
+            .list_views
+            .iter()
+            .position(|view| view.location == location && (!location || view.owner == owner))
+        else {
+            return Ok(());
+        };
+        let view = self.list_views.remove(index);
+        if !self.buffer_ids().contains(&view.buffer) {
+            return Ok(());
+        }
+
+        while let Some(window) = self.window_showing(view.buffer) {
+            if self.window_count() == 1 {
+                break;
+            }
+            self.enter_window(window);
+            self.close_window()?;
+        }
+        self.delete_buffer(view.buffer);
+        if self.window_exists(owner) {
+            self.enter_window(owner);
+        }
+        Ok(())
+    }
+
+    fn open_list_item(&mut self) {
+        let Some(view) = self.current_list_view() else {
+            return;
+        };
+        let index = self.cursor.get_display(&self.buf).1;
+        if let Err(err) = self.jump_to_item(view.location, index) {
+            self.set_message(err.to_string());
+        }
+    }
+
+    fn ex_make(&mut self, location: bool, args: &str) -> Result<()> {
+        let cmd = format!("{} {}", self.options.makeprg, args);
+        let cmd = cmd.trim();
+        let patterns = parse_errorformat(&self.options.errorformat)?;
+        let output = self
+            .shell_command(&format!("({}) 2>&1", cmd))
+            .stdin(Stdio::null())
+            .output()?;
+
+        let items = parse_errors(&String::from_utf8_lossy(&output.stdout), &patterns);
+        let found = !items.is_empty();
+        self.set_list(location, QuickfixList::new(items));
+        if found {
+            return self.jump_to_item(location, 0);
+        }
+        match output.status.code() {
+            Some(0) => self.set_message(format!("{}: no errors", cmd)),
+            code => self.set_message(format!("{}: shell returned {}", cmd, code.unwrap_or(-1))),
+        }
+        Ok(())
+    }
+
+    pub fn register_quickfix_cmds(cmds: &mut EditorCommand) {
+        type QuickfixCommand = fn(&mut EditorState, bool, &str) -> Result<()>;
+        let quickfix_cmds: &[(&[&str], &[&str], QuickfixCommand)] = &[
+            (
+                &["cn", "cnext"],
+                &["lne", "lnext"],
+                |editor, location, args| editor.jump_by(location, args, true),
+            ),
+            (
+                &["cp", "cprevious", "cN", "cNext"],
+                &["lp", "lprevious", "lN", "lNext"],
+                |editor, location, args| editor.jump_by(location, args, false),
+            ),
+            (&["cc"], &["ll"], EditorState::jump_to_number),
+            (
+                &["cr", "crewind", "cfir", "cfirst"],
+                &["lr", "lrewind", "lfir", "lfirst"],
+                |editor, location, _| editor.jump_to_item(location, 0),
+            ),
+            (
+                &["cla", "clast"],
+                &["lla", "llast"],
+                |editor, location, _| {
+                    editor.leave_list_view();
+                    let last = editor.list(location).len().saturating_sub(1);
+                    editor.jump_to_item(location, last)
+                },
+            ),
+            (
+                &["cl", "clist"],
+                &["lli", "llist"],
+                |editor, location, _| editor.show_list(location),
+            ),
+            (
+                &["cope", "copen"],
+                &["lop", "lopen"],
+                |editor, location, _| {
+                    editor.open_list_view(location);
+                    Ok(())
+                },
+            ),
+            (
+                &["ccl", "cclose"],
+                &["lcl", "lclose"],
+                |editor, location, _| editor.close_list_view(location),
+            ),
+            (&["mak", "make"], &["lmak", "lmake"], EditorState::ex_make),
+        ];
+
+        for (names, location_names, f) in quickfix_cmds {
+            let f = *f;
+            for (location, names) in [(false, names), (true, location_names)] {
+                for name in names.iter() {
+                    cmds.register_with_args(
+                        name,
+                        Box::new(move |editor, args| {
+                            if let Err(err) = f(editor, location, args) {
+                                editor.set_message(err.to_string());
+                            }
+                        }),
+                    );
+                }
+            }
+        }
+
+        cmds.register("open_list_item", Box::new(|editor| editor.open_list_item()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(output: &str, errorformat: &str) -> Vec<(String, usize, usize, String)> {
+        let cwd = env::current_dir().unwrap();
+        parse_errors(output, &parse_errorformat(errorformat).unwrap())
+            .into_iter()
+            .map(|item| {
+                let path = item.path.strip_prefix(&cwd).unwrap().display().to_string();
+                (path, item.line, item.col, item.text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let rust = "\
+   Compiling ce v0.1.0
+error[E0425]: cannot find value `x` in this scope
+ --> src/main.rs:2:5
+  |
+2 |     x
+  |     ^ not found in this scope
+
+warning: unused variable: `y`
+  --> src/lib.rs:10:9
+error: could not compile `ce`
+";
+        assert_eq!(
+            items(rust, "rust"),
+            vec![
+                (
+                    "src/main.rs".to_string(),
+                    1,
+                    4,
+                    "cannot find value `x` in this scope".to_string()
+                ),
+                (
+                    "src/lib.rs".to_string(),
+                    9,
+                    8,
+                    "unused variable: `y`".to_string()
+                ),
+            ]
+        );
+
+        let gcc = "a.c:3:7: error: expected ';'\nb.c:4: warning: x\nmake: *** Error 1\n";
+        assert_eq!(
+            items(gcc, "gcc"),
+            vec![
+                ("a.c".to_string(), 2, 6, "error: expected ';'".to_string()),
+                ("b.c".to_string(), 3, 0, "warning: x".to_string()),
+            ]
+        );
+
+        let python = "\
+Traceback (most recent call last):
+  File \"a.py\", line 3, in <module>
+    f()
+NameError: name 'f' is not defined
+";
+        assert_eq!(
+            items(python, "python"),
+            vec![(
+                "a.py".to_string(),
+                2,
+                0,
+                "NameError: name 'f' is not defined".to_string()
+            )]
+        );
+
+        assert_eq!(
+            items("skip:1:x\nfoo.txt(5,2) bad\n", r"%-Gskip.*,%f\(%l\,%c\) %m"),
+            vec![("foo.txt".to_string(), 4, 1, "bad".to_string())]
+        );
+        assert!(parse_errorformat("%q").is_err());
+    }
+
+    #[test]
+    fn test_quickfix_list() {
+        let mut state = EditorState::new("a\nb\nc\n".to_string(), None);
+        let path = env::current_dir().unwrap().join("Cargo.toml");
+        let item = |line| QuickfixItem {
+            path: path.clone(),
+            line,
+            col: 0,
+            text: String::new(),
+        };
+        state.set_list(false, QuickfixList::new(vec![item(0), item(2)]));
+        state.jump_by(false, "", true).unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 2));
+        assert!(state.jump_by(false, "", true).is_err());
+
+        state.open_list_view(false);
+        assert_eq!(state.window_count(), 2);
+        assert_eq!(state.buf.line_count(), 3);
+        state.cursor.move_y_to(&state.buf, 0);
+        state.open_list_item();
+        assert_eq!(state.get_path(), Some(path.clone()));
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 0));
+        state.close_list_view(false).unwrap();
+        assert_eq!(state.window_count(), 1);
+
+        state.set_list(true, QuickfixList::new(vec![item(1)]));
+        let first = state.current_window();
+        state.split_window(true, None).unwrap();
+        state.set_list(true, QuickfixList::default());
+        assert!(state.jump_to_number(true, "").is_err());
+        state.enter_window(first);
+        state.jump_to_number(true, "").unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 1));
+    }
+
+    #[test]
+    fn test_jump_to_column() {
+        let path = env::temp_dir().join(format!("ce-quickfix-test-{}", std::process::id()));
+        std::fs::write(&path, "aé b\n").unwrap();
+        let mut state = EditorState::new(String::new(), None);
+        let item = |col| QuickfixItem {
+            path: path.clone(),
+            line: 0,
+            col,
+            text: String::new(),
+        };
+
+        state.set_list(false, QuickfixList::new(vec![item(2), item(4), item(100)]));
+        state.jump_to_item(false, 0).unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (1, 0));
+        state.jump_to_item(false, 1).unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (4, 0));
+        state.jump_to_item(false, 2).unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (5, 0));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Result};
+
+use crate::states::{operator::TextRange, EditorState};
+
+fn split_number(s: &str) -> (Option<usize>, &str) {
+    let len = s.chars().take_while(|c| c.is_ascii_digit()).count();
+    (s[..len].parse().ok(), &s[len..])
+}
+
+impl EditorState {
+    pub fn parse_address<'a>(&self, s: &'a str) -> Result<(Option<usize>, &'a str)> {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let last = self.buf.line_count();
+
+        let (mut line, mut rest) = match s.chars().next() {
+            Some('.') => (Some(cursor_y + 1), &s[1..]),
+            Some('$') => (Some(last), &s[1..]),
+            Some('\'') => {
+                let (start, end) = self.visual_marks.ok_or_else(|| anyhow!("Mark not set"))?;
+                match s[1..].chars().next() {
+                    Some('<') => (Some(start.y.min(end.y) + 1), &s[2..]),
+                    Some('>') => (Some(start.y.max(end.y) + 1), &s[2..]),
+                    _ => return Err(anyhow!("Unknown mark: {}", s)),
+                }
+            }
+            Some(c) if c.is_ascii_digit() => split_number(s),
+            _ => (None, s),
+        };
+
+        while let Some(sign @ ('+' | '-')) = rest.chars().next() {
+            let (n, after) = split_number(&rest[1..]);
+            let base = line.unwrap_or(cursor_y + 1) as isize;
+            let n = n.unwrap_or(1) as isize;
+            let value = if sign == '+' { base + n } else { base - n };
+            if value < 0 {
+                return Err(anyhow!("Invalid range"));
+            }
+            line = Some(value as usize);
+            rest = after;
+        }
+
+        Ok((line, rest))
+    }
+
+    pub fn parse_range<'a>(&self, s: &'a str) -> Result<(Option<TextRange>, &'a str)> {
+        let s = s.trim_start();
+        if let Some(rest) = s.strip_prefix('%') {
+            return Ok((
+                Some(TextRange::lines(0, self.buf.line_count() - 1)),
+                rest.trim_start(),
+            ));
+        }
+
+        let (start, rest) = self.parse_address(s)?;
+        let (end, rest) = match rest.strip_prefix([',', ';']) {
+            Some(rest) => {
+                let (end, rest) = self.parse_address(rest)?;
+                (end.or(start), rest)
+            }
+            None => (start, rest),
+        };
+
+        let (Some(start), Some(end)) = (start, end) else {
+            return Ok((None, rest.trim_start()));
+        };
+        if start > self.buf.line_count() || end > self.buf.line_count() {
+            return Err(anyhow!("Invalid range"));
+        }
+
+        let range = TextRange::lines(start.max(1) - 1, end.max(1) - 1);
+        Ok((Some(range), rest.trim_start()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        let mut state = EditorState::new("a\nb\nc\nd\ne".to_string(), None);
+        state.cursor.move_y_to(&state.buf, 1);
+
+        let range = |state: &EditorState, s| {
+            let (range, rest) = state.parse_range(s).unwrap();
+            (
+                range.map(|range| (range.start.y, range.end.y)),
+                rest.to_string(),
+            )
+        };
+
+        assert_eq!(range(&state, "sort"), (None, "sort".to_string()));
+        assert_eq!(range(&state, "%sort"), (Some((0, 4)), "sort".to_string()));
+        assert_eq!(range(&state, "2,4d"), (Some((1, 3)), "d".to_string()));
+        assert_eq!(range(&state, ".,$y"), (Some((1, 4)), "y".to_string()));
+        assert_eq!(range(&state, ".+1,+2j"), (Some((2, 3)), "j".to_string()));
+        assert_eq!(range(&state, "4,2"), (Some((1, 3)), String::new()));
+        assert!(state.parse_range("1,9d").is_err());
+        assert!(state.parse_range("'<,'>d").is_err());
+    }
+}
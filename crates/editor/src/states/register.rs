@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+// " unnamed, 0 last yank, 1-9 deleted lines, - small delete,
+// a-z named (A-Z appends), _ discards
+#[derive(Clone, Default)]
+pub struct Registers {
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    pub fn get(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name.to_ascii_lowercase())
+    }
+
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphanumeric() || matches!(name, '"' | '-' | '_')
+    }
+
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        if name == Some('_') {
+            return;
+        }
+        if let Some(name) = name.filter(|name| *name != '"') {
+            self.set_named(name, register);
+            return;
+        }
+
+        self.registers.insert('0', register.clone());
+        self.registers.insert('"', register);
+    }
+
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        if name == Some('_') {
+            return;
+        }
+        if let Some(name) = name.filter(|name| *name != '"') {
+            self.set_named(name, register);
+            return;
+        }
+
+        if register.linewise || register.text.contains('\n') {
+            for n in (1..9).rev() {
+                let from = char::from_digit(n, 10).unwrap();
+                let to = char::from_digit(n + 1, 10).unwrap();
+                if let Some(prev) = self.registers.remove(&from) {
+                    self.registers.insert(to, prev);
+                }
+            }
+            self.registers.insert('1', register.clone());
+        } else {
+            self.registers.insert('-', register.clone());
+        }
+        self.registers.insert('"', register);
+    }
+
+    fn set_named(&mut self, name: char, register: Register) {
+        let register = match self.registers.get(&name.to_ascii_lowercase()) {
+            Some(prev) if name.is_ascii_uppercase() => {
+                let sep = if prev.linewise || register.linewise {
+                    "\n"
+                } else {
+                    ""
+                };
+                Register {
+                    text: prev.text.clone() + sep + &register.text,
+                    linewise: prev.linewise || register.linewise,
+                }
+            }
+            _ => register,
+        };
+
+        self.registers
+            .insert(name.to_ascii_lowercase(), register.clone());
+        self.registers.insert('"', register);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> Register {
+        Register {
+            text: text.to_string(),
+            linewise: true,
+        }
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut registers = Registers::default();
+
+        registers.delete(None, line("a"));
+        registers.delete(None, line("b"));
+        registers.yank(None, line("c"));
+        assert_eq!(registers.get('1'), Some(&line("b")));
+        assert_eq!(registers.get('2'), Some(&line("a")));
+        assert_eq!(registers.get('0'), Some(&line("c")));
+        assert_eq!(registers.get('"'), Some(&line("c")));
+
+        registers.yank(Some('x'), line("d"));
+        registers.yank(Some('X'), line("e"));
+        assert_eq!(registers.get('x'), Some(&line("d\ne")));
+
+        registers.delete(Some('_'), line("f"));
+        assert_eq!(registers.get('"'), Some(&line("d\ne")));
+    }
+}
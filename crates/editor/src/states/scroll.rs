@@ -0,0 +1,401 @@
+use utils::text::expand_tabs;
+
+use crate::{cmd::EditorCommand, states::EditorState};
+
+impl EditorState {
+    pub fn view_height(&self) -> usize {
+        self.view_size.y.max(1)
+    }
+
+    pub fn gutter_width(&self) -> usize {
+        self.buf.line_count().to_string().len() + 1
+    }
+
+    pub fn view_width(&self) -> usize {
+        self.view_size.x.saturating_sub(self.gutter_width()).max(1)
+    }
+
+    fn sidescrolloff(&self) -> usize {
+        self.options
+            .sidescrolloff
+            .min(self.view_width().saturating_sub(1) / 2)
+    }
+
+    fn scrolloff(&self) -> usize {
+        self.options
+            .scrolloff
+            .min(self.view_height().saturating_sub(1) / 2)
+    }
+
+    fn max_offset_y(&self) -> usize {
+        self.buf.line_count() - 1
+    }
+
+    pub fn scroll_to_cursor(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let height = self.view_height();
+        let so = self.scrolloff();
+
+        if self.options.wrap {
+            self.scroll_to_cursor_wrapped(so);
+            return;
+        }
+
+        if cursor_y < self.offset.y + so {
+            self.offset.y = cursor_y.saturating_sub(so);
+        } else if cursor_y + so >= self.offset.y + height {
+            // Don't scroll past the end of the file for scrolloff
+            let end = self.buf.line_count().saturating_sub(height);
+            self.offset.y = (cursor_y + so + 1)
+                .saturating_sub(height)
+                .min(end)
+                .max((cursor_y + 1).saturating_sub(height));
+        }
+
+        self.offset.y = self.offset.y.min(self.max_offset_y());
+
+        self.side_scroll_to_cursor();
+    }
+
+    fn side_scroll_to_cursor(&mut self) {
+        let cursor_x = self.cursor_col();
+        let width = self.view_width();
+        let siso = self.sidescrolloff();
+        let step = self.options.sidescroll;
+
+        if cursor_x < self.offset.x + siso {
+            self.offset.x = if step == 0 {
+                cursor_x.saturating_sub(width / 2)
+            } else {
+                cursor_x
+                    .saturating_sub(siso)
+                    .min(self.offset.x.saturating_sub(step))
+            };
+        } else if cursor_x + siso >= self.offset.x + width {
+            self.offset.x = if step == 0 {
+                cursor_x.saturating_sub(width / 2)
+            } else {
+                (cursor_x + siso + 1)
+                    .saturating_sub(width)
+                    .max(self.offset.x + step)
+            };
+        }
+    }
+
+    pub fn clip_line(&self, line: &str) -> String {
+        let width = self.view_width();
+        let line = expand_tabs(line, self.options.tabstop);
+        let len = line.chars().count();
+        let mut visible: Vec<char> = line.chars().skip(self.offset.x).take(width).collect();
+
+        if self.offset.x > 0 && len > 0 {
+            if visible.is_empty() {
+                visible.push('<');
+            } else {
+                visible[0] = '<';
+            }
+        }
+        if len > self.offset.x + width {
+            visible[width - 1] = '>';
+        }
+
+        visible.into_iter().collect()
+    }
+
+    fn clamp_cursor_to_view_x(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let cursor_x = self.cursor_col();
+        let siso = self.sidescrolloff();
+        let left = if self.offset.x == 0 {
+            0
+        } else {
+            self.offset.x + siso
+        };
+        let right = (self.offset.x + self.view_width() - 1)
+            .saturating_sub(siso)
+            .max(left);
+
+        if cursor_x < left {
+            let x = self.col_to_x(cursor_y, left);
+            self.cursor.move_x_to(&self.buf, x);
+        } else if cursor_x > right {
+            let x = self.col_to_x(cursor_y, right);
+            self.cursor.move_x_to(&self.buf, x);
+        }
+    }
+
+    pub fn side_scroll_by(&mut self, columns: isize) {
+        self.offset.x = if columns < 0 {
+            self.offset.x.saturating_sub(-columns as usize)
+        } else {
+            self.offset.x + columns as usize
+        };
+
+        self.clamp_cursor_to_view_x();
+    }
+
+    fn clamp_cursor_to_view(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        let (top, bottom) = self.window_bounds();
+
+        if cursor_y < top {
+            self.cursor.move_y_to(&self.buf, top);
+        } else if cursor_y > bottom {
+            self.cursor.move_y_to(&self.buf, bottom);
+        }
+    }
+
+    fn window_bounds(&self) -> (usize, usize) {
+        let so = self.scrolloff();
+        let last_line = self.buf.line_count() - 1;
+        let last_visible = self.last_visible_line();
+
+        let top = if self.offset.y == 0 {
+            0
+        } else {
+            (self.offset.y + so).min(last_visible)
+        };
+        let bottom = if last_visible == last_line {
+            last_line
+        } else {
+            last_visible.saturating_sub(so).max(top)
+        };
+
+        (top, bottom)
+    }
+
+    pub fn scroll_by(&mut self, lines: isize) {
+        self.offset.y = if lines < 0 {
+            self.offset.y.saturating_sub(-lines as usize)
+        } else {
+            (self.offset.y + lines as usize).min(self.max_offset_y())
+        };
+
+        self.clamp_cursor_to_view();
+    }
+
+    fn scroll_with_cursor(&mut self, lines: isize) {
+        let old_offset = self.offset.y;
+        let (_, old_cursor_y) = self.cursor.get_display(&self.buf);
+        self.scroll_by(lines);
+
+        let scrolled = self.offset.y as isize - old_offset as isize;
+        let moved = if scrolled == 0 { lines } else { scrolled };
+        self.cursor.move_y_to(&self.buf, old_cursor_y);
+        self.cursor.move_by(&self.buf, 0, moved);
+    }
+
+    fn half_page(&self) -> isize {
+        (self.view_height() / 2).max(1) as isize
+    }
+
+    fn full_page(&self) -> isize {
+        self.view_height().saturating_sub(2).max(1) as isize
+    }
+
+    pub fn cmd_window_top(&mut self) {
+        let (top, _) = self.window_bounds();
+        self.cursor.move_y_to(&self.buf, top);
+    }
+
+    pub fn cmd_window_middle(&mut self) {
+        let last_visible = self.last_visible_line();
+        self.cursor
+            .move_y_to(&self.buf, (self.offset.y + last_visible) / 2);
+    }
+
+    pub fn cmd_window_bottom(&mut self) {
+        let (_, bottom) = self.window_bounds();
+        self.cursor.move_y_to(&self.buf, bottom);
+    }
+
+    pub fn cmd_scroll_half_down(&mut self) {
+        self.scroll_with_cursor(self.half_page());
+    }
+
+    pub fn cmd_scroll_half_up(&mut self) {
+        self.scroll_with_cursor(-self.half_page());
+    }
+
+    pub fn cmd_scroll_page_down(&mut self) {
+        self.scroll_by(self.full_page());
+        self.cmd_window_top();
+    }
+
+    pub fn cmd_scroll_page_up(&mut self) {
+        self.scroll_by(-self.full_page());
+        self.cmd_window_bottom();
+    }
+
+    pub fn cmd_scroll_cursor_center(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        self.offset.y = cursor_y.saturating_sub(self.view_height() / 2);
+    }
+
+    pub fn cmd_scroll_cursor_top(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        self.offset.y = cursor_y.saturating_sub(self.scrolloff());
+    }
+
+    pub fn cmd_scroll_cursor_bottom(&mut self) {
+        let (_, cursor_y) = self.cursor.get_display(&self.buf);
+        self.offset.y = (cursor_y + self.scrolloff() + 1).saturating_sub(self.view_height());
+    }
+
+    pub fn cmd_scroll_cursor_left(&mut self) {
+        let cursor_x = self.cursor_col();
+        self.offset.x = cursor_x.saturating_sub(self.sidescrolloff());
+    }
+
+    pub fn cmd_scroll_cursor_right(&mut self) {
+        let cursor_x = self.cursor_col();
+        self.offset.x = (cursor_x + self.sidescrolloff() + 1).saturating_sub(self.view_width());
+    }
+
+    pub fn register_scroll_cmds(cmds: &mut EditorCommand) {
+        cmds.register("window_top", Box::new(|editor| editor.cmd_window_top()));
+        cmds.register(
+            "window_middle",
+            Box::new(|editor| editor.cmd_window_middle()),
+        );
+        cmds.register(
+            "window_bottom",
+            Box::new(|editor| editor.cmd_window_bottom()),
+        );
+        cmds.register(
+            "scroll_half_down",
+            Box::new(|editor| editor.cmd_scroll_half_down()),
+        );
+        cmds.register(
+            "scroll_half_up",
+            Box::new(|editor| editor.cmd_scroll_half_up()),
+        );
+        cmds.register(
+            "scroll_page_down",
+            Box::new(|editor| editor.cmd_scroll_page_down()),
+        );
+        cmds.register(
+            "scroll_page_up",
+            Box::new(|editor| editor.cmd_scroll_page_up()),
+        );
+        cmds.register("scroll_line_down", Box::new(|editor| editor.scroll_by(1)));
+        cmds.register("scroll_line_up", Box::new(|editor| editor.scroll_by(-1)));
+        cmds.register(
+            "scroll_column_left",
+            Box::new(|editor| editor.side_scroll_by(-1)),
+        );
+        cmds.register(
+            "scroll_column_right",
+            Box::new(|editor| editor.side_scroll_by(1)),
+        );
+        cmds.register(
+            "scroll_cursor_left",
+            Box::new(|editor| editor.cmd_scroll_cursor_left()),
+        );
+        cmds.register(
+            "scroll_cursor_right",
+            Box::new(|editor| editor.cmd_scroll_cursor_right()),
+        );
+        cmds.register(
+            "scroll_cursor_center",
+            Box::new(|editor| editor.cmd_scroll_cursor_center()),
+        );
+        cmds.register(
+            "scroll_cursor_top",
+            Box::new(|editor| editor.cmd_scroll_cursor_top()),
+        );
+        cmds.register(
+            "scroll_cursor_bottom",
+            Box::new(|editor| editor.cmd_scroll_cursor_bottom()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(lines: usize, height: usize) -> EditorState {
+        let buf = (0..lines).map(|i| i.to_string()).collect::<Vec<_>>();
+        let mut state = EditorState::new(buf.join("\n"), None);
+        state.resize(80, height + 2);
+        state
+    }
+
+    #[test]
+    fn test_scroll_to_cursor_keeps_scrolloff() {
+        let mut state = state(100, 10);
+        state.options.scrolloff = 3;
+
+        state.cursor.move_y_to(&state.buf, 8);
+        state.scroll_to_cursor();
+        assert_eq!(state.offset.y, 2);
+
+        state.cursor.move_y_to(&state.buf, 99);
+        state.scroll_to_cursor();
+        assert_eq!(state.offset.y, 90);
+
+        state.cursor.move_y_to(&state.buf, 91);
+        state.scroll_to_cursor();
+        assert_eq!(state.offset.y, 88);
+    }
+
+    #[test]
+    fn test_scroll_half_page() {
+        let mut state = state(100, 10);
+
+        state.cmd_scroll_half_down();
+        assert_eq!(state.offset.y, 5);
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 5));
+
+        state.cmd_scroll_half_up();
+        assert_eq!(state.offset.y, 0);
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 0));
+    }
+
+    #[test]
+    fn test_side_scroll_to_cursor() {
+        let mut state = EditorState::new("a".repeat(200), None);
+        state.resize(42, 12);
+        state.options.sidescroll = 1;
+        state.options.sidescrolloff = 5;
+
+        state.cursor.move_x_to(&state.buf, 40);
+        state.scroll_to_cursor();
+        assert_eq!(state.offset.x, 6);
+
+        state.cursor.move_x_to(&state.buf, 8);
+        state.scroll_to_cursor();
+        assert_eq!(state.offset.x, 3);
+
+        state.options.sidescroll = 0;
+        state.cursor.move_x_to(&state.buf, 100);
+        state.scroll_to_cursor();
+        assert_eq!(state.offset.x, 80);
+    }
+
+    #[test]
+    fn test_clip_line() {
+        let mut state = EditorState::new(String::new(), None);
+        state.resize(12, 12);
+
+        assert_eq!(state.clip_line("0123456789abc"), "012345678>");
+        state.offset.x = 2;
+        assert_eq!(state.clip_line("0123456789abc"), "<3456789a>");
+        assert_eq!(state.clip_line("0123"), "<3");
+        assert_eq!(state.clip_line(""), "");
+
+        state.offset.x = 0;
+        state.options.tabstop = 4;
+        assert_eq!(state.clip_line("\tab\tc"), "    ab  c");
+    }
+
+    #[test]
+    fn test_scroll_line_moves_cursor_into_view() {
+        let mut state = state(100, 10);
+
+        state.scroll_by(1);
+        assert_eq!(state.offset.y, 1);
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 1));
+    }
+}
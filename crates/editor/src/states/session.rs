@@ -0,0 +1,171 @@
+use std::{
+    env,
+    fs::{create_dir_all, read_dir, read_to_string, write},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    cmd::EditorCommand,
+    states::{start::state_dir, EditorState},
+};
+
+fn sessions_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("sessions"))
+}
+
+fn session_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains('/') {
+        return Err(anyhow!("Invalid session name: {}", name));
+    }
+    Ok(sessions_dir()?.join(name))
+}
+
+#[derive(Debug, PartialEq)]
+struct Session {
+    cwd: PathBuf,
+    files: Vec<PathBuf>,
+    line: usize,
+}
+
+impl Session {
+    fn parse(text: &str) -> Result<Session> {
+        let mut session = Session {
+            cwd: PathBuf::new(),
+            files: Vec::new(),
+            line: 0,
+        };
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            match line.split_once(' ') {
+                Some(("cwd", dir)) => session.cwd = PathBuf::from(dir),
+                Some(("line", n)) => session.line = n.parse().unwrap_or_default(),
+                Some(("file", path)) => session.files.push(PathBuf::from(path)),
+                _ => return Err(anyhow!("Invalid session line: {}", line)),
+            }
+        }
+        if session.files.is_empty() {
+            return Err(anyhow!("No files in session"));
+        }
+        Ok(session)
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("cwd {}\nline {}\n", self.cwd.display(), self.line);
+        for path in &self.files {
+            text.push_str(&format!("file {}\n", path.display()));
+        }
+        text
+    }
+}
+
+pub fn list_sessions() -> Vec<String> {
+    let Ok(entries) = sessions_dir().and_then(|dir| Ok(read_dir(dir)?)) else {
+        return Vec::new();
+    };
+    let mut sessions: Vec<(SystemTime, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.file_name().to_str()?.to_string()))
+        })
+        .collect();
+    sessions.sort_by(|a, b| b.cmp(a));
+    sessions.into_iter().map(|(_, name)| name).collect()
+}
+
+impl EditorState {
+    fn ex_mksession(&mut self, args: &str) -> Result<()> {
+        let name = if args.is_empty() { "default" } else { args };
+        let path = session_path(name)?;
+        let files = self.buffer_paths();
+        if files.is_empty() {
+            return Err(anyhow!("No file name"));
+        }
+        let session = Session {
+            cwd: env::current_dir()?,
+            files,
+            line: self.cursor.get_display(&self.buf).1,
+        };
+
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        write(&path, session.to_text())?;
+        self.set_message(format!("Session saved: {}", name));
+        Ok(())
+    }
+
+    pub fn load_session(&mut self, name: &str) -> Result<()> {
+        let path = session_path(name)?;
+        let text =
+            read_to_string(&path).map_err(|err| anyhow!("Can't open session {}: {}", name, err))?;
+        let session = Session::parse(&text)?;
+
+        env::set_current_dir(&session.cwd)
+            .map_err(|err| anyhow!("Can't change directory: {}", err))?;
+        let mut error = None;
+        for file in session.files.iter().rev() {
+            if let Err(err) = self.open_buffer(file.clone()) {
+                error = Some(err);
+            }
+        }
+        self.cursor.move_y_to(&self.buf, session.line);
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn ex_load_session(&mut self, args: &str) -> Result<()> {
+        let name = if args.is_empty() { "default" } else { args };
+        self.load_session(name)
+    }
+
+    pub fn register_session_cmds(cmds: &mut EditorCommand) {
+        type SessionCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let session_cmds: &[(&[&str], SessionCommand)] = &[
+            (&["mks", "mksession"], EditorState::ex_mksession),
+            (&["LoadSession"], EditorState::ex_load_session),
+        ];
+
+        for (names, f) in session_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_text() {
+        let session = Session {
+            cwd: PathBuf::from("/work"),
+            files: vec![PathBuf::from("/work/a.rs"), PathBuf::from("/tmp/b c.txt")],
+            line: 12,
+        };
+        let text = session.to_text();
+        assert_eq!(
+            text,
+            "cwd /work\nline 12\nfile /work/a.rs\nfile /tmp/b c.txt\n"
+        );
+        assert_eq!(Session::parse(&text).unwrap(), session);
+
+        assert!(Session::parse("cwd /work\n").is_err());
+        assert!(Session::parse("unknown\nfile /a\n").is_err());
+        assert!(session_path("../x").is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    env,
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::states::{buffers::display_name, EditorState};
+
+const RECENT_FILES_MAX: usize = 20;
+const START_SCREEN_FILES: usize = 9;
+const SESSION_KEYS: &[char] = &['a', 'b', 'c', 'd', 'f', 'g', 'h'];
+
+#[derive(Clone)]
+pub struct StartScreen {
+    recent: Vec<PathBuf>,
+    sessions: Vec<String>,
+}
+
+pub fn state_dir() -> Result<PathBuf> {
+    let state_home = match env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var("HOME").map_err(|_| anyhow!("HOME is not set"))?;
+            PathBuf::from(home).join(".local/state")
+        }
+    };
+    Ok(state_home.join("ce"))
+}
+
+fn recent_files_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join("recent"))
+}
+
+pub fn load_recent_files() -> Vec<PathBuf> {
+    let Ok(path) = recent_files_path() else {
+        return Vec::new();
+    };
+    read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn merge_recent_files(recent: Vec<PathBuf>, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut merged: Vec<PathBuf> = Vec::new();
+    for path in paths.into_iter().chain(recent) {
+        if !merged.contains(&path) {
+            merged.push(path);
+        }
+    }
+    merged.truncate(RECENT_FILES_MAX);
+    merged
+}
+
+pub fn save_recent_files(paths: Vec<PathBuf>) -> Result<()> {
+    let cwd = env::current_dir()?;
+    let paths = paths.into_iter().map(|path| cwd.join(path)).collect();
+    let merged = merge_recent_files(load_recent_files(), paths);
+
+    let file = recent_files_path()?;
+    if let Some(dir) = file.parent() {
+        create_dir_all(dir)?;
+    }
+    let lines: Vec<String> = merged
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    write(file, lines.join("\n") + "\n")?;
+    Ok(())
+}
+
+impl EditorState {
+    pub fn show_start_screen(&mut self, recent: Vec<PathBuf>, mut sessions: Vec<String>) {
+        let mut recent: Vec<PathBuf> = recent.into_iter().filter(|path| path.exists()).collect();
+        recent.truncate(START_SCREEN_FILES);
+        sessions.truncate(SESSION_KEYS.len());
+        self.start_screen = Some(StartScreen { recent, sessions });
+    }
+
+    pub fn is_start_screen(&self) -> bool {
+        self.start_screen.is_some()
+    }
+
+    pub fn close_start_screen(&mut self) {
+        self.start_screen = None;
+    }
+
+    pub fn start_screen_lines(&self) -> Vec<String> {
+        let Some(StartScreen { recent, sessions }) = &self.start_screen else {
+            return Vec::new();
+        };
+
+        let mut lines = vec![
+            "ce".to_string(),
+            String::new(),
+            "Recent files".to_string(),
+            String::new(),
+        ];
+        if recent.is_empty() {
+            lines.push("(none)".to_string());
+        }
+        for (i, path) in recent.iter().enumerate() {
+            lines.push(format!("[{}] {}", i + 1, display_name(Some(path))));
+        }
+        if !sessions.is_empty() {
+            lines.push(String::new());
+            lines.push("Sessions".to_string());
+            lines.push(String::new());
+        }
+        for (key, name) in SESSION_KEYS.iter().zip(sessions) {
+            lines.push(format!("[{}] {}", key, name));
+        }
+        lines.push(String::new());
+        lines.push("[e] empty buffer  [q] quit".to_string());
+        lines
+    }
+
+    pub fn on_start_screen_key(&mut self, c: char) -> bool {
+        let Some(screen) = self.start_screen.take() else {
+            return false;
+        };
+
+        match c {
+            '1'..='9' => {
+                let index = c as usize - '1' as usize;
+                match screen.recent.get(index) {
+                    Some(path) => {
+                        if let Err(err) = self.reload_buffer(path.clone()) {
+                            self.set_message(err.to_string());
+                        }
+                    }
+                    None => {
+                        self.start_screen = Some(screen);
+                    }
+                }
+                true
+            }
+            c if SESSION_KEYS.contains(&c) => {
+                let index = SESSION_KEYS.iter().position(|key| *key == c).unwrap();
+                match screen.sessions.get(index) {
+                    Some(name) => {
+                        if let Err(err) = self.load_session(name) {
+                            self.set_message(err.to_string());
+                        }
+                    }
+                    None => {
+                        self.start_screen = Some(screen);
+                    }
+                }
+                true
+            }
+            'e' => true,
+            'q' => {
+                self.quit();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_recent_files() {
+        let recent = vec![PathBuf::from("/a"), PathBuf::from("/b")];
+        let merged = merge_recent_files(recent, vec![PathBuf::from("/b"), PathBuf::from("/c")]);
+        assert_eq!(
+            merged,
+            vec![
+                PathBuf::from("/b"),
+                PathBuf::from("/c"),
+                PathBuf::from("/a")
+            ]
+        );
+
+        let many = (0..30).map(|i| PathBuf::from(format!("/{}", i))).collect();
+        assert_eq!(merge_recent_files(Vec::new(), many).len(), RECENT_FILES_MAX);
+    }
+
+    #[test]
+    fn test_start_screen_keys() {
+        let mut state = EditorState::new(String::new(), None);
+        state.show_start_screen(Vec::new(), vec!["work".to_string()]);
+        assert!(state.start_screen_lines().contains(&"[a] work".to_string()));
+
+        assert!(state.on_start_screen_key('1'));
+        assert!(state.is_start_screen());
+        assert!(state.on_start_screen_key('b'));
+        assert!(state.is_start_screen());
+        assert!(!state.on_start_screen_key('i'));
+        assert!(!state.is_start_screen());
+    }
+}
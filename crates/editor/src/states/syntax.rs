@@ -0,0 +1,745 @@
+use super::{buf::CodeBuffer, filetype::FileType, EditorState};
+
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum Scope {
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Type,
+    Constant,
+    Function,
+    Macro,
+    Variable,
+    Key,
+    Tag,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+}
+
+impl Scope {
+    pub const ALL: &'static [Scope] = &[
+        Scope::Comment,
+        Scope::String,
+        Scope::Number,
+        Scope::Keyword,
+        Scope::Type,
+        Scope::Constant,
+        Scope::Function,
+        Scope::Macro,
+        Scope::Variable,
+        Scope::Key,
+        Scope::Tag,
+        Scope::Heading,
+        Scope::Emphasis,
+        Scope::Code,
+        Scope::Link,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scope::Comment => "comment",
+            Scope::String => "string",
+            Scope::Number => "number",
+            Scope::Keyword => "keyword",
+            Scope::Type => "type",
+            Scope::Constant => "constant",
+            Scope::Function => "function",
+            Scope::Macro => "macro",
+            Scope::Variable => "variable",
+            Scope::Key => "key",
+            Scope::Tag => "tag",
+            Scope::Heading => "heading",
+            Scope::Emphasis => "emphasis",
+            Scope::Code => "code",
+            Scope::Link => "link",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Scope> {
+        Self::ALL.iter().copied().find(|scope| scope.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Token {
+    pub start: usize,
+    pub end: usize,
+    pub scope: Scope,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LineState {
+    #[default]
+    Normal,
+    Comment(usize),
+    String(usize),
+    CodeBlock,
+}
+
+pub struct StringRule {
+    pub open: &'static str,
+    pub close: &'static str,
+    pub escape: bool,
+    pub multiline: bool,
+}
+
+pub struct SyntaxDef {
+    pub line_comments: &'static [&'static str],
+    pub block_comment: Option<(&'static str, &'static str)>,
+    pub nested_comments: bool,
+    pub word_comments: bool,
+    // Longest first
+    pub strings: &'static [StringRule],
+    pub keywords: &'static [&'static str],
+    pub types: &'static [&'static str],
+    pub constants: &'static [&'static str],
+    pub capitalized_types: bool,
+    pub ident_chars: &'static str,
+    pub char_literals: bool,
+    pub bang_macros: bool,
+    pub attributes: bool,
+    pub line_macro: Option<char>,
+    pub decorator: Option<char>,
+    pub variable_prefix: Option<char>,
+    pub key_suffix: Option<char>,
+    pub keys_anywhere: bool,
+    pub section_headers: bool,
+    pub tags: bool,
+}
+
+pub const PLAIN: SyntaxDef = SyntaxDef {
+    line_comments: &[],
+    block_comment: None,
+    nested_comments: false,
+    word_comments: false,
+    strings: &[],
+    keywords: &[],
+    types: &[],
+    constants: &[],
+    capitalized_types: false,
+    ident_chars: "",
+    char_literals: false,
+    bang_macros: false,
+    attributes: false,
+    line_macro: None,
+    decorator: None,
+    variable_prefix: None,
+    key_suffix: None,
+    keys_anywhere: false,
+    section_headers: false,
+    tags: false,
+};
+
+fn starts_with(chars: &[char], i: usize, pat: &str) -> bool {
+    (i..)
+        .zip(pat.chars())
+        .all(|(j, p)| chars.get(j) == Some(&p))
+}
+
+fn next_non_space(chars: &[char], i: usize) -> Option<char> {
+    chars[i.min(chars.len())..]
+        .iter()
+        .copied()
+        .find(|c| !c.is_whitespace())
+}
+
+struct Lexer<'a> {
+    def: &'a SyntaxDef,
+    chars: Vec<char>,
+    tokens: Vec<Token>,
+}
+
+impl Lexer<'_> {
+    fn push(&mut self, start: usize, end: usize, scope: Scope) {
+        if start < end {
+            self.tokens.push(Token { start, end, scope });
+        }
+    }
+
+    fn is_ident(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || self.def.ident_chars.contains(c)
+    }
+
+    fn ident_end(&self, mut i: usize) -> usize {
+        while i < self.chars.len() && self.is_ident(self.chars[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    fn scan_comment(&self, mut i: usize, mut depth: usize) -> (usize, LineState) {
+        let (open, close) = self.def.block_comment.unwrap_or_default();
+        while i < self.chars.len() {
+            if starts_with(&self.chars, i, close) {
+                i += close.chars().count();
+                depth -= 1;
+                if depth == 0 {
+                    return (i, LineState::Normal);
+                }
+            } else if self.def.nested_comments && starts_with(&self.chars, i, open) {
+                i += open.chars().count();
+                depth += 1;
+            } else {
+                i += 1;
+            }
+        }
+        (i, LineState::Comment(depth))
+    }
+
+    fn scan_string(&self, mut i: usize, index: usize) -> (usize, LineState) {
+        let rule = &self.def.strings[index];
+        while i < self.chars.len() {
+            if rule.escape && self.chars[i] == '\\' {
+                i += 2;
+            } else if starts_with(&self.chars, i, rule.close) {
+                return (i + rule.close.chars().count(), LineState::Normal);
+            } else {
+                i += 1;
+            }
+        }
+        let state = if rule.multiline {
+            LineState::String(index)
+        } else {
+            LineState::Normal
+        };
+        (self.chars.len(), state)
+    }
+
+    fn char_literal_end(&self, i: usize) -> Option<usize> {
+        match self.chars.get(i + 1) {
+            Some('\\') => (i + 3..self.chars.len())
+                .find(|&j| self.chars[j] == '\'')
+                .map(|j| j + 1),
+            Some(_) if self.chars.get(i + 2) == Some(&'\'') => Some(i + 3),
+            _ => None,
+        }
+    }
+
+    fn bracket_end(&self, mut i: usize) -> usize {
+        let mut depth = 0;
+        while i < self.chars.len() {
+            match self.chars[i] {
+                '[' => depth += 1,
+                ']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        i
+    }
+
+    fn is_key(&self, start: usize, end: usize) -> bool {
+        let Some(suffix) = self.def.key_suffix else {
+            return false;
+        };
+        if next_non_space(&self.chars, end) != Some(suffix) {
+            return false;
+        }
+        self.def.keys_anywhere
+            || self.chars[..start]
+                .iter()
+                .all(|c| c.is_whitespace() || *c == '-' || *c == '{' || *c == ',')
+    }
+
+    fn word_scope(&self, word: &str, start: usize, end: usize) -> Option<Scope> {
+        let def = self.def;
+        if def.keywords.contains(&word) {
+            return Some(Scope::Keyword);
+        }
+        if def.constants.contains(&word) {
+            return Some(Scope::Constant);
+        }
+        if def.types.contains(&word)
+            || (def.capitalized_types && word.starts_with(|c: char| c.is_uppercase()))
+        {
+            return Some(Scope::Type);
+        }
+        if self.is_key(start, end) {
+            return Some(Scope::Key);
+        }
+        if next_non_space(&self.chars, end) == Some('(') {
+            return Some(Scope::Function);
+        }
+        None
+    }
+
+    fn run(&mut self, state: LineState) -> LineState {
+        let len = self.chars.len();
+        let mut i = 0;
+
+        match state {
+            LineState::Comment(depth) => {
+                let (end, state) = self.scan_comment(0, depth);
+                self.push(0, end, Scope::Comment);
+                if state != LineState::Normal {
+                    return state;
+                }
+                i = end;
+            }
+            LineState::String(index) if index < self.def.strings.len() => {
+                let (end, state) = self.scan_string(0, index);
+                self.push(0, end, Scope::String);
+                if state != LineState::Normal {
+                    return state;
+                }
+                i = end;
+            }
+            _ => {}
+        }
+
+        let first = self.chars.iter().position(|c| !c.is_whitespace());
+        let def = self.def;
+
+        while i < len {
+            let c = self.chars[i];
+            if c.is_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            if let Some((open, _)) = def.block_comment {
+                if starts_with(&self.chars, i, open) {
+                    let (end, state) = self.scan_comment(i + open.chars().count(), 1);
+                    self.push(i, end, Scope::Comment);
+                    if state != LineState::Normal {
+                        return state;
+                    }
+                    i = end;
+                    continue;
+                }
+            }
+
+            if def
+                .line_comments
+                .iter()
+                .any(|prefix| starts_with(&self.chars, i, prefix))
+                && (!def.word_comments || i == 0 || self.chars[i - 1].is_whitespace())
+            {
+                self.push(i, len, Scope::Comment);
+                break;
+            }
+
+            if let Some(index) = def
+                .strings
+                .iter()
+                .position(|rule| starts_with(&self.chars, i, rule.open))
+            {
+                let open = def.strings[index].open.chars().count();
+                let (end, state) = self.scan_string(i + open, index);
+                let scope = if state == LineState::Normal && self.is_key(i, end) {
+                    Scope::Key
+                } else {
+                    Scope::String
+                };
+                self.push(i, end, scope);
+                if state != LineState::Normal {
+                    return state;
+                }
+                i = end;
+                continue;
+            }
+
+            if def.char_literals && c == '\'' {
+                if let Some(end) = self.char_literal_end(i) {
+                    self.push(i, end, Scope::String);
+                    i = end;
+                } else {
+                    i = self.ident_end(i + 1);
+                }
+                continue;
+            }
+
+            if Some(i) == first && def.line_macro == Some(c) {
+                self.push(i, len, Scope::Macro);
+                break;
+            }
+
+            if Some(i) == first && def.section_headers && c == '[' {
+                let end = self.bracket_end(i);
+                self.push(i, end, Scope::Heading);
+                i = end;
+                continue;
+            }
+
+            if def.attributes
+                && c == '#'
+                && (starts_with(&self.chars, i + 1, "[") || starts_with(&self.chars, i + 1, "!["))
+            {
+                let end = self.bracket_end(i);
+                self.push(i, end, Scope::Macro);
+                i = end;
+                continue;
+            }
+
+            if def.decorator == Some(c) && self.chars.get(i + 1).is_some_and(|c| c.is_alphabetic())
+            {
+                let mut end = i + 1;
+                while end < len && (self.is_ident(self.chars[end]) || self.chars[end] == '.') {
+                    end += 1;
+                }
+                self.push(i, end, Scope::Macro);
+                i = end;
+                continue;
+            }
+
+            if def.variable_prefix == Some(c) {
+                let end = match self.chars.get(i + 1) {
+                    Some('{') | Some('(') => {
+                        let close = if self.chars[i + 1] == '{' { '}' } else { ')' };
+                        (i + 2..len)
+                            .find(|&j| self.chars[j] == close)
+                            .map_or(len, |j| j + 1)
+                    }
+                    Some(c) if self.is_ident(*c) => self.ident_end(i + 1),
+                    Some(c) if "#?@*!$".contains(*c) => i + 2,
+                    _ => i + 1,
+                };
+                if end > i + 1 {
+                    self.push(i, end, Scope::Variable);
+                }
+                i = end;
+                continue;
+            }
+
+            if def.tags && c == '<' {
+                let start = if self.chars.get(i + 1) == Some(&'/') {
+                    i + 2
+                } else {
+                    i + 1
+                };
+                if self.chars.get(start).is_some_and(|c| c.is_alphabetic()) {
+                    let end = self.ident_end(start);
+                    self.push(i, end, Scope::Tag);
+                    i = end;
+                    continue;
+                }
+            }
+            if def.tags && (c == '>' || starts_with(&self.chars, i, "/>")) {
+                let end = if c == '>' { i + 1 } else { i + 2 };
+                self.push(i, end, Scope::Tag);
+                i = end;
+                continue;
+            }
+
+            if c.is_ascii_digit() {
+                let mut end = i + 1;
+                while end < len {
+                    let d = self.chars[end];
+                    let fraction =
+                        d == '.' && self.chars.get(end + 1).is_some_and(|c| c.is_ascii_digit());
+                    if !(d.is_alphanumeric() || d == '_' || fraction) {
+                        break;
+                    }
+                    end += 1;
+                }
+                self.push(i, end, Scope::Number);
+                i = end;
+                continue;
+            }
+
+            if self.is_ident(c) {
+                let end = self.ident_end(i);
+                let word: String = self.chars[i..end].iter().collect();
+                if def.bang_macros
+                    && self.chars.get(end) == Some(&'!')
+                    && self.chars.get(end + 1) != Some(&'=')
+                {
+                    self.push(i, end + 1, Scope::Macro);
+                    i = end + 1;
+                    continue;
+                }
+                if let Some(scope) = self.word_scope(&word, i, end) {
+                    self.push(i, end, scope);
+                }
+                i = end;
+                continue;
+            }
+
+            i += 1;
+        }
+
+        LineState::Normal
+    }
+}
+
+pub fn tokenize(def: &SyntaxDef, line: &str, state: LineState) -> (Vec<Token>, LineState) {
+    let mut lexer = Lexer {
+        def,
+        chars: line.chars().collect(),
+        tokens: Vec::new(),
+    };
+    let state = lexer.run(state);
+    (lexer.tokens, state)
+}
+
+fn find(chars: &[char], i: usize, pat: &str) -> Option<usize> {
+    (i..chars.len()).find(|&j| starts_with(chars, j, pat))
+}
+
+pub fn tokenize_markdown(line: &str, state: LineState) -> (Vec<Token>, LineState) {
+    let chars: Vec<char> = line.chars().collect();
+    let len = chars.len();
+    let mut tokens = Vec::new();
+    let mut push = |start: usize, end: usize, scope: Scope| {
+        if start < end {
+            tokens.push(Token { start, end, scope });
+        }
+    };
+
+    let first = chars.iter().position(|c| !c.is_whitespace()).unwrap_or(len);
+    let fence = starts_with(&chars, first, "```") || starts_with(&chars, first, "~~~");
+    if state == LineState::CodeBlock {
+        push(0, len, Scope::Code);
+        let state = if fence {
+            LineState::Normal
+        } else {
+            LineState::CodeBlock
+        };
+        return (tokens, state);
+    }
+    if fence {
+        push(first, len, Scope::Code);
+        return (tokens, LineState::CodeBlock);
+    }
+    match chars.get(first) {
+        Some('#') => {
+            push(first, len, Scope::Heading);
+            return (tokens, LineState::Normal);
+        }
+        Some('>') => {
+            push(first, len, Scope::Comment);
+            return (tokens, LineState::Normal);
+        }
+        _ => {}
+    }
+
+    let mut i = first;
+    let digits = chars[first..]
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    let marker = match chars.get(first) {
+        Some('-' | '*' | '+') => 1,
+        Some(_) if digits > 0 && matches!(chars.get(first + digits), Some('.' | ')')) => digits + 1,
+        _ => 0,
+    };
+    if marker > 0 && chars.get(first + marker) == Some(&' ') {
+        push(first, first + marker, Scope::Keyword);
+        i = first + marker;
+    }
+
+    while i < len {
+        let c = chars[i];
+        match c {
+            '`' => {
+                if let Some(end) = find(&chars, i + 1, "`") {
+                    push(i, end + 1, Scope::Code);
+                    i = end + 1;
+                    continue;
+                }
+            }
+            '*' | '_' if c == '*' || i == 0 || !chars[i - 1].is_alphanumeric() => {
+                let delim: String = if chars.get(i + 1) == Some(&c) {
+                    [c, c].iter().collect()
+                } else {
+                    c.to_string()
+                };
+                let open = delim.chars().count();
+                let inner = chars.get(i + open).is_some_and(|c| !c.is_whitespace());
+                if let Some(end) = find(&chars, i + open, &delim).filter(|_| inner) {
+                    push(i, end + open, Scope::Emphasis);
+                    i = end + open;
+                    continue;
+                }
+            }
+            '[' => {
+                if let Some(close) = find(&chars, i + 1, "]") {
+                    if chars.get(close + 1) == Some(&'(') {
+                        if let Some(end) = find(&chars, close + 2, ")") {
+                            push(i, end + 1, Scope::Link);
+                            i = end + 1;
+                            continue;
+                        }
+                    }
+                }
+            }
+            '<' if starts_with(&chars, i + 1, "http") => {
+                if let Some(end) = find(&chars, i + 1, ">") {
+                    push(i, end + 1, Scope::Link);
+                    i = end + 1;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    (tokens, LineState::Normal)
+}
+
+#[derive(Clone)]
+struct HighlightedLine {
+    text: String,
+    tokens: Vec<Token>,
+    end: LineState,
+}
+
+#[derive(Clone, Default)]
+pub struct Highlighter {
+    filetype: FileType,
+    lines: Vec<HighlightedLine>,
+}
+
+impl Highlighter {
+    // Re-lex only from the first changed line
+    pub fn update(&mut self, buf: &CodeBuffer, filetype: FileType, end: usize) {
+        if filetype != self.filetype {
+            self.filetype = filetype;
+            self.lines.clear();
+        }
+        let def = filetype.syntax();
+        if def.is_none() && filetype != FileType::Markdown {
+            self.lines.clear();
+            return;
+        }
+
+        let end = end.min(buf.line_count());
+        let checked = end.min(self.lines.len());
+        if let Some(y) = (0..checked).find(|&y| self.lines[y].text != buf.get_line(y)) {
+            self.lines.truncate(y);
+        }
+
+        while self.lines.len() < end {
+            let y = self.lines.len();
+            let state = self.lines.last().map_or(LineState::Normal, |line| line.end);
+            let text = buf.get_line(y);
+            let (tokens, end) = match def {
+                Some(def) => tokenize(def, text, state),
+                None => tokenize_markdown(text, state),
+            };
+            self.lines.push(HighlightedLine {
+                text: text.to_string(),
+                tokens,
+                end,
+            });
+        }
+    }
+
+    pub fn tokens(&self, y: usize) -> &[Token] {
+        self.lines.get(y).map_or(&[], |line| &line.tokens)
+    }
+}
+
+impl EditorState {
+    pub fn update_highlight(&mut self) {
+        let end = self.offset.y + self.view_height();
+        self.highlighter
+            .update(&self.buf, self.options.filetype, end);
+    }
+
+    fn char_scopes(&self, y: usize, len: usize) -> Vec<Option<Scope>> {
+        let mut scopes = vec![None; len];
+        for token in self.highlighter.tokens(y) {
+            for scope in scopes.iter_mut().take(token.end).skip(token.start) {
+                *scope = Some(token.scope);
+            }
+        }
+        scopes
+    }
+
+    pub fn visible_scopes(&self) -> Vec<Vec<Option<Scope>>> {
+        self.visible_positions()
+            .into_iter()
+            .map(|(y, positions)| {
+                let scopes = self.char_scopes(y, self.buf.get_line(y).chars().count());
+                positions
+                    .into_iter()
+                    .map(|x| x.and_then(|x| scopes[x]))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(filetype: FileType, line: &str) -> Vec<(String, Scope)> {
+        let (tokens, _) = tokenize(filetype.syntax().unwrap(), line, LineState::Normal);
+        let chars: Vec<char> = line.chars().collect();
+        tokens
+            .iter()
+            .map(|t| (chars[t.start..t.end].iter().collect(), t.scope))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_rust() {
+        assert_eq!(
+            scopes(FileType::Rust, "let s: &'a str = \"x\\\"\"; // end"),
+            vec![
+                ("let".to_string(), Scope::Keyword),
+                ("str".to_string(), Scope::Type),
+                ("\"x\\\"\"".to_string(), Scope::String),
+                ("// end".to_string(), Scope::Comment),
+            ]
+        );
+        assert_eq!(
+            scopes(FileType::Rust, "println!('c', Foo::new(1.5))"),
+            vec![
+                ("println!".to_string(), Scope::Macro),
+                ("'c'".to_string(), Scope::String),
+                ("Foo".to_string(), Scope::Type),
+                ("new".to_string(), Scope::Function),
+                ("1.5".to_string(), Scope::Number),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_incremental() {
+        let mut state = EditorState::new("a /* b\nc */ d\ne\n".to_string(), None);
+        state.options.set_filetype(FileType::C);
+        state.update_highlight();
+        assert_eq!(state.highlighter.lines.len(), 4);
+        assert_eq!(state.highlighter.tokens(1)[0].scope, Scope::Comment);
+
+        state.buf.set_line(1, "c d".to_string());
+        state.highlighter.update(&state.buf, FileType::C, 2);
+        assert_eq!(state.highlighter.lines.len(), 2);
+        assert_eq!(
+            state.highlighter.tokens(1),
+            &[Token {
+                start: 0,
+                end: 3,
+                scope: Scope::Comment
+            }]
+        );
+
+        state.buf.set_line(0, "a b".to_string());
+        state.update_highlight();
+        assert!(state.highlighter.tokens(1).is_empty());
+    }
+
+    #[test]
+    fn test_tokenize_markdown() {
+        let (tokens, state) = tokenize_markdown("```rust", LineState::Normal);
+        assert_eq!(tokens[0].scope, Scope::Code);
+        assert_eq!(state, LineState::CodeBlock);
+        let (_, state) = tokenize_markdown("```", state);
+        assert_eq!(state, LineState::Normal);
+        let (tokens, _) = tokenize_markdown("- see `x` and [a](b)", state);
+        let found: Vec<Scope> = tokens.iter().map(|t| t.scope).collect();
+        assert_eq!(found, vec![Scope::Keyword, Scope::Code, Scope::Link]);
+    }
+}
@@ -0,0 +1,573 @@
+use anyhow::{anyhow, Result};
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Tree};
+use utils::types::Vec2;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{
+        buf::CodeBuffer, filetype::FileType, mode::EditorMode, operator::TextRange, EditorState,
+    },
+};
+
+const FUNCTION_KINDS: &[&str] = &[
+    "function_item",
+    "function_definition",
+    "function_declaration",
+    "function_expression",
+    "method_declaration",
+    "method_definition",
+    "arrow_function",
+    "generator_function_declaration",
+    "func_literal",
+    "closure_expression",
+    "lambda",
+];
+
+const CLASS_KINDS: &[&str] = &[
+    "struct_item",
+    "enum_item",
+    "union_item",
+    "trait_item",
+    "impl_item",
+    "class_definition",
+    "class_declaration",
+    "class_specifier",
+    "struct_specifier",
+    "interface_declaration",
+    "type_declaration",
+];
+
+const ARGUMENT_LIST_KINDS: &[&str] = &[
+    "arguments",
+    "argument_list",
+    "parameters",
+    "parameter_list",
+    "formal_parameters",
+    "type_arguments",
+    "type_parameters",
+    "closure_parameters",
+];
+
+fn language(filetype: FileType) -> Option<Language> {
+    let language = match filetype {
+        FileType::Rust => tree_sitter_rust::LANGUAGE,
+        FileType::C => tree_sitter_c::LANGUAGE,
+        FileType::Cpp => tree_sitter_cpp::LANGUAGE,
+        FileType::Go => tree_sitter_go::LANGUAGE,
+        FileType::JavaScript => tree_sitter_javascript::LANGUAGE,
+        FileType::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        FileType::Python => tree_sitter_python::LANGUAGE,
+        FileType::Shell => tree_sitter_bash::LANGUAGE,
+        FileType::Json => tree_sitter_json::LANGUAGE,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+fn point_at(text: &[u8], byte: usize) -> Point {
+    let before = &text[..byte];
+    let row = before.iter().filter(|&&b| b == b'\n').count();
+    let column = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(byte, |i| byte - i - 1);
+    Point { row, column }
+}
+
+// The common prefix and suffix are kept; everything between is replaced
+fn diff_edit(old: &str, new: &str) -> InputEdit {
+    let (old, new) = (old.as_bytes(), new.as_bytes());
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_end = old.len() - suffix;
+    let new_end = new.len() - suffix;
+    InputEdit {
+        start_byte: prefix,
+        old_end_byte: old_end,
+        new_end_byte: new_end,
+        start_position: point_at(old, prefix),
+        old_end_position: point_at(old, old_end),
+        new_end_position: point_at(new, new_end),
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct SyntaxTree {
+    filetype: FileType,
+    text: String,
+    version: usize,
+    tree: Option<Tree>,
+}
+
+impl SyntaxTree {
+    // Tell the tree what changed so only that part is reparsed
+    pub fn update(&mut self, buf: &CodeBuffer, filetype: FileType) {
+        let Some(language) = language(filetype) else {
+            self.tree = None;
+            self.text.clear();
+            return;
+        };
+        if filetype != self.filetype {
+            self.filetype = filetype;
+            self.tree = None;
+        }
+
+        if self.tree.is_some() && buf.version() == self.version {
+            return;
+        }
+        let text = buf.to_string();
+        if let Some(tree) = &mut self.tree {
+            tree.edit(&diff_edit(&self.text, &text));
+        }
+
+        let mut parser = Parser::new();
+        if parser.set_language(&language).is_err() {
+            self.tree = None;
+            return;
+        }
+        self.tree = parser.parse(&text, self.tree.as_ref());
+        self.text = text;
+        self.version = buf.version();
+    }
+}
+
+fn position(point: Point) -> Vec2<usize> {
+    Vec2::new(point.column, point.row)
+}
+
+fn point(pos: Vec2<usize>) -> Point {
+    Point {
+        row: pos.y,
+        column: pos.x,
+    }
+}
+
+fn enclosing<'t>(mut node: Node<'t>, kinds: &[&str]) -> Option<Node<'t>> {
+    loop {
+        if kinds.contains(&node.kind()) {
+            return Some(node);
+        }
+        node = node.parent()?;
+    }
+}
+
+fn inner_range(node: Node) -> Option<(Point, Point)> {
+    let body = node.child_by_field_name("body").unwrap_or(node);
+    let count = body.named_child_count();
+    let first = body.named_child(0)?;
+    let last = body.named_child(count - 1)?;
+    Some((first.start_position(), last.end_position()))
+}
+
+fn argument_outer_range(node: Node) -> (Point, Point) {
+    let (start, end) = (node.start_position(), node.end_position());
+    if let Some(comma) = node.next_sibling().filter(|n| n.kind() == ",") {
+        let end = match comma.next_sibling().filter(|n| n.is_named()) {
+            Some(next) => next.start_position(),
+            None => comma.end_position(),
+        };
+        return (start, end);
+    }
+    if let Some(comma) = node.prev_sibling().filter(|n| n.kind() == ",") {
+        let start = comma
+            .prev_sibling()
+            .map_or(comma.start_position(), |prev| prev.end_position());
+        return (start, end);
+    }
+    (start, end)
+}
+
+fn tree_lines<'t>(
+    node: Node<'t>,
+    field: Option<&str>,
+    depth: usize,
+    lines: &mut Vec<(Node<'t>, String)>,
+) {
+    let (start, end) = (node.start_position(), node.end_position());
+    let field = field
+        .map(|field| format!("{}: ", field))
+        .unwrap_or_default();
+    lines.push((
+        node,
+        format!(
+            "{}{}{} [{}, {}] - [{}, {}]",
+            "  ".repeat(depth),
+            field,
+            node.kind(),
+            start.row,
+            start.column,
+            end.row,
+            end.column
+        ),
+    ));
+
+    let mut cursor = node.walk();
+    if !cursor.goto_first_child() {
+        return;
+    }
+    loop {
+        let child = cursor.node();
+        if child.is_named() {
+            tree_lines(child, cursor.field_name(), depth + 1, lines);
+        }
+        if !cursor.goto_next_sibling() {
+            break;
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TextObject {
+    Function,
+    Class,
+    Argument,
+}
+
+impl EditorState {
+    pub fn update_syntax_tree(&mut self) {
+        self.syntax_tree.update(&self.buf, self.options.filetype);
+    }
+
+    fn current_tree(&mut self) -> Result<Tree> {
+        self.update_syntax_tree();
+        self.syntax_tree
+            .tree
+            .clone()
+            .ok_or_else(|| anyhow!("No syntax tree for filetype: {}", self.options.filetype))
+    }
+
+    fn selected_points(&self) -> (Point, Point) {
+        let (x, y) = self.cursor.get_display(&self.buf);
+        let cursor = Vec2::new(x, y);
+        let start = if self.get_mode() == EditorMode::Visual {
+            self.visual_start
+        } else {
+            cursor
+        };
+        let range = TextRange::from_visual(&self.buf, start, cursor);
+        (point(range.start), point(range.end))
+    }
+
+    fn select_points(&mut self, start: Point, end: Point) {
+        let end = if end.column > 0 {
+            Vec2::new(end.column - 1, end.row)
+        } else if end.row > start.row {
+            let y = end.row - 1;
+            Vec2::new(self.buf.line_length(y).saturating_sub(1), y)
+        } else {
+            position(end)
+        };
+
+        let visual = self.get_mode() == EditorMode::Visual;
+        match self.pending_op.as_mut() {
+            Some(op) if !visual => op.start = position(start),
+            _ => {
+                self.set_mode(EditorMode::Visual);
+                self.visual_start = position(start);
+            }
+        }
+        self.cursor.move_y_to(&self.buf, end.y);
+        self.cursor.move_x_to(&self.buf, end.x);
+    }
+
+    fn cancel_selection(&mut self, message: &str) {
+        self.pending_op = None;
+        self.set_message(message.to_string());
+    }
+
+    // af, if, ac, ic, aa, ia
+    fn select_text_object(&mut self, object: TextObject, inner: bool) -> Result<()> {
+        let tree = self.current_tree()?;
+        let (start, end) = self.selected_points();
+        let found = tree
+            .root_node()
+            .descendant_for_point_range(start, end)
+            .and_then(|node| match object {
+                TextObject::Function => enclosing(node, FUNCTION_KINDS),
+                TextObject::Class => enclosing(node, CLASS_KINDS),
+                TextObject::Argument => {
+                    let mut node = node;
+                    while !node
+                        .parent()
+                        .is_some_and(|parent| ARGUMENT_LIST_KINDS.contains(&parent.kind()))
+                    {
+                        node = node.parent()?;
+                    }
+                    Some(node).filter(|node| node.is_named())
+                }
+            });
+        let Some(node) = found else {
+            self.cancel_selection("No text object found");
+            return Ok(());
+        };
+
+        let range = match (object, inner) {
+            (TextObject::Argument, true) => Some((node.start_position(), node.end_position())),
+            (TextObject::Argument, false) => Some(argument_outer_range(node)),
+            (_, true) => inner_range(node),
+            (_, false) => Some((node.start_position(), node.end_position())),
+        };
+        match range {
+            Some((start, end)) => self.select_points(start, end),
+            None => self.cancel_selection("Empty text object"),
+        }
+        Ok(())
+    }
+
+    fn expand_selection(&mut self) -> Result<()> {
+        let tree = self.current_tree()?;
+        let (start, end) = self.selected_points();
+        let mut node = tree
+            .root_node()
+            .named_descendant_for_point_range(start, end);
+        while let Some(current) = node {
+            if (current.start_position(), current.end_position()) != (start, end) {
+                break;
+            }
+            node = current.parent();
+        }
+
+        match node {
+            Some(node) => self.select_points(node.start_position(), node.end_position()),
+            None => self.cancel_selection("Already at the root node"),
+        }
+        Ok(())
+    }
+
+    fn shrink_selection(&mut self) -> Result<()> {
+        let tree = self.current_tree()?;
+        let (start, end) = self.selected_points();
+        let (x, y) = self.cursor.get_display(&self.buf);
+        let cursor = point(Vec2::new(x, y));
+
+        let Some(node) = tree
+            .root_node()
+            .named_descendant_for_point_range(start, end)
+        else {
+            return Ok(());
+        };
+        let mut walker = node.walk();
+        let children: Vec<Node> = node.named_children(&mut walker).collect();
+        let child = children
+            .iter()
+            .find(|child| child.start_position() <= cursor && cursor < child.end_position())
+            .or(children.first());
+
+        match child {
+            Some(child) => self.select_points(child.start_position(), child.end_position()),
+            None => self.cancel_selection("No smaller node"),
+        }
+        Ok(())
+    }
+
+    fn jump_to_function(&mut self, forward: bool) -> Result<()> {
+        let tree = self.current_tree()?;
+        let mut starts = Vec::new();
+        let mut cursor = tree.walk();
+        'walk: loop {
+            if FUNCTION_KINDS.contains(&cursor.node().kind()) {
+                starts.push(cursor.node().start_position());
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    break 'walk;
+                }
+            }
+        }
+
+        let (x, y) = self.cursor.get_display(&self.buf);
+        let current = point(Vec2::new(x, y));
+        let count = self.get_count();
+        let target = if forward {
+            starts
+                .iter()
+                .filter(|&&start| start > current)
+                .nth(count - 1)
+        } else {
+            starts
+                .iter()
+                .rev()
+                .filter(|&&start| start < current)
+                .nth(count - 1)
+        };
+
+        let Some(target) = target else {
+            return Err(anyhow!("No more functions"));
+        };
+        self.cursor.move_y_to(&self.buf, target.row);
+        self.cursor.move_x_to(&self.buf, target.column);
+        Ok(())
+    }
+
+    fn ex_inspect_tree(&mut self, _args: &str) -> Result<()> {
+        let tree = self.current_tree()?;
+        let (start, end) = self.selected_points();
+        let root = tree.root_node();
+        let target = root
+            .named_descendant_for_point_range(start, end)
+            .unwrap_or(root);
+        let top = root.child_with_descendant(target).unwrap_or(root);
+
+        let mut lines = Vec::new();
+        tree_lines(top, None, 0, &mut lines);
+        let row = lines
+            .iter()
+            .position(|(node, _)| *node == target)
+            .unwrap_or(0);
+        let text: Vec<String> = lines.into_iter().map(|(_, line)| line).collect();
+
+        self.split_window(true, None)?;
+        let buffer = self.new_buffer();
+        self.switch_buffer(buffer);
+        self.clear_message();
+        self.options.set_filetype(FileType::Text);
+        self.set_buffer_text(buffer, text.join("\n"));
+        self.cursor.move_y_to(&self.buf, row);
+        self.cursor.move_x_to(&self.buf, 0);
+        Ok(())
+    }
+
+    pub fn register_syntax_tree_cmds(cmds: &mut EditorCommand) {
+        type SyntaxTreeCommand = fn(&mut EditorState) -> Result<()>;
+        let syntax_tree_cmds: &[(&str, SyntaxTreeCommand)] = &[
+            ("select_function_outer", |editor| {
+                editor.select_text_object(TextObject::Function, false)
+            }),
+            ("select_function_inner", |editor| {
+                editor.select_text_object(TextObject::Function, true)
+            }),
+            ("select_class_outer", |editor| {
+                editor.select_text_object(TextObject::Class, false)
+            }),
+            ("select_class_inner", |editor| {
+                editor.select_text_object(TextObject::Class, true)
+            }),
+            ("select_argument_outer", |editor| {
+                editor.select_text_object(TextObject::Argument, false)
+            }),
+            ("select_argument_inner", |editor| {
+                editor.select_text_object(TextObject::Argument, true)
+            }),
+            ("expand_selection", EditorState::expand_selection),
+            ("shrink_selection", EditorState::shrink_selection),
+            ("next_function", |editor| editor.jump_to_function(true)),
+            ("prev_function", |editor| editor.jump_to_function(false)),
+        ];
+
+        for (name, f) in syntax_tree_cmds {
+            let f = *f;
+            cmds.register(
+                name,
+                Box::new(move |editor| {
+                    if let Err(err) = f(editor) {
+                        editor.cancel_selection(&err.to_string());
+                    }
+                }),
+            );
+        }
+
+        cmds.register_with_args(
+            "InspectTree",
+            Box::new(|editor, args| {
+                if let Err(err) = editor.ex_inspect_tree(args) {
+                    editor.set_message(err.to_string());
+                }
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\nfn main() {\n    add(1, 2);\n}\n";
+
+    fn rust_state() -> EditorState {
+        let mut state = EditorState::new(SOURCE.to_string(), None);
+        state.options.set_filetype(FileType::Rust);
+        state
+    }
+
+    #[test]
+    fn test_incremental_reparse() {
+        let mut state = rust_state();
+        state.update_syntax_tree();
+        state.buf.insert_str("    let c = 1;", 0, 1);
+        state.buf.split_line(14, 1);
+        state.update_syntax_tree();
+
+        let tree = state.syntax_tree.tree.clone().unwrap();
+        assert!(!tree.root_node().has_error());
+        let mut parser = Parser::new();
+        parser
+            .set_language(&language(FileType::Rust).unwrap())
+            .unwrap();
+        let fresh = parser.parse(state.buf.to_string(), None).unwrap();
+        assert_eq!(tree.root_node().to_sexp(), fresh.root_node().to_sexp());
+        assert_eq!(state.syntax_tree.version, state.buf.version());
+
+        state.buf = CodeBuffer::new(SOURCE.to_string());
+        state.update_syntax_tree();
+        assert_eq!(state.syntax_tree.text, SOURCE);
+    }
+
+    #[test]
+    fn test_text_objects() {
+        let mut state = rust_state();
+        state.cursor.move_y_to(&state.buf, 1);
+        state.cursor.move_x_to(&state.buf, 4);
+        state
+            .select_text_object(TextObject::Function, false)
+            .unwrap();
+        assert!(state.get_mode() == EditorMode::Visual);
+        assert_eq!(state.visual_start, Vec2::new(0, 0));
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 2));
+
+        state.set_mode(EditorMode::Normal);
+        state
+            .select_text_object(TextObject::Function, true)
+            .unwrap();
+        assert_eq!(state.visual_start, Vec2::new(4, 1));
+        assert_eq!(state.cursor.get_display(&state.buf), (8, 1));
+
+        state.set_mode(EditorMode::Normal);
+        state.cursor.move_y_to(&state.buf, 5);
+        state.cursor.move_x_to(&state.buf, 8);
+        state
+            .select_text_object(TextObject::Argument, false)
+            .unwrap();
+        assert_eq!(state.visual_start, Vec2::new(8, 5));
+        assert_eq!(state.cursor.get_display(&state.buf), (10, 5));
+    }
+
+    #[test]
+    fn test_expand_and_jump() {
+        let mut state = rust_state();
+        state.cursor.move_y_to(&state.buf, 1);
+        state.cursor.move_x_to(&state.buf, 4);
+        state.expand_selection().unwrap();
+        // a -> a + b
+        assert_eq!(state.visual_start, Vec2::new(4, 1));
+        assert_eq!(state.cursor.get_display(&state.buf), (8, 1));
+        state.shrink_selection().unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (8, 1));
+
+        state.set_mode(EditorMode::Normal);
+        state.jump_to_function(true).unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 4));
+        state.jump_to_function(false).unwrap();
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 0));
+        assert!(state.jump_to_function(false).is_err());
+    }
+}
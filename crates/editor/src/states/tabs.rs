@@ -0,0 +1,274 @@
+use std::{mem::swap, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use utils::types::Vec2;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{
+        file::expand_path,
+        window::{Rect, WindowList},
+        EditorState,
+    },
+};
+
+#[derive(Clone)]
+pub struct TabList {
+    tabs: Vec<WindowList>,
+    current: usize,
+    screen: Vec2<usize>,
+}
+
+impl TabList {
+    pub fn new(screen: Vec2<usize>) -> Self {
+        Self {
+            tabs: vec![WindowList::new(1, Rect::new(0, 0, screen.x, screen.y))],
+            current: 0,
+            screen,
+        }
+    }
+}
+
+impl EditorState {
+    pub fn tab_count(&self) -> usize {
+        self.tabs.tabs.len()
+    }
+
+    pub fn has_tab_line(&self) -> bool {
+        self.tab_count() > 1
+    }
+
+    pub fn tab_labels(&self) -> Vec<(String, bool)> {
+        (0..self.tab_count())
+            .map(|i| {
+                let current = i == self.tabs.current;
+                let name = if current {
+                    self.buffer_name()
+                } else {
+                    self.buffer_label(self.tabs.tabs[i].current_buffer())
+                };
+                (format!(" {} {} ", i + 1, name), current)
+            })
+            .collect()
+    }
+
+    pub fn tabs_screen_size(&self) -> Vec2<usize> {
+        self.tabs.screen
+    }
+
+    pub fn layout_tabs(&mut self) {
+        let screen = self.tabs.screen;
+        let top = if self.has_tab_line() { 1 } else { 0 };
+        let left = self.explorer_width();
+        let height = screen.y.saturating_sub(top);
+        self.set_explorer_height(height);
+        self.set_window_area(Rect::new(left, top, screen.x.saturating_sub(left), height));
+    }
+
+    pub fn set_screen_size(&mut self, w: usize, h: usize) {
+        self.tabs.screen = Vec2::new(w, h);
+        self.layout_tabs();
+    }
+
+    pub fn replace_window_buffer(&mut self, old: usize, new: usize) {
+        self.windows.replace_buffer(old, new);
+        for tab in self.tabs.tabs.iter_mut() {
+            tab.replace_buffer(old, new);
+        }
+    }
+
+    fn switch_tab(&mut self, index: usize) {
+        if index == self.tabs.current || index >= self.tab_count() {
+            return;
+        }
+
+        self.cancel_change();
+        self.store_window();
+        swap(&mut self.windows, &mut self.tabs.tabs[self.tabs.current]);
+        swap(&mut self.windows, &mut self.tabs.tabs[index]);
+        self.tabs.current = index;
+
+        self.layout_tabs();
+        self.load_window(self.current_window());
+        self.cursors.clear();
+    }
+
+    pub fn push_tab(&mut self) {
+        let buffer = self.buffers_current();
+        let tab = WindowList::new(buffer, Rect::default());
+        self.tabs.tabs.insert(self.tabs.current + 1, tab);
+        self.switch_tab(self.tabs.current + 1);
+    }
+
+    pub fn new_tab(&mut self, path: Option<PathBuf>) {
+        self.push_tab();
+        match path {
+            Some(path) => {
+                if let Err(err) = self.open_buffer(path) {
+                    self.set_message(err.to_string());
+                }
+            }
+            None => {
+                let id = self.new_buffer();
+                self.switch_buffer(id);
+            }
+        }
+    }
+
+    pub fn open_tabs(&mut self, paths: &[PathBuf]) {
+        let mut error = None;
+        for path in paths {
+            let buffer = self.buffers_current();
+            let tab = WindowList::new(buffer, Rect::default());
+            self.tabs.tabs.push(tab);
+            self.switch_tab(self.tab_count() - 1);
+            if let Err(err) = self.open_buffer(path.clone()) {
+                error = Some(err.to_string());
+            }
+        }
+        self.switch_tab(0);
+        self.clear_message();
+        if let Some(error) = error {
+            self.set_message(error);
+        }
+    }
+
+    pub fn close_tab(&mut self) -> Result<()> {
+        if self.tab_count() == 1 {
+            return Err(anyhow!("Cannot close last tab page"));
+        }
+
+        let current = self.tabs.current;
+        let next = if current + 1 < self.tab_count() {
+            current + 1
+        } else {
+            current - 1
+        };
+        self.switch_tab(next);
+        self.tabs.tabs.remove(current);
+        if self.tabs.current > current {
+            self.tabs.current -= 1;
+        }
+        self.layout_tabs();
+        Ok(())
+    }
+
+    fn cmd_next_tab(&mut self) {
+        let index = match self.count {
+            Some(count) => count.max(1) - 1,
+            None => (self.tabs.current + 1) % self.tab_count(),
+        };
+        self.switch_tab(index);
+    }
+
+    fn cmd_prev_tab(&mut self) {
+        let count = self.get_count() % self.tab_count();
+        let index = (self.tabs.current + self.tab_count() - count) % self.tab_count();
+        self.switch_tab(index);
+    }
+
+    fn move_tab(&mut self, args: &str) -> Result<()> {
+        let last = self.tab_count() - 1;
+        let current = self.tabs.current as isize;
+        let index = if args.is_empty() {
+            last as isize
+        } else if let Some(n) = args.strip_prefix('+') {
+            current + n.parse::<isize>().unwrap_or(1)
+        } else if let Some(n) = args.strip_prefix('-') {
+            current - n.parse::<isize>().unwrap_or(1)
+        } else {
+            let n = args
+                .parse::<isize>()
+                .map_err(|_| anyhow!("Invalid argument: {}", args))?;
+            if n <= current {
+                n
+            } else {
+                n - 1
+            }
+        };
+        if index < 0 || index > last as isize {
+            return Err(anyhow!("Invalid argument: {}", args));
+        }
+
+        let tab = self.tabs.tabs.remove(self.tabs.current);
+        self.tabs.tabs.insert(index as usize, tab);
+        self.tabs.current = index as usize;
+        Ok(())
+    }
+
+    pub fn register_tab_cmds(cmds: &mut EditorCommand) {
+        type TabCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let tab_cmds: &[(&[&str], TabCommand)] = &[
+            (&["tabnew", "tabe", "tabedit"], |editor, args| {
+                let path = Some(args).filter(|args| !args.is_empty());
+                editor.new_tab(path.map(expand_path).transpose()?);
+                Ok(())
+            }),
+            (&["tabc", "tabclose"], |editor, _| editor.close_tab()),
+            (&["tabm", "tabmove"], EditorState::move_tab),
+            (&["tabn", "tabnext"], |editor, args| {
+                match args.parse::<usize>() {
+                    Ok(n) => editor.switch_tab(n.max(1) - 1),
+                    Err(_) => editor.switch_tab((editor.tabs.current + 1) % editor.tab_count()),
+                }
+                Ok(())
+            }),
+            (&["tabp", "tabprevious", "tabN", "tabNext"], |editor, _| {
+                let count = editor.tab_count();
+                editor.switch_tab((editor.tabs.current + count - 1) % count);
+                Ok(())
+            }),
+        ];
+
+        for (names, f) in tab_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+
+        cmds.register("next_tab", Box::new(|editor| editor.cmd_next_tab()));
+        cmds.register("prev_tab", Box::new(|editor| editor.cmd_prev_tab()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tabs() {
+        let mut state = EditorState::new("a\nb".to_string(), None);
+        state.set_screen_size(80, 20);
+        state.cursor.move_y_to(&state.buf, 1);
+
+        state.new_tab(None);
+        assert_eq!(state.tab_count(), 2);
+        assert_eq!(state.buf.to_string(), "\n");
+        assert_eq!(state.window_rect(state.current_window()).y, 1);
+
+        state.split_window(true, None).unwrap();
+        state.cmd_next_tab();
+        assert_eq!(state.buf.to_string(), "a\nb");
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 1));
+        assert_eq!(state.window_ids().len(), 1);
+
+        state.move_tab("").unwrap();
+        assert_eq!(state.tabs.current, 1);
+        state.move_tab("0").unwrap();
+        assert_eq!(state.tabs.current, 0);
+
+        state.close_tab().unwrap();
+        assert_eq!(state.tab_count(), 1);
+        assert_eq!(state.window_ids().len(), 2);
+        assert_eq!(state.window_rect(state.current_window()).y, 0);
+        assert!(state.close_tab().is_err());
+    }
+}
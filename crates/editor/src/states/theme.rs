@@ -0,0 +1,475 @@
+use std::{collections::HashMap, env, fs::read_to_string, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use crossterm::style::{Attribute, Color, ContentStyle};
+use serde::Deserialize;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{syntax::Scope, EditorState},
+};
+
+const BUNDLED_THEMES: &[(&str, &str)] = &[
+    ("default", include_str!("../../themes/default.toml")),
+    ("dark", include_str!("../../themes/dark.toml")),
+    ("light", include_str!("../../themes/light.toml")),
+    ("gruvbox", include_str!("../../themes/gruvbox.toml")),
+];
+
+const ANSI_COLORS: &[(&str, Color, (u8, u8, u8))] = &[
+    ("black", Color::Black, (0, 0, 0)),
+    ("dark_red", Color::DarkRed, (205, 0, 0)),
+    ("dark_green", Color::DarkGreen, (0, 205, 0)),
+    ("dark_yellow", Color::DarkYellow, (205, 205, 0)),
+    ("dark_blue", Color::DarkBlue, (0, 0, 238)),
+    ("dark_magenta", Color::DarkMagenta, (205, 0, 205)),
+    ("dark_cyan", Color::DarkCyan, (0, 205, 205)),
+    ("grey", Color::Grey, (229, 229, 229)),
+    ("dark_grey", Color::DarkGrey, (127, 127, 127)),
+    ("red", Color::Red, (255, 0, 0)),
+    ("green", Color::Green, (0, 255, 0)),
+    ("yellow", Color::Yellow, (255, 255, 0)),
+    ("blue", Color::Blue, (92, 92, 255)),
+    ("magenta", Color::Magenta, (255, 0, 255)),
+    ("cyan", Color::Cyan, (0, 255, 255)),
+    ("white", Color::White, (255, 255, 255)),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn ansi_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => ANSI_COLORS[n as usize].2,
+        16..=231 => {
+            let n = n - 16;
+            (
+                CUBE_LEVELS[(n / 36) as usize],
+                CUBE_LEVELS[(n / 6 % 6) as usize],
+                CUBE_LEVELS[(n % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + (n - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+fn nearest_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |v: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+            .unwrap() as u8
+    };
+    let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let grey = 232 + ((average.saturating_sub(8) + 5) / 10).min(23) as u8;
+
+    if distance(ansi_rgb(grey), rgb) < distance(ansi_rgb(cube), rgb) {
+        grey
+    } else {
+        cube
+    }
+}
+
+fn nearest_ansi16(rgb: (u8, u8, u8)) -> Color {
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, _, ansi)| distance(*ansi, rgb))
+        .map(|(_, color, _)| *color)
+        .unwrap()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub fn detect() -> Self {
+        Self::from_env(
+            env::var("COLORTERM").ok().as_deref(),
+            env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            ColorDepth::TrueColor
+        } else if term.is_some_and(|term| term.contains("256color")) {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    pub fn convert(&self, color: Color) -> Color {
+        match (self, color) {
+            (ColorDepth::Ansi256, Color::Rgb { r, g, b }) => {
+                Color::AnsiValue(nearest_ansi256((r, g, b)))
+            }
+            (ColorDepth::Ansi16, Color::Rgb { r, g, b }) => nearest_ansi16((r, g, b)),
+            (ColorDepth::Ansi16, Color::AnsiValue(n)) => nearest_ansi16(ansi_rgb(n)),
+            _ => color,
+        }
+    }
+}
+
+fn parse_color(value: &str) -> Result<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+        };
+        if let (6, Some(r), Some(g), Some(b)) = (hex.len(), channel(0), channel(2), channel(4)) {
+            return Ok(Color::Rgb { r, g, b });
+        }
+    }
+    if let Ok(n) = value.parse::<u8>() {
+        return Ok(Color::AnsiValue(n));
+    }
+    if value == "reset" {
+        return Ok(Color::Reset);
+    }
+    ANSI_COLORS
+        .iter()
+        .find(|(name, _, _)| *name == value)
+        .map(|(_, color, _)| *color)
+        .ok_or_else(|| anyhow!("Invalid color: {}", value))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Style {
+    pub fn patch(self, other: Style) -> Style {
+        Style {
+            fg: other.fg.or(self.fg),
+            bg: other.bg.or(self.bg),
+            bold: self.bold || other.bold,
+            italic: self.italic || other.italic,
+            underline: self.underline || other.underline,
+            reverse: self.reverse || other.reverse,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum StyleDef {
+    Color(String),
+    Style {
+        fg: Option<String>,
+        bg: Option<String>,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default)]
+        italic: bool,
+        #[serde(default)]
+        underline: bool,
+        #[serde(default)]
+        reverse: bool,
+    },
+}
+
+impl StyleDef {
+    fn to_style(&self) -> Result<Style> {
+        match self {
+            StyleDef::Color(fg) => Ok(Style {
+                fg: Some(parse_color(fg)?),
+                ..Style::default()
+            }),
+            StyleDef::Style {
+                fg,
+                bg,
+                bold,
+                italic,
+                underline,
+                reverse,
+            } => Ok(Style {
+                fg: fg.as_deref().map(parse_color).transpose()?,
+                bg: bg.as_deref().map(parse_color).transpose()?,
+                bold: *bold,
+                italic: *italic,
+                underline: *underline,
+                reverse: *reverse,
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    name: Option<String>,
+    #[serde(default)]
+    ui: HashMap<String, StyleDef>,
+    #[serde(default)]
+    syntax: HashMap<String, StyleDef>,
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub depth: ColorDepth,
+    pub normal: Style,
+    pub info_bar: Style,
+    pub info_bar_inactive: Style,
+    pub tab_line: Style,
+    pub tab_line_selected: Style,
+    pub line_number: Style,
+    pub selection: Style,
+    pub search: Style,
+    pub cursor_line: Style,
+    pub popup: Style,
+    pub popup_selected: Style,
+    pub diagnostic: Style,
+    syntax: HashMap<Scope, Style>,
+}
+
+impl Theme {
+    fn empty(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            depth: ColorDepth::detect(),
+            normal: Style::default(),
+            info_bar: Style::default(),
+            info_bar_inactive: Style::default(),
+            tab_line: Style::default(),
+            tab_line_selected: Style::default(),
+            line_number: Style::default(),
+            selection: Style::default(),
+            search: Style::default(),
+            cursor_line: Style::default(),
+            popup: Style::default(),
+            popup_selected: Style::default(),
+            diagnostic: Style::default(),
+            syntax: HashMap::new(),
+        }
+    }
+
+    fn ui_style_mut(&mut self, name: &str) -> Option<&mut Style> {
+        let style = match name {
+            "normal" => &mut self.normal,
+            "info_bar" => &mut self.info_bar,
+            "info_bar_inactive" => &mut self.info_bar_inactive,
+            "tab_line" => &mut self.tab_line,
+            "tab_line_selected" => &mut self.tab_line_selected,
+            "line_number" => &mut self.line_number,
+            "selection" => &mut self.selection,
+            "search" => &mut self.search,
+            "cursor_line" => &mut self.cursor_line,
+            "popup" => &mut self.popup,
+            "popup_selected" => &mut self.popup_selected,
+            "diagnostic" => &mut self.diagnostic,
+            _ => return None,
+        };
+        Some(style)
+    }
+
+    fn parse(name: &str, text: &str, base: Theme) -> Result<Theme> {
+        let file: ThemeFile =
+            toml::from_str(text).map_err(|err| anyhow!("Invalid theme {}: {}", name, err))?;
+        let mut theme = base;
+        theme.name = file.name.unwrap_or_else(|| name.to_string());
+
+        for (element, def) in &file.ui {
+            let style = def.to_style()?;
+            *theme
+                .ui_style_mut(element)
+                .ok_or_else(|| anyhow!("Unknown UI element: {}", element))? = style;
+        }
+        for (scope, def) in &file.syntax {
+            let scope =
+                Scope::from_name(scope).ok_or_else(|| anyhow!("Unknown scope: {}", scope))?;
+            theme.syntax.insert(scope, def.to_style()?);
+        }
+        Ok(theme)
+    }
+
+    pub fn load(name: &str) -> Result<Theme> {
+        let path = if name.ends_with(".toml") {
+            Some(PathBuf::from(name))
+        } else {
+            themes_dir()
+                .map(|dir| dir.join(format!("{}.toml", name)))
+                .filter(|path| path.exists())
+        };
+        let text = match path {
+            Some(path) => read_to_string(&path)
+                .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?,
+            None => BUNDLED_THEMES
+                .iter()
+                .find(|(bundled, _)| *bundled == name)
+                .map(|(_, text)| text.to_string())
+                .ok_or_else(|| anyhow!("Cannot find color scheme '{}'", name))?,
+        };
+        let name = name
+            .strip_suffix(".toml")
+            .map(|path| path.rsplit('/').next().unwrap_or(path))
+            .unwrap_or(name);
+        Self::parse(name, &text, Theme::default())
+    }
+
+    pub fn scope_style(&self, scope: Scope) -> Style {
+        self.syntax.get(&scope).copied().unwrap_or_default()
+    }
+
+    pub fn content_style(&self, style: Style) -> ContentStyle {
+        let style = self.normal.patch(style);
+        let mut content = ContentStyle::new();
+        content.foreground_color = style.fg.map(|color| self.depth.convert(color));
+        content.background_color = style.bg.map(|color| self.depth.convert(color));
+        let attributes = [
+            (style.bold, Attribute::Bold),
+            (style.italic, Attribute::Italic),
+            (style.underline, Attribute::Underlined),
+            (style.reverse, Attribute::Reverse),
+        ];
+        for (enabled, attribute) in attributes {
+            if enabled {
+                content.attributes.set(attribute);
+            }
+        }
+        content
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let (name, text) = BUNDLED_THEMES[0];
+        Self::parse(name, text, Theme::empty(name)).unwrap()
+    }
+}
+
+fn themes_dir() -> Option<PathBuf> {
+    let config_home = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+    Some(config_home.join("ce").join("themes"))
+}
+
+impl EditorState {
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    fn ex_colorscheme(&mut self, args: &str) -> Result<()> {
+        if args.is_empty() {
+            self.set_message(self.theme.name.clone());
+            return Ok(());
+        }
+        self.theme = Theme::load(args)?;
+        Ok(())
+    }
+
+    pub fn register_theme_cmds(cmds: &mut EditorCommand) {
+        for name in ["colo", "colorscheme"] {
+            cmds.register_with_args(
+                name,
+                Box::new(|editor, args| {
+                    if let Err(err) = editor.ex_colorscheme(args) {
+                        editor.set_message(err.to_string());
+                    }
+                }),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_themes() {
+        for (name, _) in BUNDLED_THEMES {
+            let theme = Theme::load(name).unwrap();
+            assert_eq!(theme.name, *name);
+            assert!(theme.info_bar.bg.is_some());
+        }
+
+        let theme = Theme::load("dark").unwrap();
+        assert_eq!(
+            theme.scope_style(Scope::Keyword).fg,
+            Some(Color::Rgb {
+                r: 0xc6,
+                g: 0x78,
+                b: 0xdd
+            })
+        );
+        assert!(Theme::load("nothing").is_err());
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let text =
+            "[ui]\nline_number = \"#ff0000\"\n[syntax]\ncomment = { fg = \"12\", italic = true }\n";
+        let theme = Theme::parse("mine", text, Theme::default()).unwrap();
+        assert_eq!(theme.name, "mine");
+        assert_eq!(
+            theme.line_number.fg,
+            Some(Color::Rgb { r: 255, g: 0, b: 0 })
+        );
+        assert_eq!(
+            theme.scope_style(Scope::Comment),
+            Style {
+                fg: Some(Color::AnsiValue(12)),
+                italic: true,
+                ..Style::default()
+            }
+        );
+        assert_eq!(theme.info_bar, Theme::default().info_bar);
+
+        assert!(Theme::parse("x", "[ui]\nstatus = \"red\"\n", Theme::default()).is_err());
+        assert!(Theme::parse("x", "[syntax]\ncomment = \"#12\"\n", Theme::default()).is_err());
+    }
+
+    #[test]
+    fn test_color_depth() {
+        assert_eq!(
+            ColorDepth::from_env(Some("truecolor"), Some("xterm")),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm-256color")),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(ColorDepth::from_env(None, None), ColorDepth::Ansi16);
+
+        let orange = Color::Rgb {
+            r: 0xff,
+            g: 0x87,
+            b: 0x00,
+        };
+        assert_eq!(ColorDepth::Ansi256.convert(orange), Color::AnsiValue(208));
+        assert_eq!(ColorDepth::Ansi16.convert(orange), Color::DarkYellow);
+        let grey = Color::Rgb {
+            r: 0x80,
+            g: 0x80,
+            b: 0x80,
+        };
+        assert_eq!(ColorDepth::Ansi256.convert(grey), Color::AnsiValue(244));
+        assert_eq!(ColorDepth::Ansi16.convert(grey), Color::DarkGrey);
+        assert_eq!(
+            ColorDepth::Ansi16.convert(Color::AnsiValue(196)),
+            Color::Red
+        );
+        assert_eq!(ColorDepth::TrueColor.convert(orange), orange);
+    }
+}
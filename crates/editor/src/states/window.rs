@@ -0,0 +1,756 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use utils::types::Vec2;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{cursor::EditorCursor, file::expand_path, quickfix::QuickfixList, EditorState},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, w: usize, h: usize) -> Self {
+        Self { x, y, w, h }
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        self.x <= x && x < self.x + self.w && self.y <= y && y < self.y + self.h
+    }
+}
+
+#[derive(Clone)]
+pub struct Window {
+    pub id: usize,
+    buffer: usize,
+    cursor: EditorCursor,
+    offset: Vec2<usize>,
+    pub rect: Rect,
+    location_list: QuickfixList,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Layout {
+    Window(usize),
+    Split {
+        vertical: bool,
+        children: Vec<(Layout, f64)>,
+    },
+}
+
+fn min_size(vertical: bool) -> usize {
+    if vertical {
+        1
+    } else {
+        2
+    }
+}
+
+impl Layout {
+    fn contains(&self, id: usize) -> bool {
+        match self {
+            Layout::Window(window) => *window == id,
+            Layout::Split { children, .. } => children.iter().any(|(child, _)| child.contains(id)),
+        }
+    }
+
+    fn window_ids(&self, ids: &mut Vec<usize>) {
+        match self {
+            Layout::Window(id) => ids.push(*id),
+            Layout::Split { children, .. } => {
+                for (child, _) in children {
+                    child.window_ids(ids);
+                }
+            }
+        }
+    }
+
+    // Cumulative rounding makes the sizes add up to rect
+    fn child_rects(vertical: bool, children: &[(Layout, f64)], rect: Rect) -> Vec<Rect> {
+        // Vertical splits have a separator column between them
+        let available = if vertical {
+            rect.w.saturating_sub(children.len() - 1)
+        } else {
+            rect.h
+        };
+        let total: f64 = children.iter().map(|(_, size)| size).sum();
+
+        let mut rects = Vec::new();
+        let mut pos = if vertical { rect.x } else { rect.y };
+        let (mut ratio, mut end) = (0.0, 0);
+        for (_, size) in children {
+            ratio += size;
+            let start = end;
+            end = if total > 0.0 {
+                (available as f64 * ratio / total).round() as usize
+            } else {
+                available
+            };
+            let size = end - start;
+            rects.push(if vertical {
+                Rect::new(pos, rect.y, size, rect.h)
+            } else {
+                Rect::new(rect.x, pos, rect.w, size)
+            });
+            pos += size + if vertical { 1 } else { 0 };
+        }
+        rects
+    }
+
+    // Ratios are kept, so shrinking the terminal and growing it back restores the sizes
+    fn compute(&self, rect: Rect, rects: &mut Vec<(usize, Rect)>) {
+        match self {
+            Layout::Window(id) => rects.push((*id, rect)),
+            Layout::Split { vertical, children } => {
+                let child_rects = Self::child_rects(*vertical, children, rect);
+                for ((child, _), child_rect) in children.iter().zip(child_rects) {
+                    child.compute(child_rect, rects);
+                }
+            }
+        }
+    }
+
+    fn split(&mut self, target: usize, new: usize, vertical: bool) -> bool {
+        match self {
+            Layout::Window(id) if *id == target => {
+                *self = Layout::Split {
+                    vertical,
+                    children: vec![(Layout::Window(new), 1.0), (Layout::Window(target), 1.0)],
+                };
+                true
+            }
+            Layout::Window(_) => false,
+            Layout::Split {
+                vertical: split_vertical,
+                children,
+            } => {
+                let Some(i) = children
+                    .iter()
+                    .position(|(child, _)| child.contains(target))
+                else {
+                    return false;
+                };
+                if children[i].0 == Layout::Window(target) && *split_vertical == vertical {
+                    let size = children[i].1 / 2.0;
+                    children[i].1 = size;
+                    children.insert(i, (Layout::Window(new), size));
+                    true
+                } else {
+                    children[i].0.split(target, new, vertical)
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, target: usize) -> bool {
+        let Layout::Split { children, .. } = self else {
+            return false;
+        };
+        let Some(i) = children
+            .iter()
+            .position(|(child, _)| child.contains(target))
+        else {
+            return false;
+        };
+
+        if children[i].0 == Layout::Window(target) {
+            let (_, size) = children.remove(i);
+            let neighbor = i.min(children.len() - 1);
+            children[neighbor].1 += size;
+        } else if !children[i].0.remove(target) {
+            return false;
+        }
+
+        if children.len() == 1 {
+            *self = children.remove(0).0;
+        }
+        true
+    }
+
+    fn resize(&mut self, rect: Rect, target: usize, vertical: bool, delta: isize) -> bool {
+        let Layout::Split {
+            vertical: split_vertical,
+            children,
+        } = self
+        else {
+            return false;
+        };
+        let Some(i) = children
+            .iter()
+            .position(|(child, _)| child.contains(target))
+        else {
+            return false;
+        };
+        let child_rects = Self::child_rects(*split_vertical, children, rect);
+        if children[i]
+            .0
+            .resize(child_rects[i], target, vertical, delta)
+        {
+            return true;
+        }
+        if *split_vertical != vertical || children.len() < 2 {
+            return false;
+        }
+
+        let sizes: Vec<usize> = child_rects
+            .iter()
+            .map(|rect| if vertical { rect.w } else { rect.h })
+            .collect();
+        for ((_, size), cells) in children.iter_mut().zip(&sizes) {
+            *size = *cells as f64;
+        }
+
+        let j = if i + 1 < children.len() { i + 1 } else { i - 1 };
+        let min = min_size(vertical);
+        let total = sizes[i] + sizes[j];
+        if total < min * 2 {
+            return false;
+        }
+        let size = (sizes[i] as isize + delta).clamp(min as isize, (total - min) as isize) as usize;
+        children[i].1 = size as f64;
+        children[j].1 = (total - size) as f64;
+        true
+    }
+
+    fn equalize(&mut self) {
+        if let Layout::Split { children, .. } = self {
+            for (child, size) in children.iter_mut() {
+                *size = 1.0;
+                child.equalize();
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct WindowList {
+    windows: Vec<Window>,
+    current: usize,
+    layout: Layout,
+    next_id: usize,
+    area: Rect,
+}
+
+impl WindowList {
+    pub fn new(buffer: usize, area: Rect) -> Self {
+        Self {
+            windows: vec![Window {
+                id: 1,
+                buffer,
+                cursor: EditorCursor::default(),
+                offset: Vec2::default(),
+                rect: area,
+                location_list: QuickfixList::default(),
+            }],
+            current: 1,
+            layout: Layout::Window(1),
+            next_id: 2,
+            area,
+        }
+    }
+
+    fn index_of(&self, id: usize) -> usize {
+        self.windows
+            .iter()
+            .position(|window| window.id == id)
+            .unwrap()
+    }
+
+    fn get(&self, id: usize) -> &Window {
+        &self.windows[self.index_of(id)]
+    }
+
+    fn get_mut(&mut self, id: usize) -> &mut Window {
+        let index = self.index_of(id);
+        &mut self.windows[index]
+    }
+
+    pub fn current_buffer(&self) -> usize {
+        self.get(self.current).buffer
+    }
+
+    pub fn replace_buffer(&mut self, old: usize, new: usize) {
+        for window in self.windows.iter_mut() {
+            if window.buffer == old {
+                window.buffer = new;
+                window.cursor = EditorCursor::default();
+                window.offset = Vec2::default();
+            }
+        }
+    }
+}
+
+impl EditorState {
+    pub fn current_window(&self) -> usize {
+        self.windows.current
+    }
+
+    pub fn window_ids(&self) -> Vec<usize> {
+        let mut ids = Vec::new();
+        self.windows.layout.window_ids(&mut ids);
+        ids
+    }
+
+    pub fn window_rect(&self, id: usize) -> Rect {
+        self.windows.get(id).rect
+    }
+
+    pub fn layout_windows(&mut self) {
+        let area = self.windows.area;
+        let mut rects = Vec::new();
+        self.windows.layout.compute(area, &mut rects);
+        for (id, rect) in rects {
+            self.windows.get_mut(id).rect = rect;
+        }
+
+        let rect = self.window_rect(self.windows.current);
+        self.view_size = Vec2::new(rect.w, rect.h.saturating_sub(1));
+    }
+
+    pub fn set_window_area(&mut self, area: Rect) {
+        self.windows.area = area;
+        self.layout_windows();
+    }
+
+    pub fn store_window(&mut self) {
+        let id = self.windows.current;
+        let buffer = self.buffers_current();
+        let window = self.windows.get_mut(id);
+        window.buffer = buffer;
+        window.cursor = self.cursor.clone();
+        window.offset = self.offset;
+    }
+
+    pub fn load_window(&mut self, id: usize) {
+        let window = self.windows.get(id).clone();
+        self.activate_buffer(window.buffer);
+        self.cursor = window.cursor;
+        self.cursor.clamp(&self.buf);
+        self.offset = window.offset;
+        self.view_size = Vec2::new(window.rect.w, window.rect.h.saturating_sub(1));
+        self.windows.current = id;
+    }
+
+    pub fn focus_window(&mut self, id: usize) {
+        if id == self.windows.current {
+            return;
+        }
+        self.store_window();
+        self.load_window(id);
+    }
+
+    pub fn window_buffer(&self, id: usize) -> usize {
+        if id == self.windows.current {
+            return self.buffers_current();
+        }
+        self.windows.get(id).buffer
+    }
+
+    pub fn window_view(&mut self, id: usize) -> EditorState {
+        let window = self.windows.get(id).clone();
+        let mut view = EditorState::new(String::new(), None);
+        self.copy_buffer_to(window.buffer, window.offset.y + window.rect.h, &mut view);
+        view.cursor = window.cursor;
+        view.cursor.clamp(&view.buf);
+        view.offset = window.offset;
+        view.view_size = Vec2::new(window.rect.w, window.rect.h.saturating_sub(1));
+        view.windows = WindowList::new(window.buffer, window.rect);
+        view.theme = self.theme.clone();
+        view
+    }
+
+    pub fn enter_window(&mut self, id: usize) {
+        if id != self.windows.current {
+            self.cancel_change();
+            self.focus_window(id);
+            self.cursors.clear();
+        }
+    }
+
+    pub fn window_exists(&self, id: usize) -> bool {
+        self.windows.windows.iter().any(|window| window.id == id)
+    }
+
+    pub fn window_showing(&self, buffer: usize) -> Option<usize> {
+        if self.buffers_current() == buffer {
+            return Some(self.windows.current);
+        }
+        self.windows
+            .windows
+            .iter()
+            .find(|window| window.id != self.windows.current && window.buffer == buffer)
+            .map(|window| window.id)
+    }
+
+    pub fn location_list(&self, id: usize) -> &QuickfixList {
+        &self.windows.get(id).location_list
+    }
+
+    pub fn location_list_mut(&mut self, id: usize) -> &mut QuickfixList {
+        &mut self.windows.get_mut(id).location_list
+    }
+
+    pub fn set_window_height(&mut self, height: usize) {
+        let current = self.windows.current;
+        let delta = height as isize - self.window_rect(current).h as isize;
+        let area = self.windows.area;
+        self.windows.layout.resize(area, current, false, delta);
+        self.layout_windows();
+    }
+
+    pub fn split_window(&mut self, vertical: bool, path: Option<PathBuf>) -> Result<()> {
+        let current = self.windows.current;
+        let rect = self.window_rect(current);
+        let (size, needed) = if vertical {
+            (rect.w, min_size(true) * 2 + 1)
+        } else {
+            (rect.h, min_size(false) * 2)
+        };
+        if size < needed {
+            return Err(anyhow!("Not enough room"));
+        }
+
+        let id = self.windows.next_id;
+        self.windows.next_id += 1;
+
+        self.store_window();
+        let mut window = self.windows.get(current).clone();
+        window.id = id;
+        self.windows.windows.push(window);
+        self.windows.layout.split(current, id, vertical);
+        self.layout_windows();
+        self.load_window(id);
+        self.cursors.clear();
+
+        if let Some(path) = path {
+            self.open_buffer(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.windows.windows.len()
+    }
+
+    pub fn close_window(&mut self) -> Result<()> {
+        if self.window_count() == 1 {
+            return Err(anyhow!("Cannot close last window"));
+        }
+
+        let current = self.windows.current;
+        let ids = self.window_ids();
+        let index = ids.iter().position(|id| *id == current).unwrap();
+        let next = if index > 0 { ids[index - 1] } else { ids[1] };
+
+        self.enter_window(next);
+        self.windows.layout.remove(current);
+        let index = self.windows.index_of(current);
+        self.windows.windows.remove(index);
+        self.layout_windows();
+        Ok(())
+    }
+
+    fn only_window(&mut self) {
+        let current = self.windows.current;
+        self.windows.windows.retain(|window| window.id == current);
+        self.windows.layout = Layout::Window(current);
+        self.layout_windows();
+    }
+
+    fn focus_direction(&mut self, dx: isize, dy: isize) {
+        for _ in 0..self.get_count() {
+            if !self.focus_neighbor(dx, dy) {
+                return;
+            }
+        }
+    }
+
+    fn focus_neighbor(&mut self, dx: isize, dy: isize) -> bool {
+        let rect = self.window_rect(self.windows.current);
+        let (col, row) = self.cursor_screen_pos();
+        let x = (rect.x + col.min(rect.w.saturating_sub(1))) as isize;
+        let y = (rect.y + row.min(rect.h.saturating_sub(1))) as isize;
+
+        let x = match dx {
+            1 => (rect.x + rect.w + 1) as isize,
+            -1 => rect.x as isize - 2,
+            _ => x,
+        };
+        let y = match dy {
+            1 => (rect.y + rect.h) as isize,
+            -1 => rect.y as isize - 1,
+            _ => y,
+        };
+
+        let target = self
+            .windows
+            .windows
+            .iter()
+            .find(|window| x >= 0 && y >= 0 && window.rect.contains(x as usize, y as usize))
+            .map(|window| window.id);
+        match target {
+            Some(id) => {
+                self.enter_window(id);
+                true
+            }
+            // Moving left from the leftmost window focuses the explorer
+            None if dx < 0 && self.is_explorer_visible() => {
+                self.focus_explorer();
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn focus_next_window(&mut self) {
+        let ids = self.window_ids();
+        let index = ids
+            .iter()
+            .position(|id| *id == self.windows.current)
+            .unwrap();
+        self.enter_window(ids[(index + 1) % ids.len()]);
+    }
+
+    fn resize_window(&mut self, vertical: bool, sign: isize) {
+        let delta = sign * self.get_count() as isize;
+        let current = self.windows.current;
+        let area = self.windows.area;
+        self.windows.layout.resize(area, current, vertical, delta);
+        self.layout_windows();
+    }
+
+    fn equalize_windows(&mut self) {
+        self.windows.layout.equalize();
+        self.layout_windows();
+    }
+
+    pub fn register_window_cmds(cmds: &mut EditorCommand) {
+        type WindowCommand = fn(&mut EditorState, &str) -> Result<()>;
+        let window_cmds: &[(&[&str], WindowCommand)] = &[
+            (&["sp", "split"], |editor, args| {
+                let path = Some(args).filter(|args| !args.is_empty());
+                editor.split_window(false, path.map(expand_path).transpose()?)
+            }),
+            (&["vs", "vsp", "vsplit"], |editor, args| {
+                let path = Some(args).filter(|args| !args.is_empty());
+                editor.split_window(true, path.map(expand_path).transpose()?)
+            }),
+            (&["clo", "close"], |editor, _| editor.close_window()),
+            (&["on", "only"], |editor, _| {
+                editor.only_window();
+                Ok(())
+            }),
+        ];
+
+        for (names, f) in window_cmds {
+            for name in names.iter() {
+                let f = *f;
+                cmds.register_with_args(
+                    name,
+                    Box::new(move |editor, args| {
+                        if let Err(err) = f(editor, args) {
+                            editor.set_message(err.to_string());
+                        }
+                    }),
+                );
+            }
+        }
+
+        cmds.register(
+            "window_split",
+            Box::new(|editor| {
+                let _ = editor.split_window(false, None);
+            }),
+        );
+        cmds.register(
+            "window_vsplit",
+            Box::new(|editor| {
+                let _ = editor.split_window(true, None);
+            }),
+        );
+        cmds.register(
+            "window_close",
+            Box::new(|editor| {
+                if let Err(err) = editor.close_window() {
+                    editor.set_message(err.to_string());
+                }
+            }),
+        );
+        cmds.register("window_only", Box::new(|editor| editor.only_window()));
+        cmds.register(
+            "window_left",
+            Box::new(|editor| editor.focus_direction(-1, 0)),
+        );
+        cmds.register(
+            "window_down",
+            Box::new(|editor| editor.focus_direction(0, 1)),
+        );
+        cmds.register(
+            "window_up",
+            Box::new(|editor| editor.focus_direction(0, -1)),
+        );
+        cmds.register(
+            "window_right",
+            Box::new(|editor| editor.focus_direction(1, 0)),
+        );
+        cmds.register("window_next", Box::new(|editor| editor.focus_next_window()));
+        cmds.register(
+            "window_equalize",
+            Box::new(|editor| editor.equalize_windows()),
+        );
+        cmds.register(
+            "window_increase_height",
+            Box::new(|editor| editor.resize_window(false, 1)),
+        );
+        cmds.register(
+            "window_decrease_height",
+            Box::new(|editor| editor.resize_window(false, -1)),
+        );
+        cmds.register(
+            "window_increase_width",
+            Box::new(|editor| editor.resize_window(true, 1)),
+        );
+        cmds.register(
+            "window_decrease_width",
+            Box::new(|editor| editor.resize_window(true, -1)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rects(state: &EditorState) -> Vec<(usize, usize, usize, usize)> {
+        state
+            .window_ids()
+            .iter()
+            .map(|id| {
+                let rect = state.window_rect(*id);
+                (rect.x, rect.y, rect.w, rect.h)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_windows() {
+        let mut state = EditorState::new("a\nb\nc".to_string(), None);
+        state.set_window_area(Rect::new(0, 0, 81, 20));
+
+        state.split_window(true, None).unwrap();
+        assert_eq!(rects(&state), vec![(0, 0, 40, 20), (41, 0, 40, 20)]);
+        assert_eq!(state.current_window(), 2);
+
+        state.split_window(false, None).unwrap();
+        assert_eq!(
+            rects(&state),
+            vec![(0, 0, 40, 10), (0, 10, 40, 10), (41, 0, 40, 20)]
+        );
+
+        state.resize_window(false, 3);
+        assert_eq!(
+            rects(&state),
+            vec![(0, 0, 40, 13), (0, 13, 40, 7), (41, 0, 40, 20)]
+        );
+
+        state.focus_direction(1, 0);
+        assert_eq!(state.current_window(), 1);
+        state.focus_direction(-1, 0);
+        assert_eq!(state.current_window(), 3);
+
+        state.close_window().unwrap();
+        assert_eq!(rects(&state), vec![(0, 0, 40, 20), (41, 0, 40, 20)]);
+        state.only_window();
+        assert_eq!(rects(&state), vec![(0, 0, 81, 20)]);
+        assert!(state.close_window().is_err());
+    }
+
+    #[test]
+    fn test_not_enough_room() {
+        let mut state = EditorState::new("a\nb\nc".to_string(), None);
+        state.set_window_area(Rect::new(0, 0, 80, 5));
+        state.split_window(false, None).unwrap();
+        assert!(state.split_window(false, None).is_err());
+        assert_eq!(state.window_count(), 2);
+
+        state.set_window_area(Rect::new(0, 0, 80, 3));
+        state.resize_window(false, 1);
+        state.set_window_height(1);
+        assert_eq!(rects(&state), vec![(0, 0, 80, 2), (0, 2, 80, 1)]);
+    }
+
+    #[test]
+    fn test_focus_direction_count() {
+        let mut state = EditorState::new("a\nb\nc".to_string(), None);
+        state.set_window_area(Rect::new(0, 0, 80, 20));
+        state.split_window(true, None).unwrap();
+        state.split_window(true, None).unwrap();
+        assert_eq!(state.window_ids(), vec![3, 2, 1]);
+        state.enter_window(1);
+
+        state.count = Some(2);
+        state.focus_direction(-1, 0);
+        assert_eq!(state.current_window(), 3);
+    }
+
+    #[test]
+    fn test_window_view() {
+        let mut state = EditorState::new("a\nb\nc".to_string(), None);
+        state.split_window(false, None).unwrap();
+        state.cursor.move_y_to(&state.buf, 2);
+        state.focus_next_window();
+        let id = state.new_buffer();
+        state.switch_buffer(id);
+        state.buf.insert_str("other", 0, 0);
+
+        let view = state.window_view(2);
+        assert_eq!(view.buf.to_string(), "a\nb\nc");
+        assert_eq!(view.cursor.get_display(&view.buf), (0, 2));
+        assert_eq!(
+            view.window_rect(view.current_window()),
+            state.window_rect(2)
+        );
+        assert_eq!(state.current_window(), 1);
+        assert_eq!(state.buf.get_line(0), "other");
+    }
+
+    #[test]
+    fn test_keep_split_ratios() {
+        let mut state = EditorState::new("a\nb\nc".to_string(), None);
+        state.set_window_area(Rect::new(0, 0, 80, 30));
+        state.split_window(false, None).unwrap();
+        state.split_window(false, None).unwrap();
+        state.resize_window(false, 5);
+        let before = rects(&state);
+        assert_eq!(
+            before,
+            vec![(0, 0, 80, 13), (0, 13, 80, 2), (0, 15, 80, 15)]
+        );
+
+        state.set_window_area(Rect::new(0, 0, 80, 7));
+        state.set_window_area(Rect::new(0, 0, 80, 30));
+        assert_eq!(rects(&state), before);
+    }
+
+    #[test]
+    fn test_window_cursors() {
+        let mut state = EditorState::new("a\nb\nc".to_string(), None);
+        state.split_window(false, None).unwrap();
+        state.cursor.move_y_to(&state.buf, 2);
+
+        state.focus_next_window();
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 0));
+        state.buf.remove_line(2);
+        state.buf.remove_line(1);
+
+        state.focus_next_window();
+        assert_eq!(state.cursor.get_display(&state.buf), (0, 0));
+    }
+}
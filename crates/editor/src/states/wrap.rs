@@ -0,0 +1,550 @@
+use utils::text::{indent_width, index_at_col};
+
+use utils::types::Vec2;
+
+use crate::{
+    cmd::EditorCommand,
+    states::{mode::EditorMode, operator::TextRange, EditorState},
+};
+
+const BREAKAT: &[char] = &[
+    ' ', '\t', '!', '@', '*', '-', '+', ';', ':', ',', '.', '/', '?',
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenRow {
+    pub start: usize,
+    pub end: usize,
+    pub prefix: String,
+}
+
+impl ScreenRow {
+    pub fn prefix_width(&self) -> usize {
+        self.prefix.chars().count()
+    }
+}
+
+impl EditorState {
+    pub fn screen_rows(&self, y: usize) -> Vec<ScreenRow> {
+        let line: Vec<char> = self.buf.get_line(y).chars().collect();
+        let len = line.len();
+
+        if !self.options.wrap {
+            return vec![ScreenRow {
+                start: 0,
+                end: len,
+                prefix: String::new(),
+            }];
+        }
+
+        let width = self.view_width();
+        let cells = self.cell_widths(&line);
+        let mut prefix = String::new();
+        if self.options.breakindent {
+            let indent = indent_width(self.buf.get_line(y), self.options.tabstop);
+            prefix.push_str(&" ".repeat(indent));
+        }
+        prefix.push_str(&self.options.showbreak);
+        if prefix.chars().count() + 1 > width / 2 {
+            prefix.clear();
+        }
+
+        let mut rows = Vec::new();
+        let mut start = 0;
+        loop {
+            let row_prefix = if rows.is_empty() {
+                String::new()
+            } else {
+                prefix.clone()
+            };
+            let avail = width - row_prefix.chars().count();
+
+            let mut end = start;
+            let mut used = 0;
+            while end < len && (end == start || used + cells[end] <= avail) {
+                used += cells[end];
+                end += 1;
+            }
+
+            if end == len {
+                rows.push(ScreenRow {
+                    start,
+                    end: len,
+                    prefix: row_prefix,
+                });
+                break;
+            }
+
+            if self.options.linebreak {
+                if let Some(pos) = (start + 1..=end)
+                    .rev()
+                    .find(|&i| BREAKAT.contains(&line[i - 1]))
+                {
+                    end = pos;
+                }
+            }
+
+            rows.push(ScreenRow {
+                start,
+                end,
+                prefix: row_prefix,
+            });
+            start = end;
+        }
+
+        rows
+    }
+
+    pub fn cell_widths(&self, line: &[char]) -> Vec<usize> {
+        let tabstop = self.options.tabstop;
+        let mut col = 0;
+
+        line.iter()
+            .map(|c| {
+                let width = match c {
+                    '\t' => tabstop - col % tabstop,
+                    _ => 1,
+                };
+                col += width;
+                width
+            })
+            .collect()
+    }
+
+    fn row_text(&self, line: &[char], row: &ScreenRow) -> String {
+        let cells = self.cell_widths(line);
+        let mut text = row.prefix.clone();
+
+        for x in row.start..row.end {
+            match line[x] {
+                '\t' => text.push_str(&" ".repeat(cells[x])),
+                c => text.push(c),
+            }
+        }
+
+        text
+    }
+
+    // In Normal mode the cursor sits on the last cell of a tab
+    fn cursor_cell_offset(&self, line: &[char], x: usize) -> usize {
+        if self.get_mode() != EditorMode::Insert && line.get(x) == Some(&'\t') {
+            self.cell_widths(line)[x] - 1
+        } else {
+            0
+        }
+    }
+
+    fn char_index(&self, y: usize, x: usize) -> usize {
+        let line = self.buf.get_line(y);
+        line.char_indices().take_while(|(i, _)| *i < x).count()
+    }
+
+    fn byte_index(&self, y: usize, x: usize) -> usize {
+        let line = self.buf.get_line(y);
+        line.char_indices().nth(x).map_or(line.len(), |(i, _)| i)
+    }
+
+    pub fn cursor_col(&self) -> usize {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        self.col_at(self.char_index(cursor_y, cursor_x), cursor_y)
+    }
+
+    fn col_at(&self, x: usize, y: usize) -> usize {
+        let line: Vec<char> = self.buf.get_line(y).chars().collect();
+        self.cells_between(&line, 0, x) + self.cursor_cell_offset(&line, x)
+    }
+
+    pub fn col_to_x(&self, y: usize, col: usize) -> usize {
+        index_at_col(self.buf.get_line(y), col, self.options.tabstop)
+    }
+
+    fn cells_between(&self, line: &[char], start: usize, x: usize) -> usize {
+        self.cell_widths(line)[start..x.min(line.len())]
+            .iter()
+            .sum()
+    }
+
+    fn row_index(rows: &[ScreenRow], x: usize) -> usize {
+        rows.iter()
+            .position(|row| x < row.end)
+            .unwrap_or(rows.len() - 1)
+    }
+
+    fn rows_between(&self, from: usize, to: usize) -> usize {
+        (from..to).map(|y| self.screen_rows(y).len()).sum()
+    }
+
+    pub fn scroll_to_cursor_wrapped(&mut self, so: usize) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let height = self.view_height();
+        let cursor_x = self.char_index(cursor_y, cursor_x);
+        let row = Self::row_index(&self.screen_rows(cursor_y), cursor_x);
+
+        self.offset.x = 0;
+        if cursor_y < self.offset.y {
+            self.offset.y = cursor_y;
+        }
+
+        while self.offset.y > 0 && self.rows_between(self.offset.y, cursor_y) + row < so {
+            self.offset.y -= 1;
+        }
+
+        let mut below = self.screen_rows(cursor_y).len() - row - 1;
+        let mut y = cursor_y + 1;
+        while below < so && y < self.buf.line_count() {
+            below += self.screen_rows(y).len();
+            y += 1;
+        }
+        let below = below.min(so);
+
+        while self.offset.y < cursor_y
+            && self.rows_between(self.offset.y, cursor_y) + row + 1 + below > height
+        {
+            self.offset.y += 1;
+        }
+    }
+
+    pub fn last_visible_line(&self) -> usize {
+        let last_line = self.buf.line_count() - 1;
+
+        if !self.options.wrap {
+            return (self.offset.y + self.view_height() - 1).min(last_line);
+        }
+
+        let mut rows = 0;
+        let mut y = self.offset.y;
+        while y < last_line {
+            rows += self.screen_rows(y).len();
+            if rows >= self.view_height() {
+                break;
+            }
+            y += 1;
+        }
+
+        y
+    }
+
+    pub fn visible_rows(&self) -> Vec<(Option<usize>, String)> {
+        let height = self.view_height();
+        let mut visible = Vec::new();
+
+        for y in self.offset.y..self.buf.line_count() {
+            let line = self.buf.get_line(y);
+
+            if !self.options.wrap {
+                visible.push((Some(y + 1), self.clip_line(line)));
+            } else {
+                let chars: Vec<char> = line.chars().collect();
+                for (i, row) in self.screen_rows(y).iter().enumerate() {
+                    let number = if i == 0 { Some(y + 1) } else { None };
+                    visible.push((number, self.row_text(&chars, row)));
+                }
+            }
+
+            if visible.len() >= height {
+                break;
+            }
+        }
+
+        visible.truncate(height);
+        visible
+    }
+
+    // A tab repeats its position for each cell; prefixes and `<`/`>` are None
+    pub fn visible_positions(&self) -> Vec<(usize, Vec<Option<usize>>)> {
+        let height = self.view_height();
+        let mut visible = Vec::new();
+
+        for y in self.offset.y..self.buf.line_count() {
+            let chars: Vec<char> = self.buf.get_line(y).chars().collect();
+            let cells = self.cell_widths(&chars);
+            let expand = |range: std::ops::Range<usize>| {
+                range.flat_map(|x| std::iter::repeat_n(Some(x), cells[x]))
+            };
+
+            if !self.options.wrap {
+                let width = self.view_width();
+                let expanded: Vec<Option<usize>> = expand(0..chars.len()).collect();
+                let len = expanded.len();
+                let mut row: Vec<Option<usize>> = expanded
+                    .into_iter()
+                    .skip(self.offset.x)
+                    .take(width)
+                    .collect();
+                if self.offset.x > 0 && len > 0 {
+                    if row.is_empty() {
+                        row.push(None);
+                    } else {
+                        row[0] = None;
+                    }
+                }
+                if len > self.offset.x + width {
+                    row[width - 1] = None;
+                }
+                visible.push((y, row));
+            } else {
+                for row in self.screen_rows(y) {
+                    let mut positions = vec![None; row.prefix_width()];
+                    positions.extend(expand(row.start..row.end));
+                    visible.push((y, positions));
+                }
+            }
+
+            if visible.len() >= height {
+                break;
+            }
+        }
+
+        visible.truncate(height);
+        visible
+    }
+
+    pub fn visual_selection(&self) -> Option<TextRange> {
+        if self.get_mode() != EditorMode::Visual {
+            return None;
+        }
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let range =
+            TextRange::from_visual(&self.buf, self.visual_start, Vec2::new(cursor_x, cursor_y));
+        let (start, end) = (range.start, range.end);
+        let end_x =
+            self.char_index(end.y, end.x) + usize::from(end.x > self.buf.line_length(end.y));
+        Some(TextRange::chars(
+            Vec2::new(self.char_index(start.y, start.x), start.y),
+            Vec2::new(end_x, end.y),
+        ))
+    }
+
+    pub fn cursor_screen_pos(&self) -> (usize, usize) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        self.screen_pos(cursor_x, cursor_y).unwrap_or_default()
+    }
+
+    pub fn screen_pos(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let x = self.char_index(y, x);
+        if !self.options.wrap {
+            let col = self.col_at(x, y).checked_sub(self.offset.x)?;
+            return Some((col, y.checked_sub(self.offset.y)?));
+        }
+        if y < self.offset.y {
+            return None;
+        }
+
+        let line: Vec<char> = self.buf.get_line(y).chars().collect();
+        let rows = self.screen_rows(y);
+        let index = Self::row_index(&rows, x);
+        let row = &rows[index];
+        let col = row.prefix_width()
+            + self.cells_between(&line, row.start, x)
+            + self.cursor_cell_offset(&line, x);
+
+        Some((
+            col.min(self.view_width() - 1),
+            self.rows_between(self.offset.y, y) + index,
+        ))
+    }
+
+    fn move_screen_row(&mut self, down: bool) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+        let cursor_x = self.char_index(cursor_y, cursor_x);
+        let line: Vec<char> = self.buf.get_line(cursor_y).chars().collect();
+        let rows = self.screen_rows(cursor_y);
+        let index = Self::row_index(&rows, cursor_x);
+        let col =
+            rows[index].prefix_width() + self.cells_between(&line, rows[index].start, cursor_x);
+
+        let (y, target_rows, target) = if down {
+            if index + 1 < rows.len() {
+                (cursor_y, rows, index + 1)
+            } else if cursor_y + 1 < self.buf.line_count() {
+                (cursor_y + 1, self.screen_rows(cursor_y + 1), 0)
+            } else {
+                return;
+            }
+        } else if index > 0 {
+            (cursor_y, rows, index - 1)
+        } else if cursor_y > 0 {
+            let rows = self.screen_rows(cursor_y - 1);
+            let last = rows.len() - 1;
+            (cursor_y - 1, rows, last)
+        } else {
+            return;
+        };
+
+        let target_line: Vec<char> = self.buf.get_line(y).chars().collect();
+        let cells = self.cell_widths(&target_line);
+        let row = &target_rows[target];
+        let col = col.saturating_sub(row.prefix_width());
+        let mut x = row.start;
+        let mut used = 0;
+        while x < row.end && used + cells[x] <= col {
+            used += cells[x];
+            x += 1;
+        }
+        if target + 1 < target_rows.len() {
+            x = x.min(row.end - 1);
+        }
+
+        self.cursor.move_y_to(&self.buf, y);
+        self.cursor.move_x_to(&self.buf, self.byte_index(y, x));
+    }
+
+    pub fn cmd_screen_down(&mut self) {
+        self.move_screen_row(true);
+    }
+
+    pub fn cmd_screen_up(&mut self) {
+        self.move_screen_row(false);
+    }
+
+    pub fn cmd_screen_line_start(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+
+        let x = if self.options.wrap {
+            let rows = self.screen_rows(cursor_y);
+            let index = Self::row_index(&rows, self.char_index(cursor_y, cursor_x));
+            self.byte_index(cursor_y, rows[index].start)
+        } else {
+            self.col_to_x(cursor_y, self.offset.x)
+        };
+        self.cursor.move_x_to(&self.buf, x);
+    }
+
+    pub fn cmd_screen_line_end(&mut self) {
+        let (cursor_x, cursor_y) = self.cursor.get_display(&self.buf);
+
+        let x = if self.options.wrap {
+            let rows = self.screen_rows(cursor_y);
+            let index = Self::row_index(&rows, self.char_index(cursor_y, cursor_x));
+            let end = if index + 1 < rows.len() {
+                rows[index].end - 1
+            } else {
+                rows[index].end
+            };
+            self.byte_index(cursor_y, end)
+        } else {
+            self.col_to_x(cursor_y, self.offset.x + self.view_width() - 1)
+        };
+        self.cursor.move_x_to(&self.buf, x);
+    }
+
+    pub fn register_wrap_cmds(cmds: &mut EditorCommand) {
+        cmds.register("screen_down", Box::new(|editor| editor.cmd_screen_down()));
+        cmds.register("screen_up", Box::new(|editor| editor.cmd_screen_up()));
+        cmds.register(
+            "screen_line_start",
+            Box::new(|editor| editor.cmd_screen_line_start()),
+        );
+        cmds.register(
+            "screen_line_end",
+            Box::new(|editor| editor.cmd_screen_line_end()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(buf: &str) -> EditorState {
+        let mut state = EditorState::new(buf.to_string(), None);
+        state.resize(12, 7);
+        state.options.wrap = true;
+        state
+    }
+
+    fn ranges(rows: Vec<ScreenRow>) -> Vec<(usize, usize)> {
+        rows.iter().map(|row| (row.start, row.end)).collect()
+    }
+
+    #[test]
+    fn test_screen_rows() {
+        let mut state = state("aaaa bbbb cccc dddd");
+        assert_eq!(ranges(state.screen_rows(0)), vec![(0, 10), (10, 19)]);
+
+        state.options.linebreak = true;
+        assert_eq!(ranges(state.screen_rows(0)), vec![(0, 10), (10, 19)]);
+
+        state.options.showbreak = "> ".to_string();
+        let rows = state.screen_rows(0);
+        assert_eq!(ranges(rows.clone()), vec![(0, 10), (10, 15), (15, 19)]);
+        assert_eq!(rows[1].prefix, "> ");
+    }
+
+    #[test]
+    fn test_screen_rows_breakindent() {
+        let mut state = state("    aaaaaaaaaa");
+        state.options.breakindent = true;
+
+        let rows = state.screen_rows(0);
+        assert_eq!(ranges(rows.clone()), vec![(0, 10), (10, 14)]);
+        assert_eq!(rows[1].prefix, "    ");
+    }
+
+    #[test]
+    fn test_move_screen_row() {
+        let mut state = state("0123456789abcdefghij\nxyz");
+
+        state.cursor.move_x_to(&state.buf, 3);
+        state.cmd_screen_down();
+        assert_eq!(state.cursor.get_display(&state.buf), (13, 0));
+        state.cmd_screen_down();
+        assert_eq!(state.cursor.get_display(&state.buf), (3, 1));
+        state.cmd_screen_up();
+        assert_eq!(state.cursor.get_display(&state.buf), (13, 0));
+        assert_eq!(state.cursor_screen_pos(), (3, 1));
+    }
+
+    #[test]
+    fn test_move_screen_row_multibyte() {
+        let mut state = state("ééééééééééabc\nxyz");
+
+        state.cursor.move_x_to(&state.buf, "éé".len());
+        assert_eq!(state.cursor_screen_pos(), (2, 0));
+        state.cmd_screen_down();
+        assert_eq!(
+            state.cursor.get_display(&state.buf),
+            ("éééééééééé".len() + 2, 0)
+        );
+        state.cmd_screen_line_start();
+        assert_eq!(
+            state.cursor.get_display(&state.buf),
+            ("éééééééééé".len(), 0)
+        );
+        state.cmd_screen_up();
+        state.cmd_screen_line_end();
+        assert_eq!(state.cursor.get_display(&state.buf), ("ééééééééé".len(), 0));
+        assert_eq!(state.cursor_screen_pos(), (9, 0));
+    }
+
+    #[test]
+    fn test_cursor_on_tab() {
+        let mut state = state("a\tb");
+        state.options.tabstop = 4;
+
+        state.cursor.move_x_to(&state.buf, 1);
+        assert_eq!(state.cursor_screen_pos(), (3, 0));
+        state.set_mode(EditorMode::Insert);
+        assert_eq!(state.cursor_screen_pos(), (1, 0));
+
+        state.options.wrap = false;
+        state.cursor.move_x_to(&state.buf, 2);
+        assert_eq!(state.cursor_screen_pos(), (4, 0));
+        assert_eq!(state.visible_rows()[0].1, "a   b");
+    }
+
+    #[test]
+    fn test_scroll_to_cursor_wrapped() {
+        let line = "a".repeat(25);
+        let mut state = state(&[line.as_str(); 4].join("\n"));
+
+        state.cursor.move_y_to(&state.buf, 1);
+        state.scroll_to_cursor();
+        assert_eq!(state.offset.y, 0);
+
+        state.cursor.move_y_to(&state.buf, 2);
+        state.scroll_to_cursor();
+        assert_eq!(state.offset.y, 1);
+        assert_eq!(state.last_visible_line(), 2);
+    }
+}
@@ -3,6 +3,7 @@ use std::io::stdout;
 use anyhow::Result;
 use crossterm::{
     execute,
+    style::ResetColor,
     terminal::{
         disable_raw_mode, enable_raw_mode, size, EnterAlternateScreen, LeaveAlternateScreen,
     },
@@ -16,7 +17,7 @@ pub fn init_terminal() -> Result<()> {
 }
 
 pub fn cleanup_terminal() -> Result<()> {
-    execute!(stdout(), LeaveAlternateScreen)?;
+    execute!(stdout(), ResetColor, LeaveAlternateScreen)?;
     disable_raw_mode()?;
 
     Ok(())
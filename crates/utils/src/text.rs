@@ -2,6 +2,57 @@ pub fn lines(s: String) -> Vec<String> {
     s.split("\n").map(|s| s.to_string()).collect()
 }
 
+pub fn indent_len(line: &str) -> usize {
+    line.chars().take_while(|c| *c == ' ' || *c == '\t').count()
+}
+
+pub fn display_width(s: &str, tabstop: usize) -> usize {
+    s.chars().fold(0, |width, c| match c {
+        '\t' => (width / tabstop + 1) * tabstop,
+        _ => width + 1,
+    })
+}
+
+pub fn expand_tabs(s: &str, tabstop: usize) -> String {
+    let mut expanded = String::new();
+    for c in s.chars() {
+        match c {
+            '\t' => {
+                let width = tabstop - expanded.chars().count() % tabstop;
+                expanded.push_str(&" ".repeat(width));
+            }
+            _ => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+pub fn index_at_col(s: &str, col: usize, tabstop: usize) -> usize {
+    let mut width = 0;
+    for (index, c) in s.char_indices() {
+        width = match c {
+            '\t' => (width / tabstop + 1) * tabstop,
+            _ => width + 1,
+        };
+        if width > col {
+            return index;
+        }
+    }
+    s.len()
+}
+
+pub fn indent_width(line: &str, tabstop: usize) -> usize {
+    display_width(&line[..indent_len(line)], tabstop)
+}
+
+pub fn make_indent(width: usize, tabstop: usize, expandtab: bool) -> String {
+    if expandtab {
+        " ".repeat(width)
+    } else {
+        "\t".repeat(width / tabstop) + &" ".repeat(width % tabstop)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -16,4 +67,18 @@ mod tests {
         ];
         assert_eq!(lines(input), expected);
     }
+
+    #[test]
+    fn test_indent() {
+        assert_eq!(indent_len("\t  foo"), 3);
+        assert_eq!(display_width("ab\tc", 4), 5);
+        assert_eq!(expand_tabs("ab\tc\td", 4), "ab  c   d");
+        assert_eq!(index_at_col("ab\tc", 3, 4), 2);
+        assert_eq!(index_at_col("ab\tc", 4, 4), 3);
+        assert_eq!(index_at_col("ab\tc", 9, 4), 4);
+        assert_eq!(indent_width("\t  foo", 8), 10);
+        assert_eq!(indent_width("  \tfoo", 4), 4);
+        assert_eq!(make_indent(10, 8, false), "\t  ");
+        assert_eq!(make_indent(10, 8, true), " ".repeat(10));
+    }
 }
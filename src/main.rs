@@ -10,13 +10,15 @@ use crossterm::{
     terminal::{Clear, ClearType},
 };
 use editor::Editor;
-use utils::cli::{cleanup_terminal, init_terminal};
+use utils::cli::{cleanup_terminal, init_terminal, terminal_size};
 
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Args {
     #[arg()]
-    path: String,
+    paths: Vec<String>,
+    #[arg(short = 'p')]
+    tabs: bool,
 }
 
 fn main() -> Result<()> {
@@ -28,8 +30,13 @@ fn main() -> Result<()> {
     write!(stdout, "{}", Clear(ClearType::All))?;
     stdout.flush().unwrap();
 
-    let path = PathBuf::from(args.path);
-    let mut editor = Editor::open(path.clone());
+    let mut editor = if args.paths.is_empty() {
+        Editor::start()
+    } else {
+        Editor::open(args.paths.iter().map(PathBuf::from).collect(), args.tabs)
+    };
+    let (term_w, term_h) = terminal_size()?;
+    editor.resize(term_w, term_h);
     editor.draw(&mut stdout)?;
 
     loop {
@@ -41,6 +48,7 @@ fn main() -> Result<()> {
     }
 
     cleanup_terminal()?;
+    let _ = editor.save_recent_files();
 
     Ok(())
 }